@@ -0,0 +1,220 @@
+// agent_coordinator/src/agents.rs
+//! Llamadas request/reply a los agentes LLM y de herramientas.
+//!
+//! Todas las peticiones llevan la cabecera `Task-Id` para que los agentes puedan
+//! abortarlas si la tarea se cancela, y se reintentan con backoff si el error es
+//! transitorio (ver `common::messaging::request`). Al Agente LLM se le habla con el
+//! contrato protobuf `LlmRequest` / `LlmResponse`; los prompts salen de plantillas
+//! versionadas (`common::prompts`) y su versión viaja en `LlmRequest.prompt_version`.
+
+use anyhow::{anyhow, Context, Result};
+use async_nats::{Client, HeaderMap};
+use bytes::Bytes;
+use common::messaging::messages::{LlmRequest, LlmResponse};
+use common::messaging::request::{self, RetryPolicy};
+use common::messaging::{
+    CONTENT_TYPE_HEADER, NATS_LLM_SUBJECT, NATS_TOOL_SUBJECT, PROTOBUF_CONTENT_TYPE, TASK_ID_HEADER,
+};
+use common::prompts::RenderedPrompt;
+use prost::Message as _;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::env;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+/// Timeout por defecto de cada intento (la generación LLM puede tardar).
+const DEFAULT_REQUEST_TIMEOUT_SECS: u64 = 300;
+
+/// Intentos por defecto de [`call_llm_checked`].
+const DEFAULT_CHECK_ATTEMPTS: usize = 3;
+
+/// Respuesta rechazada (JSON) y sus errores, para volver a pedirla al LLM.
+pub type Feedback = (String, Vec<String>);
+
+/// Ejecuta un comando del Agente de Herramientas y devuelve la respuesta en texto.
+///
+/// `args` debe ser un objeto JSON; se le añade el campo `cmd`.
+pub async fn call_tool(client: &Client, task_id: &str, cmd: &str, args: Value) -> Result<String> {
+    let mut payload = match args {
+        Value::Object(map) => map,
+        _ => {
+            return Err(anyhow!(
+                "los argumentos de '{cmd}' deben ser un objeto JSON"
+            ))
+        }
+    };
+    payload.insert("cmd".to_string(), Value::String(cmd.to_string()));
+
+    request(
+        client,
+        task_id,
+        NATS_TOOL_SUBJECT,
+        serde_json::to_vec(&payload)?.into(),
+    )
+    .await
+}
+
+/// Igual que [`call_tool`], pero interpreta la respuesta como JSON.
+pub async fn call_tool_json(
+    client: &Client,
+    task_id: &str,
+    cmd: &str,
+    args: Value,
+) -> Result<Value> {
+    let text = call_tool(client, task_id, cmd, args).await?;
+    Ok(serde_json::from_str(&text)?)
+}
+
+/// Envía un prompt al Agente LLM y devuelve el texto generado.
+pub async fn call_llm(client: &Client, task_id: &str, prompt: &RenderedPrompt) -> Result<String> {
+    let req = LlmRequest {
+        user_prompt: prompt.text.clone(),
+        prompt_version: prompt.version.clone(),
+        ..Default::default()
+    };
+    Ok(llm_request(client, task_id, req).await?.content)
+}
+
+/// Envía un prompt al Agente LLM pidiendo JSON que cumpla `schema`. El agente valida la
+/// respuesta y pide correcciones al modelo; si no lo consigue, falla con
+/// `invalid_output` en lugar de devolver un JSON roto.
+pub async fn call_llm_json(
+    client: &Client,
+    task_id: &str,
+    prompt: &RenderedPrompt,
+    schema: &Value,
+) -> Result<Value> {
+    let req = LlmRequest {
+        user_prompt: prompt.text.clone(),
+        prompt_version: prompt.version.clone(),
+        json_schema: schema.to_string(),
+        ..Default::default()
+    };
+    let res = llm_request(client, task_id, req).await?;
+    if res.attempts > 1 {
+        debug!(
+            "Tarea {task_id}: LLM {} necesitó {} intentos para cumplir el esquema",
+            res.request_id, res.attempts
+        );
+    }
+    Ok(serde_json::from_str(&res.content)?)
+}
+
+/// Igual que [`call_llm_json`], pero además convierte la respuesta en `T` y la pasa por
+/// `check`, que devuelve los problemas de contenido que el esquema no puede expresar
+/// (p. ej. citas que no están en el CV). Si hay alguno, vuelve a pedirla con
+/// `prompt(Some(&feedback))`, como mucho tantas veces como diga la variable de entorno
+/// `attempts_var` (3 por defecto). `what` describe la petición en errores y avisos.
+pub async fn call_llm_checked<T, P, C>(
+    client: &Client,
+    task_id: &str,
+    what: &str,
+    attempts_var: &str,
+    schema: &Value,
+    prompt: P,
+    check: C,
+) -> Result<T>
+where
+    T: DeserializeOwned,
+    P: Fn(Option<&Feedback>) -> Result<RenderedPrompt>,
+    C: Fn(&T) -> Vec<String>,
+{
+    let max_attempts = env::var(attempts_var)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(DEFAULT_CHECK_ATTEMPTS);
+
+    let mut feedback: Option<Feedback> = None;
+    for attempt in 1..=max_attempts {
+        let json = call_llm_json(client, task_id, &prompt(feedback.as_ref())?, schema)
+            .await
+            .with_context(|| format!("{what} con el LLM"))?;
+        let raw = json.to_string();
+
+        let errors = match serde_json::from_value::<T>(json) {
+            Ok(value) => {
+                let errors = check(&value);
+                if errors.is_empty() {
+                    return Ok(value);
+                }
+                errors
+            }
+            Err(e) => vec![format!("el JSON no tiene la forma pedida: {e}")],
+        };
+        warn!(
+            "Tarea {task_id}: {what}: respuesta rechazada (intento {attempt}/{max_attempts}): {}",
+            errors.join("; ")
+        );
+        feedback = Some((raw, errors));
+    }
+
+    let errors = feedback.map(|(_, e)| e.join("; ")).unwrap_or_default();
+    Err(anyhow!(
+        "{what}: el LLM no produjo una respuesta válida tras {max_attempts} intentos: {errors}"
+    ))
+}
+
+async fn llm_request(client: &Client, task_id: &str, mut req: LlmRequest) -> Result<LlmResponse> {
+    req.request_id = Uuid::new_v4().to_string();
+    let mut headers = task_headers(task_id);
+    headers.insert(CONTENT_TYPE_HEADER, PROTOBUF_CONTENT_TYPE);
+    let reply = request::request(
+        client,
+        NATS_LLM_SUBJECT,
+        headers,
+        req.encode_to_vec().into(),
+        policy(),
+    )
+    .await?;
+    let res = LlmResponse::decode(reply)?;
+    if !res.prompt_versions.is_empty() {
+        info!(
+            "Tarea {task_id}: LLM {} con las plantillas {}",
+            res.request_id,
+            res.prompt_versions.join(", ")
+        );
+    }
+    if let Some(usage) = &res.usage {
+        debug!(
+            "Tarea {task_id}: LLM {} ({}) en {} ms, {} tokens ({} de prompt)",
+            res.request_id, res.model, res.duration_ms, usage.total_tokens, usage.prompt_tokens
+        );
+    }
+    Ok(res)
+}
+
+/// Request/reply con la cabecera `Task-Id`, timeout por intento y reintentos.
+async fn request(client: &Client, task_id: &str, subject: &str, payload: Bytes) -> Result<String> {
+    let reply = request::request(client, subject, task_headers(task_id), payload, policy()).await?;
+    Ok(String::from_utf8(reply.to_vec())?)
+}
+
+fn task_headers(task_id: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(TASK_ID_HEADER, task_id);
+    headers
+}
+
+/// Política de reintentos (`AGENT_REQUEST_*`) con el timeout de
+/// `COORDINATOR_REQUEST_TIMEOUT_SECS`.
+fn policy() -> &'static RetryPolicy {
+    static POLICY: OnceLock<RetryPolicy> = OnceLock::new();
+    POLICY.get_or_init(|| {
+        let timeout = env::var("COORDINATOR_REQUEST_TIMEOUT_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_REQUEST_TIMEOUT_SECS);
+        RetryPolicy::from_env().with_timeout(Duration::from_secs(timeout))
+    })
+}
+
+/// Recorta el primer objeto JSON (`{...}`) de una respuesta libre del LLM.
+pub fn extract_json_object(text: &str) -> Option<&str> {
+    let start = text.find('{')?;
+    let end = text.rfind('}')?;
+    (start < end).then(|| &text[start..=end])
+}
@@ -0,0 +1,104 @@
+// agent_coordinator/src/cancel.rs
+//! Cancelación cooperativa de tareas (`agents.coordinator.cancel`).
+//!
+//! Si la tarea está en marcha se aborta su paso en curso; si aún está en la cola se
+//! marca como cancelada para descartarla al salir. En ambos casos se reenvía el
+//! `task_id` a `agents.llm.cancel` y `agents.tool.cancel` para que los agentes
+//! abandonen la generación o la herramienta que estén ejecutando para esa tarea. Un
+//! `task_id` que no está en el KV ni en la cola se rechaza.
+
+use anyhow::Result;
+use async_nats::jetstream;
+use async_nats::Client;
+use bytes::Bytes;
+use common::cancel::InFlight;
+use common::kv::parse_id;
+use common::messaging::{
+    NATS_COORDINATOR_CANCEL_SUBJECT, NATS_LLM_CANCEL_SUBJECT, NATS_TOOL_CANCEL_SUBJECT,
+};
+use common::task::TaskStatus;
+use futures::StreamExt;
+use tracing::{error, info};
+
+use crate::queue;
+use crate::status::publish_status;
+use crate::store::{validate_task_id, TaskStore};
+
+pub const CANCEL_REASON: &str = "Cancelada a petición del usuario";
+
+/// Atiende peticiones de cancelación. El payload es el `task_id` en texto plano o
+/// un objeto JSON `{"task_id": "..."}`.
+pub async fn handle_cancellations(
+    client: Client,
+    js: jetstream::Context,
+    store: TaskStore,
+    inflight: InFlight,
+) -> Result<()> {
+    let mut sub = client.subscribe(NATS_COORDINATOR_CANCEL_SUBJECT).await?;
+    while let Some(msg) = sub.next().await {
+        let response = match parse_id(&msg.payload, "task_id") {
+            Some(task_id) => cancel(&client, &js, &store, &inflight, &task_id).await,
+            None => "ERROR: falta el task_id a cancelar".to_string(),
+        };
+        if let Some(reply) = msg.reply {
+            client
+                .publish(reply, Bytes::from(response.into_bytes()))
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+async fn cancel(
+    client: &Client,
+    js: &jetstream::Context,
+    store: &TaskStore,
+    inflight: &InFlight,
+    task_id: &str,
+) -> String {
+    if let Err(e) = validate_task_id(task_id) {
+        return format!("ERROR: {e}");
+    }
+    // Sin registro en el KV, la tarea solo puede estar en la cola esperando a empezar
+    let known = match store.load(task_id).await {
+        Ok(Some(_)) => Ok(true),
+        Ok(None) => queue::is_queued(js, task_id).await,
+        Err(e) => Err(e),
+    };
+    match known {
+        Ok(true) => {}
+        Ok(false) => return format!("ERROR: la tarea '{task_id}' no existe"),
+        Err(e) => return format!("ERROR: no se pudo cancelar la tarea '{task_id}': {e}"),
+    }
+
+    // Los agentes abortan lo que tengan en curso para la tarea
+    for subject in [NATS_LLM_CANCEL_SUBJECT, NATS_TOOL_CANCEL_SUBJECT] {
+        if let Err(e) = client
+            .publish(subject, Bytes::from(task_id.to_string()))
+            .await
+        {
+            error!("No se pudo propagar la cancelación de {task_id} a {subject}: {e}");
+        }
+    }
+
+    if inflight.cancel(task_id) > 0 {
+        // El worker de la cola publica el estado `Cancelled` al ver el aborto
+        info!("Tarea {task_id}: cancelación del paso en curso");
+        return "OK".to_string();
+    }
+
+    match store.mark_cancelled(task_id, CANCEL_REASON).await {
+        Ok(true) => {
+            info!("Tarea {task_id}: cancelada antes de empezar");
+            publish_status(
+                client,
+                task_id,
+                TaskStatus::Cancelled(CANCEL_REASON.to_string()),
+            )
+            .await;
+            "OK".to_string()
+        }
+        Ok(false) => format!("ERROR: la tarea '{task_id}' ya había terminado"),
+        Err(e) => format!("ERROR: no se pudo cancelar la tarea '{task_id}': {e}"),
+    }
+}
@@ -0,0 +1,418 @@
+// agent_coordinator/src/cover_letters.rs
+//! Cartas de presentación para las ofertas preseleccionadas (las de mayor puntuación).
+//!
+//! Para cada oferta se descarga su página con `web_page` y el LLM redacta la carta a
+//! partir del CV y de la oferta, en JSON con el esquema de `LetterDraft`. Cada párrafo,
+//! salvo el saludo y la despedida, debe citar literalmente los fragmentos del CV en los
+//! que se apoya; si falta alguna cita o no aparece en el CV se vuelve a pedir con los
+//! errores, como mucho `COVER_LETTER_MAX_ATTEMPTS` veces. La carta se escribe en Markdown
+//! (con las citas como notas) con `file_writer` y en DOCX con `docx_writer`.
+
+use anyhow::{Context, Result};
+use async_nats::Client;
+use common::offers::text::{mentions, normalize};
+use common::offers::{CoverLetter, JobOffer};
+use common::prompts::{self, RenderedPrompt};
+use common::task::CoverLetterOptions;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::Path;
+use tracing::warn;
+
+use crate::agents::{call_llm_checked, call_tool_json, Feedback};
+use crate::pipeline::{offer_page, output_dir};
+use crate::scoring::shortlist;
+use crate::status::progress;
+use crate::store::TaskRun;
+
+/// Palabras como mucho del saludo y la despedida, los únicos párrafos sin citas.
+const SALUTATION_MAX_WORDS: usize = 15;
+
+/// Palabras mínimas de cada cita: una palabra suelta ("Rust") no respalda nada.
+const MIN_QUOTE_WORDS: usize = 3;
+
+/// Carta tal y como la devuelve el LLM.
+#[derive(Serialize, Deserialize, Debug)]
+struct LetterDraft {
+    paragraphs: Vec<LetterParagraph>,
+}
+
+impl LetterDraft {
+    fn json_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "paragraphs": {
+                    "type": "array",
+                    "minItems": 1,
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "text": { "type": "string", "minLength": 1 },
+                            "evidence": { "type": "array", "items": { "type": "string" } }
+                        },
+                        "required": ["text", "evidence"]
+                    }
+                }
+            },
+            "required": ["paragraphs"]
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct LetterParagraph {
+    text: String,
+    /// Fragmentos literales del CV que respaldan lo que afirma el párrafo.
+    #[serde(default)]
+    evidence: Vec<String>,
+}
+
+/// Escribe la carta de las mejores ofertas (`options.top`, con al menos
+/// `options.min_score`) y la anota en cada oferta. Una carta que falla no detiene la
+/// tarea: queda como aviso en el informe.
+pub async fn write_cover_letters(
+    client: &Client,
+    task_id: &str,
+    run: &mut TaskRun,
+    cv_text: &str,
+    options: &CoverLetterOptions,
+    offers: &mut [JobOffer],
+) -> Result<()> {
+    let selected = shortlist(offers, options.top, options.min_score);
+    let dir = Path::new(&output_dir())
+        .join(format!("cartas_{task_id}"))
+        .to_string_lossy()
+        .into_owned();
+
+    let total = selected.len();
+    for (n, i) in selected.into_iter().enumerate() {
+        progress(
+            client,
+            task_id,
+            &format!("Escribiendo carta de presentación {}/{total}", n + 1),
+        )
+        .await;
+        let offer = &offers[i];
+        let page = offer_page(client, task_id, run, i, offer).await;
+        let stem = Path::new(&dir)
+            .join(format!("{:02}_{}", n + 1, slug(&offer.title)))
+            .to_string_lossy()
+            .into_owned();
+        let letter = run
+            .step(
+                &format!("cover_letter:{n}"),
+                write_letter(client, task_id, cv_text, options, offer, &page, &stem),
+            )
+            .await;
+        match letter {
+            Ok(letter) => offers[i].cover_letter = Some(letter),
+            Err(e) => {
+                warn!("Tarea {task_id}: sin carta para '{}': {e:#}", offer.title);
+                let warning = format!("carta de presentación de '{}': {e:#}", offer.title);
+                run.update_report(|report| {
+                    if !report.warnings.contains(&warning) {
+                        report.warnings.push(warning);
+                    }
+                })
+                .await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn write_letter(
+    client: &Client,
+    task_id: &str,
+    cv_text: &str,
+    options: &CoverLetterOptions,
+    offer: &JobOffer,
+    page: &str,
+    stem: &str,
+) -> Result<CoverLetter> {
+    let draft = draft_letter(client, task_id, cv_text, options, offer, page).await?;
+
+    let markdown = format!("{stem}.md");
+    let docx = format!("{stem}.docx");
+    call_tool_json(
+        client,
+        task_id,
+        "file_writer",
+        json!({ "path": markdown, "content": render_markdown(offer, &draft) }),
+    )
+    .await
+    .context("escritura de la carta en Markdown")?;
+    let paragraphs: Vec<&str> = draft.paragraphs.iter().map(|p| p.text.as_str()).collect();
+    call_tool_json(
+        client,
+        task_id,
+        "docx_writer",
+        json!({ "path": docx, "paragraphs": paragraphs }),
+    )
+    .await
+    .context("escritura de la carta en DOCX")?;
+    Ok(CoverLetter { markdown, docx })
+}
+
+/// Pide la carta al LLM hasta que todas sus citas aparecen en el CV.
+async fn draft_letter(
+    client: &Client,
+    task_id: &str,
+    cv_text: &str,
+    options: &CoverLetterOptions,
+    offer: &JobOffer,
+    page: &str,
+) -> Result<LetterDraft> {
+    call_llm_checked(
+        client,
+        task_id,
+        "redacción de la carta",
+        "COVER_LETTER_MAX_ATTEMPTS",
+        &LetterDraft::json_schema(),
+        |feedback| letter_prompt(task_id, cv_text, options, offer, page, feedback),
+        |draft| validate(draft, cv_text, options),
+    )
+    .await
+}
+
+fn letter_prompt(
+    task_id: &str,
+    cv_text: &str,
+    options: &CoverLetterOptions,
+    offer: &JobOffer,
+    page: &str,
+    feedback: Option<&Feedback>,
+) -> Result<RenderedPrompt> {
+    let (previous, errors) = feedback.cloned().unzip();
+    Ok(prompts::render(
+        "cover_letter",
+        task_id,
+        &json!({
+            "language": options.language,
+            "tone": options.tone,
+            "max_words": options.max_words,
+            "title": offer.title,
+            "company": offer.company.trim(),
+            "location": offer.location.trim(),
+            "url": offer.source_url,
+            "page": page,
+            "cv_text": cv_text,
+            "previous": previous,
+            "errors": errors,
+        }),
+    )?)
+}
+
+/// Problemas de la carta (vacío si es válida): párrafos sin citas (salvo un saludo y una
+/// despedida breves, primero y último), citas que no están en el CV, párrafos vacíos o
+/// longitud excesiva.
+fn validate(draft: &LetterDraft, cv_text: &str, options: &CoverLetterOptions) -> Vec<String> {
+    let mut errors = Vec::new();
+    if draft.paragraphs.iter().all(|p| p.text.trim().is_empty()) {
+        return vec!["la carta está vacía".to_string()];
+    }
+    if draft.paragraphs.iter().any(|p| p.text.trim().is_empty()) {
+        errors.push("hay un párrafo vacío".to_string());
+    }
+    let last = draft.paragraphs.len() - 1;
+    for (i, paragraph) in draft.paragraphs.iter().enumerate() {
+        let short = paragraph.text.split_whitespace().count() <= SALUTATION_MAX_WORDS;
+        let salutation = last > 0 && (i == 0 || i == last) && short;
+        if paragraph.evidence.is_empty() && !salutation {
+            errors.push(format!("el párrafo {} no cita el CV en 'evidence'", i + 1));
+        }
+    }
+
+    let cv = normalize(cv_text);
+    for quote in draft.paragraphs.iter().flat_map(|p| &p.evidence) {
+        let normalized = normalize(quote);
+        if normalized.split(' ').count() < MIN_QUOTE_WORDS {
+            errors.push(format!(
+                "la cita «{quote}» es demasiado corta: copia al menos {MIN_QUOTE_WORDS} palabras seguidas del CV"
+            ));
+        } else if !mentions(&cv, &normalized) {
+            errors.push(format!(
+                "la cita «{quote}» no aparece literalmente en el CV"
+            ));
+        }
+    }
+
+    let words: usize = draft
+        .paragraphs
+        .iter()
+        .map(|p| p.text.split_whitespace().count())
+        .sum();
+    // Margen del 25 % sobre la longitud pedida
+    if words > options.max_words + options.max_words / 4 {
+        errors.push(format!(
+            "la carta tiene {words} palabras; el máximo es {}",
+            options.max_words
+        ));
+    }
+    errors
+}
+
+/// Carta en Markdown con una nota por párrafo que cita los fragmentos del CV.
+fn render_markdown(offer: &JobOffer, draft: &LetterDraft) -> String {
+    let mut md = format!("# Carta de presentación: {}\n\n", offer.title.trim());
+    if !offer.company.trim().is_empty() {
+        md.push_str(&format!("**Empresa:** {}  \n", offer.company.trim()));
+    }
+    md.push_str(&format!("**Oferta:** <{}>\n\n", offer.source_url));
+
+    let mut notes = Vec::new();
+    for paragraph in &draft.paragraphs {
+        md.push_str(paragraph.text.trim());
+        if !paragraph.evidence.is_empty() {
+            notes.push(&paragraph.evidence);
+            md.push_str(&format!("[^{}]", notes.len()));
+        }
+        md.push_str("\n\n");
+    }
+
+    if !notes.is_empty() {
+        md.push_str("---\n\nFragmentos del CV en los que se apoya cada párrafo:\n\n");
+        for (n, evidence) in notes.iter().enumerate() {
+            let quotes: Vec<String> = evidence.iter().map(|q| format!("«{}»", q.trim())).collect();
+            md.push_str(&format!("[^{}]: {}\n", n + 1, quotes.join(" · ")));
+        }
+    }
+    md
+}
+
+/// Nombre de archivo a partir del título: minúsculas, sin tildes y con guiones.
+fn slug(title: &str) -> String {
+    let slug: String = normalize(title)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let slug: Vec<&str> = slug.split('-').filter(|w| !w.is_empty()).collect();
+    let slug = slug.join("-");
+    let slug: String = slug.chars().take(40).collect();
+    if slug.is_empty() {
+        "oferta".to_string()
+    } else {
+        slug.trim_end_matches('-').to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CV: &str = "Desarrollador backend con 5 años de experiencia en Rust y PostgreSQL.";
+
+    fn options() -> CoverLetterOptions {
+        serde_json::from_str("{}").unwrap()
+    }
+
+    fn paragraph(text: &str, evidence: &[&str]) -> LetterParagraph {
+        LetterParagraph {
+            text: text.to_string(),
+            evidence: evidence.iter().map(|e| e.to_string()).collect(),
+        }
+    }
+
+    fn draft(paragraphs: Vec<LetterParagraph>) -> LetterDraft {
+        LetterDraft { paragraphs }
+    }
+
+    #[test]
+    fn greeting_and_sign_off_need_no_evidence() {
+        let letter = draft(vec![
+            paragraph("Estimado equipo de Acme:", &[]),
+            paragraph(
+                "Llevo cinco años desarrollando servicios en Rust.",
+                &["5 años de experiencia en Rust"],
+            ),
+            paragraph("Un saludo cordial, Ana García", &[]),
+        ]);
+        assert!(validate(&letter, CV, &options()).is_empty());
+    }
+
+    #[test]
+    fn every_body_paragraph_needs_evidence() {
+        let letter = draft(vec![
+            paragraph("Estimado equipo de Acme:", &[]),
+            paragraph(
+                "Llevo cinco años desarrollando servicios en Rust.",
+                &["5 años de experiencia en Rust"],
+            ),
+            paragraph("Además domino PostgreSQL y Kubernetes.", &[]),
+            paragraph("Un saludo cordial, Ana García", &[]),
+        ]);
+        assert_eq!(
+            validate(&letter, CV, &options()),
+            vec!["el párrafo 3 no cita el CV en 'evidence'"]
+        );
+    }
+
+    #[test]
+    fn long_first_or_last_paragraphs_are_body() {
+        let long = "Me dirijo a ustedes porque su oferta encaja con mi experiencia como \
+                    desarrollador backend y con lo que busco en mi próximo puesto.";
+        let letter = draft(vec![
+            paragraph(long, &[]),
+            paragraph("Un saludo cordial, Ana García", &[]),
+        ]);
+        assert_eq!(
+            validate(&letter, CV, &options()),
+            vec!["el párrafo 1 no cita el CV en 'evidence'"]
+        );
+
+        let single = draft(vec![paragraph("Hola.", &[])]);
+        assert_eq!(
+            validate(&single, CV, &options()),
+            vec!["el párrafo 1 no cita el CV en 'evidence'"]
+        );
+    }
+
+    #[test]
+    fn quotes_must_appear_in_the_cv() {
+        let letter = draft(vec![paragraph(
+            "Tengo experiencia con Kubernetes.",
+            &["experiencia en Kubernetes"],
+        )]);
+        assert_eq!(
+            validate(&letter, CV, &options()),
+            vec!["la cita «experiencia en Kubernetes» no aparece literalmente en el CV"]
+        );
+    }
+
+    #[test]
+    fn quotes_need_whole_words_and_a_minimum_length() {
+        let short = draft(vec![paragraph("Domino Rust.", &["Rust"])]);
+        assert_eq!(
+            validate(&short, CV, &options()),
+            vec!["la cita «Rust» es demasiado corta: copia al menos 3 palabras seguidas del CV"]
+        );
+
+        let partial = draft(vec![paragraph(
+            "Conozco bien Postgres.",
+            &["experiencia en Rust y Postgre"],
+        )]);
+        assert_eq!(
+            validate(&partial, CV, &options()),
+            vec!["la cita «experiencia en Rust y Postgre» no aparece literalmente en el CV"]
+        );
+
+        let exact = draft(vec![paragraph(
+            "Conozco bien Postgres.",
+            &["Rust y PostgreSQL."],
+        )]);
+        assert!(validate(&exact, CV, &options()).is_empty());
+    }
+
+    #[test]
+    fn empty_letters_are_rejected() {
+        assert_eq!(
+            validate(&draft(vec![]), CV, &options()),
+            vec!["la carta está vacía"]
+        );
+        assert_eq!(
+            validate(&draft(vec![paragraph("  ", &[])]), CV, &options()),
+            vec!["la carta está vacía"]
+        );
+    }
+}
@@ -0,0 +1,45 @@
+// agent_coordinator/src/cv_profile.rs
+//! Extracción del `CvProfile` estructurado a partir del texto del CV con el Agente LLM.
+//!
+//! La respuesta se valida en dos niveles: la forma del JSON la comprueba el Agente LLM
+//! contra `CvProfile::json_schema` (y la repara él mismo), y aquí las reglas de contenido
+//! (`CvProfile::validate`). Si estas fallan, se vuelve a pedir al LLM con los errores,
+//! como mucho `CV_PROFILE_MAX_ATTEMPTS` veces. El prompt es la plantilla `cv_profile`.
+
+use anyhow::Result;
+use async_nats::Client;
+use common::cv::CvProfile;
+use common::prompts::{self, RenderedPrompt};
+use serde_json::json;
+
+use crate::agents::{call_llm_checked, Feedback};
+
+pub async fn extract_cv_profile(
+    client: &Client,
+    task_id: &str,
+    cv_text: &str,
+) -> Result<CvProfile> {
+    call_llm_checked(
+        client,
+        task_id,
+        "extracción del perfil",
+        "CV_PROFILE_MAX_ATTEMPTS",
+        &CvProfile::json_schema(),
+        |feedback| profile_prompt(task_id, cv_text, feedback),
+        CvProfile::validate,
+    )
+    .await
+}
+
+fn profile_prompt(
+    task_id: &str,
+    cv_text: &str,
+    feedback: Option<&Feedback>,
+) -> Result<RenderedPrompt> {
+    let (previous, errors) = feedback.cloned().unzip();
+    Ok(prompts::render(
+        "cv_profile",
+        task_id,
+        &json!({ "cv_text": cv_text, "previous": previous, "errors": errors }),
+    )?)
+}
@@ -0,0 +1,299 @@
+// agent_coordinator/src/dedup.rs
+//! Deduplicación de ofertas encontradas por varias consultas o en varios portales.
+//!
+//! Dos ofertas son la misma si su URL canónica coincide (sin parámetros de seguimiento
+//! ni redirecciones como `duckduckgo.com/l/?uddg=`) o si ambas tienen la misma empresa y
+//! sus títulos normalizados se parecen lo suficiente. Sin empresa (los resultados de
+//! `web_search` no la traen) solo cuenta la URL: dos "Desarrollador Backend" de empresas
+//! distintas no se fusionan. La oferta resultante conserva en `sources` todas las URLs de
+//! las que procede.
+
+use common::offers::text::normalize;
+use common::offers::JobOffer;
+use std::collections::HashSet;
+use url::Url;
+
+/// Similitud mínima (Dice sobre palabras) de título + empresa para fusionar dos ofertas.
+const SIMILARITY_THRESHOLD: f64 = 0.8;
+
+/// Parámetros de query que solo sirven para seguimiento (además de los `utm_*`).
+const TRACKING_PARAMS: &[&str] = &[
+    "gclid",
+    "fbclid",
+    "msclkid",
+    "dclid",
+    "yclid",
+    "igshid",
+    "mc_cid",
+    "mc_eid",
+    "_hsenc",
+    "_hsmi",
+    "ref",
+    "ref_src",
+    "refid",
+    "trk",
+    "trackingid",
+    "src",
+    "rut",
+    "sid",
+];
+
+/// Palabras que no distinguen una oferta de otra (portales y relleno de los títulos).
+const NOISE_WORDS: &[&str] = &[
+    "linkedin",
+    "indeed",
+    "infojobs",
+    "glassdoor",
+    "tecnoempleo",
+    "computrabajo",
+    "jobatus",
+    "jooble",
+    "empleo",
+    "empleos",
+    "oferta",
+    "ofertas",
+    "trabajo",
+    "job",
+    "jobs",
+    "hiring",
+    "com",
+    "es",
+    "www",
+];
+
+/// Fusiona las ofertas duplicadas manteniendo el orden de aparición.
+pub fn dedup_offers(offers: Vec<JobOffer>) -> Vec<JobOffer> {
+    let mut merged: Vec<(JobOffer, HashSet<String>)> = Vec::new();
+    for mut offer in offers {
+        offer.source_url = canonical_url(&offer.source_url);
+        let words = words(&offer);
+
+        match merged
+            .iter_mut()
+            .find(|(kept, kept_words)| same_offer(kept, kept_words, &offer, &words))
+        {
+            Some((kept, _)) => merge_into(kept, offer),
+            None => {
+                if offer.sources.is_empty() && !offer.source_url.is_empty() {
+                    offer.sources.push(offer.source_url.clone());
+                }
+                merged.push((offer, words));
+            }
+        }
+    }
+    merged.into_iter().map(|(offer, _)| offer).collect()
+}
+
+fn same_offer(
+    kept: &JobOffer,
+    kept_words: &HashSet<String>,
+    offer: &JobOffer,
+    words: &HashSet<String>,
+) -> bool {
+    if !offer.source_url.is_empty() && kept.sources.contains(&offer.source_url) {
+        return true;
+    }
+    let both_companies = !kept.company.trim().is_empty() && !offer.company.trim().is_empty();
+    if !both_companies || normalize(&kept.company) != normalize(&offer.company) {
+        return false;
+    }
+    dice(kept_words, words) >= SIMILARITY_THRESHOLD
+}
+
+/// Añade las fuentes de `dup` a `kept` y completa los campos que `kept` no tenga.
+fn merge_into(kept: &mut JobOffer, dup: JobOffer) {
+    let urls = std::iter::once(dup.source_url).chain(dup.sources);
+    for url in urls {
+        if !url.is_empty() && !kept.sources.contains(&url) {
+            kept.sources.push(url);
+        }
+    }
+    for (field, value) in [
+        (&mut kept.company, dup.company),
+        (&mut kept.location, dup.location),
+        (&mut kept.contact, dup.contact),
+    ] {
+        if field.trim().is_empty() {
+            *field = value;
+        }
+    }
+}
+
+/// URL canónica: sin envoltorios de redirección, fragmento ni parámetros de seguimiento,
+/// con `https`, host en minúsculas sin `www.` y parámetros ordenados. Si no se puede
+/// interpretar se devuelve tal cual.
+pub fn canonical_url(raw: &str) -> String {
+    let raw = raw.trim();
+    let with_scheme = if raw.starts_with("//") {
+        format!("https:{raw}")
+    } else {
+        raw.to_string()
+    };
+    let Ok(mut url) = Url::parse(&with_scheme) else {
+        return raw.to_string();
+    };
+
+    // Redirecciones de buscadores: la URL real va en un parámetro
+    for _ in 0..3 {
+        match unwrap_redirect(&url) {
+            Some(target) => url = target,
+            None => break,
+        }
+    }
+
+    if url.scheme() == "http" {
+        let _ = url.set_scheme("https");
+    }
+    if let Some(host) = url.host_str() {
+        let host = host.to_ascii_lowercase();
+        let host = host.strip_prefix("www.").unwrap_or(&host).to_string();
+        let _ = url.set_host(Some(&host));
+    }
+    url.set_fragment(None);
+
+    let mut params: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(k, _)| !is_tracking_param(k))
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    params.sort();
+    if params.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(params);
+    }
+
+    let path = url.path().to_string();
+    if path.len() > 1 && path.ends_with('/') {
+        url.set_path(path.trim_end_matches('/'));
+    }
+    url.to_string()
+}
+
+/// Destino de una URL de redirección (`duckduckgo.com/l/?uddg=`, `google.*/url?q=`).
+fn unwrap_redirect(url: &Url) -> Option<Url> {
+    let host = url.host_str()?.to_ascii_lowercase();
+    let param = if host.ends_with("duckduckgo.com") && url.path().starts_with("/l") {
+        "uddg"
+    } else if host.contains("google.") && url.path() == "/url" {
+        "q"
+    } else {
+        return None;
+    };
+    let target = url.query_pairs().find(|(k, _)| k == param)?.1;
+    Url::parse(&target).ok()
+}
+
+fn is_tracking_param(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    key.starts_with("utm_") || TRACKING_PARAMS.contains(&key.as_str())
+}
+
+/// Palabras significativas de título + empresa, normalizadas.
+fn words(offer: &JobOffer) -> HashSet<String> {
+    normalize(&format!("{} {}", offer.title, offer.company))
+        .split_whitespace()
+        .filter(|w| !NOISE_WORDS.contains(w))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Coeficiente de Dice entre dos conjuntos de palabras.
+pub fn dice(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let common = a.intersection(b).count();
+    2.0 * common as f64 / (a.len() + b.len()) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offer(title: &str, company: &str, url: &str) -> JobOffer {
+        JobOffer {
+            title: title.to_string(),
+            company: company.to_string(),
+            source_url: url.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn canonical_url_unwraps_duckduckgo_redirects() {
+        let wrapped = "//duckduckgo.com/l/?uddg=https%3A%2F%2Fwww.example.com%2Fjobs%2F42%3Futm_source%3Dddg&rut=abc";
+        assert_eq!(canonical_url(wrapped), "https://example.com/jobs/42");
+    }
+
+    #[test]
+    fn canonical_url_drops_tracking_www_fragment_and_trailing_slash() {
+        assert_eq!(
+            canonical_url("http://WWW.Example.com/jobs/42/?utm_medium=x&id=7&gclid=1#apply"),
+            "https://example.com/jobs/42?id=7"
+        );
+        assert_eq!(
+            canonical_url("https://example.com/a?b=2&a=1"),
+            "https://example.com/a?a=1&b=2"
+        );
+    }
+
+    #[test]
+    fn canonical_url_keeps_unparseable_text() {
+        assert_eq!(canonical_url(" no es una url "), "no es una url");
+    }
+
+    #[test]
+    fn same_canonical_url_is_merged() {
+        let merged = dedup_offers(vec![
+            offer(
+                "Backend Rust",
+                "",
+                "https://www.example.com/jobs/1?utm_source=a",
+            ),
+            offer(
+                "Rust Backend Developer",
+                "ACME",
+                "http://example.com/jobs/1/",
+            ),
+        ]);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].company, "ACME");
+        assert_eq!(merged[0].sources, vec!["https://example.com/jobs/1"]);
+    }
+
+    #[test]
+    fn similar_titles_without_company_are_not_merged() {
+        let merged = dedup_offers(vec![
+            offer("Desarrollador Backend", "", "https://a.example/1"),
+            offer("Desarrollador Backend", "", "https://b.example/2"),
+        ]);
+        assert_eq!(merged.len(), 2);
+    }
+
+    #[test]
+    fn similar_titles_of_the_same_company_are_merged() {
+        let merged = dedup_offers(vec![
+            offer("Desarrollador Backend Rust", "Acme", "https://a.example/1"),
+            offer(
+                "Desarrollador Backend Rust - LinkedIn",
+                "ACME",
+                "https://b.example/2",
+            ),
+        ]);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(
+            merged[0].sources,
+            vec!["https://a.example/1", "https://b.example/2"]
+        );
+    }
+
+    #[test]
+    fn similar_titles_of_different_companies_are_not_merged() {
+        let merged = dedup_offers(vec![
+            offer("Desarrollador Backend", "Acme", "https://a.example/1"),
+            offer("Desarrollador Backend", "Globex", "https://b.example/2"),
+        ]);
+        assert_eq!(merged.len(), 2);
+    }
+}
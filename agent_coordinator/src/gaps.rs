@@ -0,0 +1,348 @@
+// agent_coordinator/src/gaps.rs
+//! Análisis de carencias del CV frente a las ofertas preseleccionadas.
+//!
+//! Para cada oferta el LLM compara el texto del CV con el de la oferta y devuelve, en
+//! JSON con el esquema de `GapDraft`, los requisitos que el CV no muestra y reescrituras
+//! de puntos del CV que destacan la experiencia que sí encaja. Las reescrituras no
+//! pueden inventar: el texto original debe estar en el CV y la reescritura no puede
+//! añadir ninguna de las carencias. Si no se cumple, se vuelve a pedir con los errores,
+//! como mucho `GAP_ANALYSIS_MAX_ATTEMPTS` veces. El resultado va a una hoja del Excel y
+//! a `carencias_<task_id>.md`.
+
+use anyhow::{Context, Result};
+use async_nats::Client;
+use common::gaps::{BulletRewrite, Gap, GapReport};
+use common::offers::text::{mentions, normalize};
+use common::offers::JobOffer;
+use common::prompts::{self, RenderedPrompt};
+use common::task::GapAnalysisOptions;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::Path;
+use tracing::warn;
+
+use crate::agents::{call_llm_checked, call_tool_json, Feedback};
+use crate::pipeline::{offer_page, output_dir};
+use crate::scoring::shortlist;
+use crate::status::progress;
+use crate::store::TaskRun;
+
+/// Análisis tal y como lo devuelve el LLM.
+#[derive(Serialize, Deserialize, Debug)]
+struct GapDraft {
+    #[serde(default)]
+    missing: Vec<Gap>,
+    #[serde(default)]
+    suggestions: Vec<BulletRewrite>,
+}
+
+impl GapDraft {
+    fn json_schema() -> Value {
+        let text = json!({ "type": "string" });
+        json!({
+            "type": "object",
+            "properties": {
+                "missing": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "requirement": { "type": "string", "minLength": 1 },
+                            "detail": text
+                        },
+                        "required": ["requirement"]
+                    }
+                },
+                "suggestions": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "original": { "type": "string", "minLength": 1 },
+                            "rewrite": { "type": "string", "minLength": 1 },
+                            "reason": text
+                        },
+                        "required": ["original", "rewrite"]
+                    }
+                }
+            },
+            "required": ["missing", "suggestions"]
+        })
+    }
+}
+
+/// Analiza las mejores ofertas (`options.top`, con al menos `options.min_score`) y
+/// escribe el informe en Markdown. Devuelve los informes para la hoja del Excel. Una
+/// oferta cuyo análisis falla queda como aviso en el informe de la tarea.
+pub async fn analyze_gaps(
+    client: &Client,
+    task_id: &str,
+    run: &mut TaskRun,
+    cv_text: &str,
+    options: &GapAnalysisOptions,
+    offers: &[JobOffer],
+) -> Result<Vec<GapReport>> {
+    let selected = shortlist(offers, options.top, options.min_score);
+    let total = selected.len();
+    let mut reports = Vec::with_capacity(total);
+    for (n, i) in selected.into_iter().enumerate() {
+        progress(
+            client,
+            task_id,
+            &format!("Analizando carencias del CV {}/{total}", n + 1),
+        )
+        .await;
+        let offer = &offers[i];
+        let page = offer_page(client, task_id, run, i, offer).await;
+        let report = run
+            .step(
+                &format!("gap_analysis:{n}"),
+                analyze_offer(client, task_id, cv_text, offer, &page),
+            )
+            .await;
+        match report {
+            Ok(report) => reports.push(report),
+            Err(e) => {
+                warn!(
+                    "Tarea {task_id}: sin análisis para '{}': {e:#}",
+                    offer.title
+                );
+                let warning = format!("análisis de carencias de '{}': {e:#}", offer.title);
+                run.update_report(|report| {
+                    if !report.warnings.contains(&warning) {
+                        report.warnings.push(warning);
+                    }
+                })
+                .await?;
+            }
+        }
+    }
+
+    if !reports.is_empty() {
+        let path = Path::new(&output_dir())
+            .join(format!("carencias_{task_id}.md"))
+            .to_string_lossy()
+            .into_owned();
+        run.step(
+            "gap_report",
+            write_markdown(client, task_id, &path, &reports),
+        )
+        .await?;
+    }
+    Ok(reports)
+}
+
+async fn analyze_offer(
+    client: &Client,
+    task_id: &str,
+    cv_text: &str,
+    offer: &JobOffer,
+    page: &str,
+) -> Result<GapReport> {
+    let draft: GapDraft = call_llm_checked(
+        client,
+        task_id,
+        "análisis de carencias",
+        "GAP_ANALYSIS_MAX_ATTEMPTS",
+        &GapDraft::json_schema(),
+        |feedback| gap_prompt(task_id, cv_text, offer, page, feedback),
+        |draft| validate(draft, cv_text),
+    )
+    .await?;
+    Ok(GapReport {
+        title: offer.title.clone(),
+        company: offer.company.clone(),
+        source_url: offer.source_url.clone(),
+        score: offer.score.as_ref().map(|s| s.score),
+        missing: draft.missing,
+        suggestions: draft.suggestions,
+    })
+}
+
+fn gap_prompt(
+    task_id: &str,
+    cv_text: &str,
+    offer: &JobOffer,
+    page: &str,
+    feedback: Option<&Feedback>,
+) -> Result<RenderedPrompt> {
+    let (previous, errors) = feedback.cloned().unzip();
+    let skills_missing = offer.score.as_ref().map(|s| &s.skills_missing);
+    Ok(prompts::render(
+        "gap_analysis",
+        task_id,
+        &json!({
+            "title": offer.title,
+            "company": offer.company,
+            "url": offer.source_url,
+            "page": page,
+            "skills_missing": skills_missing,
+            "cv_text": cv_text,
+            "previous": previous,
+            "errors": errors,
+        }),
+    )?)
+}
+
+/// Problemas del análisis (vacío si es válido): originales que no están en el CV o
+/// reescrituras que añaden alguna de las carencias.
+fn validate(draft: &GapDraft, cv_text: &str) -> Vec<String> {
+    let mut errors = Vec::new();
+    if draft
+        .missing
+        .iter()
+        .any(|g| g.requirement.trim().is_empty())
+    {
+        errors.push("hay una carencia sin 'requirement'".to_string());
+    }
+
+    let cv = normalize(cv_text);
+    for suggestion in &draft.suggestions {
+        let original = normalize(&suggestion.original);
+        if original.is_empty() || !cv.contains(&original) {
+            errors.push(format!(
+                "el original «{}» no aparece literalmente en el CV",
+                suggestion.original
+            ));
+            continue;
+        }
+        if suggestion.rewrite.trim().is_empty() {
+            errors.push(format!("falta la reescritura de «{}»", suggestion.original));
+            continue;
+        }
+        let rewrite = normalize(&suggestion.rewrite);
+        for gap in &draft.missing {
+            let requirement = normalize(&gap.requirement);
+            if mentions(&rewrite, &requirement) && !mentions(&cv, &requirement) {
+                errors.push(format!(
+                    "la reescritura de «{}» añade «{}», que el CV no muestra",
+                    suggestion.original, gap.requirement
+                ));
+            }
+        }
+    }
+    errors
+}
+
+async fn write_markdown(
+    client: &Client,
+    task_id: &str,
+    path: &str,
+    reports: &[GapReport],
+) -> Result<String> {
+    call_tool_json(
+        client,
+        task_id,
+        "file_writer",
+        json!({ "path": path, "content": render_markdown(reports) }),
+    )
+    .await
+    .context("escritura del análisis de carencias")?;
+    Ok(path.to_string())
+}
+
+fn render_markdown(reports: &[GapReport]) -> String {
+    let mut md = "# Carencias del CV y sugerencias por oferta\n".to_string();
+    for report in reports {
+        md.push_str(&format!("\n## {}", report.title.trim()));
+        if !report.company.trim().is_empty() {
+            md.push_str(&format!(" — {}", report.company.trim()));
+        }
+        md.push('\n');
+        if let Some(score) = report.score {
+            md.push_str(&format!("\nPuntuación: {score}/100  \n"));
+        }
+        md.push_str(&format!("Oferta: <{}>\n", report.source_url));
+
+        md.push_str("\n### Lo que el CV no muestra\n\n");
+        if report.missing.is_empty() {
+            md.push_str("Nada destacable.\n");
+        }
+        for gap in &report.missing {
+            md.push_str(&format!("- **{}**", gap.requirement.trim()));
+            if !gap.detail.trim().is_empty() {
+                md.push_str(&format!(": {}", gap.detail.trim()));
+            }
+            md.push('\n');
+        }
+
+        md.push_str("\n### Sugerencias para el CV\n\n");
+        if report.suggestions.is_empty() {
+            md.push_str("Sin sugerencias.\n");
+        }
+        for suggestion in &report.suggestions {
+            md.push_str(&format!(
+                "- Original: {}\n  - Propuesta: {}\n",
+                suggestion.original.trim(),
+                suggestion.rewrite.trim()
+            ));
+            if !suggestion.reason.trim().is_empty() {
+                md.push_str(&format!("  - Por qué: {}\n", suggestion.reason.trim()));
+            }
+        }
+    }
+    md
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CV: &str = "Desarrollé APIs REST en Rust para 2 millones de usuarios.";
+
+    fn draft(value: Value) -> GapDraft {
+        assert!(common::schema::validate(&GapDraft::json_schema(), &value).is_empty());
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn schema_requires_both_lists() {
+        let errors = common::schema::validate(
+            &GapDraft::json_schema(),
+            &json!({ "missing": [{ "requirement": "" }] }),
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "/: falta la propiedad 'suggestions'",
+                "/missing/0/requirement: longitud mínima 1"
+            ]
+        );
+    }
+
+    #[test]
+    fn rewrites_must_start_from_the_cv() {
+        let ok = draft(json!({
+            "missing": [{ "requirement": "Kubernetes" }],
+            "suggestions": [{
+                "original": "Desarrollé APIs REST en Rust",
+                "rewrite": "Diseñé y desarrollé APIs REST de alto tráfico en Rust"
+            }]
+        }));
+        assert!(validate(&ok, CV).is_empty());
+
+        let invented = draft(json!({
+            "missing": [],
+            "suggestions": [{ "original": "Lideré un equipo", "rewrite": "Lideré un equipo de 5" }]
+        }));
+        assert_eq!(
+            validate(&invented, CV),
+            vec!["el original «Lideré un equipo» no aparece literalmente en el CV"]
+        );
+    }
+
+    #[test]
+    fn rewrites_cannot_add_missing_requirements() {
+        let padded = draft(json!({
+            "missing": [{ "requirement": "Kubernetes" }],
+            "suggestions": [{
+                "original": "Desarrollé APIs REST en Rust",
+                "rewrite": "Desarrollé APIs REST en Rust desplegadas en Kubernetes"
+            }]
+        }));
+        assert_eq!(
+            validate(&padded, CV),
+            vec!["la reescritura de «Desarrollé APIs REST en Rust» añade «Kubernetes», que el CV no muestra"]
+        );
+    }
+}
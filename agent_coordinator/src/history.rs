@@ -0,0 +1,293 @@
+// agent_coordinator/src/history.rs
+//! Comparación de las ofertas de una búsqueda programada con las de su ejecución
+//! anterior.
+//!
+//! El perfil guarda una instantánea de cada oferta vista (`SeenOffer`). En la siguiente
+//! ejecución cada oferta encontrada queda como nueva, abierta o modificada (otro título,
+//! salario o ubicación), y las de la instantánea que ya no aparecen se añaden al final
+//! como desaparecidas. Una oferta es la misma si comparte URL canónica o, si la URL ha
+//! cambiado, título y empresa.
+
+use common::offers::text::{annual_salary, normalize};
+use common::offers::{JobOffer, OfferStatus};
+use serde::{Deserialize, Serialize};
+
+/// Lo que se recuerda de una oferta para compararla en la siguiente ejecución.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SeenOffer {
+    pub source_url: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub company: String,
+    #[serde(default)]
+    pub location: String,
+    #[serde(default)]
+    pub contact: String,
+    /// Salario anual que indicaba la oferta (`text::annual_salary`).
+    #[serde(default)]
+    pub salary: Option<u32>,
+}
+
+impl SeenOffer {
+    pub fn from_offer(offer: &JobOffer) -> Self {
+        SeenOffer {
+            source_url: offer.source_url.clone(),
+            title: offer.title.clone(),
+            company: offer.company.clone(),
+            location: offer.location.clone(),
+            contact: offer.contact.clone(),
+            salary: annual_salary(offer),
+        }
+    }
+
+    fn to_offer(&self) -> JobOffer {
+        JobOffer {
+            title: self.title.clone(),
+            company: self.company.clone(),
+            location: self.location.clone(),
+            contact: self.contact.clone(),
+            source_url: self.source_url.clone(),
+            sources: vec![self.source_url.clone()],
+            ..Default::default()
+        }
+    }
+}
+
+/// Marca el estado de cada oferta frente a `previous` y añade al final las de
+/// `previous` que ya no aparecen, con estado `gone`.
+pub fn diff_offers(previous: &[SeenOffer], offers: &mut Vec<JobOffer>) {
+    let mut matched = vec![false; previous.len()];
+    for offer in offers.iter_mut() {
+        let found = (0..previous.len())
+            .filter(|&i| !matched[i])
+            .find(|&i| same_url(&previous[i], offer))
+            .or_else(|| {
+                (0..previous.len())
+                    .filter(|&i| !matched[i])
+                    .find(|&i| same_title(&previous[i], offer))
+            });
+        let Some(i) = found else {
+            offer.status = Some(OfferStatus::New);
+            continue;
+        };
+        matched[i] = true;
+        offer.changes = changes(&previous[i], offer);
+        offer.status = Some(if offer.changes.is_empty() {
+            OfferStatus::Open
+        } else {
+            OfferStatus::Changed
+        });
+    }
+
+    for (seen, _) in previous.iter().zip(&matched).filter(|(_, m)| !**m) {
+        let mut gone = seen.to_offer();
+        gone.status = Some(OfferStatus::Gone);
+        offers.push(gone);
+    }
+}
+
+/// Instantánea de las ofertas que siguen publicadas, para la próxima ejecución.
+pub fn snapshot(offers: &[JobOffer]) -> Vec<SeenOffer> {
+    offers
+        .iter()
+        .filter(|o| o.status != Some(OfferStatus::Gone))
+        .map(SeenOffer::from_offer)
+        .collect()
+}
+
+fn same_url(seen: &SeenOffer, offer: &JobOffer) -> bool {
+    !seen.source_url.is_empty()
+        && (seen.source_url == offer.source_url || offer.sources.contains(&seen.source_url))
+}
+
+fn same_title(seen: &SeenOffer, offer: &JobOffer) -> bool {
+    !seen.title.trim().is_empty()
+        && !seen.company.trim().is_empty()
+        && normalize(&seen.title) == normalize(&offer.title)
+        && normalize(&seen.company) == normalize(&offer.company)
+}
+
+/// Cambios de título, salario o ubicación, como "campo: antes → ahora".
+fn changes(seen: &SeenOffer, offer: &JobOffer) -> Vec<String> {
+    let mut changes = Vec::new();
+    for (field, before, now) in [
+        ("título", &seen.title, &offer.title),
+        ("ubicación", &seen.location, &offer.location),
+    ] {
+        if normalize(before) != normalize(now) {
+            changes.push(format!("{field}: «{}» → «{}»", before.trim(), now.trim()));
+        }
+    }
+    let salary = annual_salary(offer);
+    if seen.salary != salary {
+        let show = |s: Option<u32>| s.map_or("sin indicar".to_string(), |s| s.to_string());
+        changes.push(format!("salario: {} → {}", show(seen.salary), show(salary)));
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offer(title: &str, company: &str, location: &str, url: &str) -> JobOffer {
+        JobOffer {
+            title: title.to_string(),
+            company: company.to_string(),
+            location: location.to_string(),
+            source_url: url.to_string(),
+            sources: vec![url.to_string()],
+            ..Default::default()
+        }
+    }
+
+    fn statuses(offers: &[JobOffer]) -> Vec<(&str, Option<OfferStatus>)> {
+        offers
+            .iter()
+            .map(|o| (o.source_url.as_str(), o.status))
+            .collect()
+    }
+
+    #[test]
+    fn offers_are_new_open_changed_or_gone() {
+        let previous = snapshot(&[
+            offer("Backend Rust", "Acme", "Madrid", "https://a/1"),
+            offer("Data engineer 40.000 €", "Beta", "Sevilla", "https://b/2"),
+            offer("Frontend", "Gamma", "Bilbao", "https://c/3"),
+            offer("QA", "Delta", "Remoto", "https://d/4"),
+        ]);
+        let mut offers = vec![
+            offer("Backend Rust", "ACME", "madrid", "https://a/1"),
+            offer("Data engineer 45.000 €", "Beta", "Málaga", "https://b/2"),
+            offer("SRE", "Epsilon", "Madrid", "https://e/5"),
+        ];
+        diff_offers(&previous, &mut offers);
+
+        assert_eq!(
+            statuses(&offers),
+            [
+                ("https://a/1", Some(OfferStatus::Open)),
+                ("https://b/2", Some(OfferStatus::Changed)),
+                ("https://e/5", Some(OfferStatus::New)),
+                ("https://c/3", Some(OfferStatus::Gone)),
+                ("https://d/4", Some(OfferStatus::Gone)),
+            ]
+        );
+        assert!(offers[0].changes.is_empty());
+        assert_eq!(
+            offers[1].changes,
+            [
+                "título: «Data engineer 40.000 €» → «Data engineer 45.000 €»",
+                "ubicación: «Sevilla» → «Málaga»",
+                "salario: 40000 → 45000",
+            ]
+        );
+        assert_eq!(offers[3].title, "Frontend");
+        assert_eq!(offers[3].sources, ["https://c/3"]);
+    }
+
+    #[test]
+    fn gone_offers_go_after_every_current_offer() {
+        let previous = snapshot(&[
+            offer("A", "Acme", "", "https://a/1"),
+            offer("B", "Acme", "", "https://a/2"),
+        ]);
+        let mut offers = vec![
+            offer("C", "Acme", "", "https://a/3"),
+            offer("B", "Acme", "", "https://a/2"),
+            offer("D", "Acme", "", "https://a/4"),
+        ];
+        diff_offers(&previous, &mut offers);
+
+        // pipeline.rs puntúa solo las anteriores a la primera desaparecida
+        let current = offers
+            .iter()
+            .take_while(|o| o.status != Some(OfferStatus::Gone))
+            .count();
+        assert_eq!(current, 3);
+        assert_eq!(offers.len(), 4);
+        assert_eq!(offers[3].source_url, "https://a/1");
+        assert_eq!(offers[3].status, Some(OfferStatus::Gone));
+    }
+
+    #[test]
+    fn moved_offers_match_by_title_and_company() {
+        let previous = snapshot(&[offer("Backend Rust", "Acme", "Madrid", "https://a/1")]);
+
+        let mut moved = vec![offer("Backend  RUST", "acme", "Madrid", "https://a/99")];
+        diff_offers(&previous, &mut moved);
+        assert_eq!(
+            statuses(&moved),
+            [("https://a/99", Some(OfferStatus::Open))]
+        );
+
+        // Otra URL con el mismo título pero otra empresa es otra oferta
+        let mut other = vec![offer("Backend Rust", "Beta", "Madrid", "https://b/1")];
+        diff_offers(&previous, &mut other);
+        assert_eq!(
+            statuses(&other),
+            [
+                ("https://b/1", Some(OfferStatus::New)),
+                ("https://a/1", Some(OfferStatus::Gone)),
+            ]
+        );
+    }
+
+    #[test]
+    fn merged_sources_keep_the_match() {
+        let previous = snapshot(&[offer("Backend", "Acme", "", "https://old/1")]);
+        let mut offers = vec![offer("Backend", "Acme", "", "https://new/1")];
+        offers[0].sources.push("https://old/1".to_string());
+        diff_offers(&previous, &mut offers);
+        assert_eq!(offers.len(), 1);
+        assert_eq!(offers[0].status, Some(OfferStatus::Open));
+    }
+
+    #[test]
+    fn same_title_needs_title_and_company() {
+        let seen = SeenOffer::from_offer(&offer("Backend", "", "", "https://a/1"));
+        assert!(!same_title(&seen, &offer("Backend", "", "", "https://a/2")));
+        let seen = SeenOffer::from_offer(&offer("", "Acme", "", "https://a/1"));
+        assert!(!same_title(&seen, &offer("", "Acme", "", "https://a/2")));
+        let seen = SeenOffer::from_offer(&offer("Backend Ágil", "Acme S.L.", "", "u"));
+        assert!(same_title(
+            &seen,
+            &offer("backend agil", "ACME, S.L.", "", "v")
+        ));
+    }
+
+    #[test]
+    fn changes_report_salary_appearing_and_disappearing() {
+        let seen = SeenOffer::from_offer(&offer("Backend", "Acme", "Madrid", "u"));
+        assert_eq!(
+            changes(&seen, &offer("Backend", "Acme 30.000 €", "Madrid", "u")),
+            ["salario: sin indicar → 30000"]
+        );
+        let seen = SeenOffer::from_offer(&offer("Backend", "Acme", "Madrid 2.000 €/mes", "u"));
+        assert_eq!(
+            changes(&seen, &offer("Backend", "Acme", "Madrid", "u")),
+            [
+                "ubicación: «Madrid 2.000 €/mes» → «Madrid»",
+                "salario: 24000 → sin indicar",
+            ]
+        );
+    }
+
+    #[test]
+    fn snapshot_skips_gone_offers() {
+        let mut offers = vec![
+            offer("A", "Acme", "Madrid 35.000 €", "https://a/1"),
+            offer("B", "Acme", "", "https://a/2"),
+        ];
+        offers[1].status = Some(OfferStatus::Gone);
+        offers[0].status = Some(OfferStatus::New);
+
+        let seen = snapshot(&offers);
+        assert_eq!(seen.len(), 1);
+        assert_eq!(seen[0].source_url, "https://a/1");
+        assert_eq!(seen[0].title, "A");
+        assert_eq!(seen[0].company, "Acme");
+        assert_eq!(seen[0].salary, Some(35_000));
+    }
+}
@@ -1,29 +1,118 @@
 // agent_coordinator/src/lib.rs
 use anyhow::Result;
-use async_nats::jetstream;
+use async_nats::{jetstream, Client};
 use bytes::Bytes;
+use common::cancel::InFlight;
+use common::messaging::NATS_COORDINATOR_SUBJECT;
+use common::task::InitialTask;
 use futures::StreamExt;
 use std::env;
+use tracing::info;
 
-/// Subjects (core)
-const NATS_COORDINATOR_SUBJECT: &str = "agents.coordinator";
-const _NATS_LLM_SUBJECT: &str = "agents.llm";
-const _NATS_TOOL_SUBJECT: &str = "agents.tool";
+mod agents;
+mod cancel;
+mod cover_letters;
+mod cv_profile;
+mod dedup;
+mod gaps;
+mod history;
+mod pipeline;
+mod planner;
+mod profiles;
+mod queries;
+mod queue;
+mod scheduler;
+mod scoring;
+mod status;
+mod store;
+mod task_api;
 
-/// Coordinador básico: responde "OK" a cualquier request con reply.
-/// Sustituye este loop por tu enrutado real cuando quieras.
+/// Coordinador: recibe una `InitialTask` (JSON) en `agents.coordinator`, la encola en
+/// JetStream y responde "OK". Las tareas de la cola ejecutan el pipeline CV → ofertas,
+/// o un plan generado por el LLM si la tarea trae `goal`. Además encola las búsquedas
+/// programadas de los perfiles guardados.
 pub async fn run() -> Result<()> {
     let nats_url = env::var("NATS_URL").unwrap_or_else(|_| "nats://127.0.0.1:4222".to_string());
-
     let client = async_nats::connect(&nats_url).await?;
-    let _jetstream = jetstream::new(client.clone());
+    let jetstream = jetstream::new(client.clone());
+
+    let settings = queue::QueueSettings::from_env();
+    let consumer = queue::setup(&jetstream, &settings).await?;
+    info!(
+        "Cola de tareas lista (stream {}, max_deliver {})",
+        queue::TASKS_STREAM,
+        settings.max_deliver
+    );
+
+    let store = store::TaskStore::open(&jetstream).await?;
+    queue::recover_orphans(&client, &jetstream, &store).await?;
 
+    let profiles = profiles::ProfileStore::open(&jetstream).await?;
+
+    let inflight = InFlight::new();
+    tokio::try_join!(
+        accept_tasks(&client, &jetstream),
+        cancel::handle_cancellations(
+            client.clone(),
+            jetstream.clone(),
+            store.clone(),
+            inflight.clone()
+        ),
+        task_api::serve(client.clone(), store.clone()),
+        profiles::serve(client.clone(), jetstream.clone(), profiles.clone()),
+        scheduler::run(jetstream.clone(), profiles.clone()),
+        queue::consume(
+            client.clone(),
+            jetstream.clone(),
+            store,
+            profiles,
+            inflight,
+            consumer,
+            settings
+        ),
+    )?;
+    Ok(())
+}
+
+/// Recibe tareas por request/reply en `agents.coordinator` y las encola.
+async fn accept_tasks(client: &Client, jetstream: &jetstream::Context) -> Result<()> {
     let mut sub = client.subscribe(NATS_COORDINATOR_SUBJECT).await?;
     while let Some(msg) = sub.next().await {
+        let response = match serde_json::from_slice::<InitialTask>(&msg.payload) {
+            Ok(task) if store::validate_task_id(&task.task_id).is_err() => {
+                format!("ERROR: task_id '{}' no válido", task.task_id)
+            }
+            Ok(task) => match queue::submit(jetstream, &task).await {
+                Ok(()) => {
+                    info!("Tarea encolada: {} ({})", task.task_id, task.task_name);
+                    "OK".to_string()
+                }
+                Err(e) => format!("ERROR: no se pudo encolar la tarea: {e}"),
+            },
+            Err(e) => format!("ERROR: el payload no es una InitialTask válida: {e}"),
+        };
+
         if let Some(reply) = msg.reply {
-            client.publish(reply, Bytes::from_static(b"OK")).await?;
+            client
+                .publish(reply, Bytes::from(response.into_bytes()))
+                .await?;
         }
     }
     Ok(())
 }
 
+/// Ejecuta una tarea y devuelve su resultado (ruta del archivo generado o salida final).
+async fn execute_task(
+    client: &Client,
+    task: &InitialTask,
+    profiles: &profiles::ProfileStore,
+    run: &mut store::TaskRun,
+) -> Result<String> {
+    if task.profile_id.is_some() {
+        return pipeline::run_profile_search(client, task, profiles, run).await;
+    }
+    match task.goal.as_deref() {
+        Some(goal) => planner::run_planned_task(client, task, goal, run).await,
+        None => pipeline::run_cv_pipeline(client, task, run).await,
+    }
+}
@@ -0,0 +1,602 @@
+// agent_coordinator/src/pipeline.rs
+//! Pipeline CV → ofertas: extrae el texto del CV, pide perfil y consultas al LLM,
+//! busca ofertas en la web y las escribe en un Excel. Las búsquedas programadas de un
+//! perfil guardado reutilizan los mismos pasos y marcan cada oferta como nueva, abierta,
+//! modificada o desaparecida respecto a la ejecución anterior.
+
+use anyhow::{anyhow, Context, Result};
+use async_nats::Client;
+use base64::{engine::general_purpose, Engine as _};
+use common::cv::CvProfile;
+use common::gaps::GapReport;
+use common::kv::now_ms;
+use common::offers::{sort_by_score, JobOffer, OfferFilters, OfferStatus};
+use common::prompts;
+use common::task::InitialTask;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::env;
+use std::path::Path;
+use tracing::{info, warn};
+
+use crate::agents::{call_llm_json, call_tool_json};
+use crate::cover_letters::write_cover_letters;
+use crate::cv_profile::extract_cv_profile;
+use crate::dedup::dedup_offers;
+use crate::gaps::analyze_gaps;
+use crate::history::{diff_offers, snapshot};
+use crate::profiles::ProfileStore;
+use crate::queries::{dedup_queries, generate_queries, max_queries};
+use crate::scoring::score_offers;
+use crate::status::progress;
+use crate::store::{ExcludedOffer, SearchFailure, TaskRun};
+
+/// Paso con el `CvProfile` extraído del CV (lo lee también `tasks.result`).
+pub const CV_PROFILE_STEP: &str = "extract_profile";
+
+/// Caracteres de la página de una oferta que se pasan al LLM.
+const OFFER_PAGE_CHARS: usize = 6000;
+
+/// Búsquedas simultáneas por defecto (`SEARCH_CONCURRENCY`).
+const DEFAULT_SEARCH_CONCURRENCY: usize = 4;
+
+/// Respuesta esperada del LLM al analizar el CV.
+#[derive(Serialize, Deserialize, Debug)]
+struct ProfileAndQueries {
+    #[serde(default)]
+    profile: String,
+    queries: Vec<String>,
+}
+
+/// Ejecuta el pipeline completo y devuelve la ruta del Excel generado.
+///
+/// Cada paso se guarda en `run`; al reanudar una tarea se saltan los ya completados.
+pub async fn run_cv_pipeline(
+    client: &Client,
+    task: &InitialTask,
+    run: &mut TaskRun,
+) -> Result<String> {
+    let task_id = &task.task_id;
+
+    // 1) Texto del CV
+    progress(client, task_id, "Extrayendo texto del CV").await;
+    let cv_text = run
+        .step("extract_cv_text", extract_cv_text(client, task))
+        .await?;
+
+    // 2) Perfil estructurado (habilidades, idiomas, seniority...)
+    let cv_profile = cv_profile_step(client, task_id, run, &cv_text).await?;
+
+    // 3) Consultas
+    let queries = queries_step(client, task_id, run, cv_profile.as_ref(), &cv_text).await?;
+
+    // 4) Búsquedas, filtradas y ordenadas por encaje con el perfil
+    let offers = search_all(client, task_id, run, &queries).await?;
+    let mut offers = apply_filters(client, task_id, run, &task.filters, offers).await?;
+    rank_offers(task_id, cv_profile.as_ref(), &mut offers);
+
+    // 5) Cartas de presentación para las mejores ofertas
+    if let Some(options) = &task.cover_letters {
+        write_cover_letters(client, task_id, run, &cv_text, options, &mut offers).await?;
+    }
+
+    // 6) Carencias del CV frente a las mejores ofertas
+    let gaps = match &task.gap_analysis {
+        Some(options) => analyze_gaps(client, task_id, run, &cv_text, options, &offers).await?,
+        None => Vec::new(),
+    };
+
+    // 7) Excel
+    run.set_offers(&offers).await?;
+    progress(
+        client,
+        task_id,
+        &format!("Escribiendo {} ofertas en Excel", offers.len()),
+    )
+    .await;
+    run.step(
+        "excel_writer",
+        write_excel(client, task_id, &output_path(task_id), &offers, &gaps),
+    )
+    .await
+}
+
+/// Ejecuta la búsqueda de un perfil guardado y escribe en el Excel sus ofertas con el
+/// estado respecto a la ejecución anterior, incluidas las que ya no aparecen. Devuelve la
+/// ruta del Excel generado.
+pub async fn run_profile_search(
+    client: &Client,
+    task: &InitialTask,
+    profiles: &ProfileStore,
+    run: &mut TaskRun,
+) -> Result<String> {
+    let task_id = &task.task_id;
+    let profile_id = task
+        .profile_id
+        .as_deref()
+        .ok_or_else(|| anyhow!("la tarea no tiene profile_id"))?;
+    let profile = profiles
+        .load(profile_id)
+        .await?
+        .ok_or_else(|| anyhow!("perfil '{profile_id}' no encontrado"))?;
+
+    // 1) Perfil estructurado, si el perfil guardado incluye el CV
+    let cv_profile = match profile.cv_text.as_deref().filter(|t| !t.trim().is_empty()) {
+        Some(cv_text) => cv_profile_step(client, task_id, run, cv_text).await?,
+        None => None,
+    };
+
+    // 2) Consultas: las fijas del perfil o generadas a partir del CV / perfil
+    let queries = if profile.queries.is_empty() {
+        let text = profile
+            .cv_text
+            .as_deref()
+            .or(profile.profile.as_deref())
+            .unwrap_or_default();
+        queries_step(client, task_id, run, cv_profile.as_ref(), text).await?
+    } else {
+        profile.queries.clone()
+    };
+
+    // 3) Búsquedas y filtros. Los filtros llegan en la tarea; las encoladas antes de que
+    // existieran usan los del perfil.
+    let filters = if task.filters.is_empty() {
+        &profile.filters
+    } else {
+        &task.filters
+    };
+    let offers = search_all(client, task_id, run, &queries).await?;
+    let offers = apply_filters(client, task_id, run, filters, offers).await?;
+
+    // 4) Estado respecto a la ejecución anterior. Queda guardado como paso para que un
+    // reintento no compare contra las ofertas que esta misma ejecución ya anotó.
+    let mut offers: Vec<JobOffer> = run
+        .step("diff_offers", async {
+            let mut offers = offers;
+            diff_offers(&profile.last_offers, &mut offers);
+            Ok(offers)
+        })
+        .await?;
+    // Las desaparecidas van al final y no se puntúan
+    let current = offers
+        .iter()
+        .take_while(|o| o.status != Some(OfferStatus::Gone))
+        .count();
+    rank_offers(task_id, cv_profile.as_ref(), &mut offers[..current]);
+    progress(client, task_id, &diff_summary(&offers)).await;
+
+    // 5) Excel con el estado de cada oferta
+    run.set_offers(&offers).await?;
+    let path = profile
+        .output_path
+        .clone()
+        .unwrap_or_else(|| output_path(task_id));
+    let written = run
+        .step(
+            "excel_writer",
+            write_excel(client, task_id, &path, &offers, &[]),
+        )
+        .await?;
+
+    // 6) Las ofertas de hoy son la referencia de la próxima ejecución
+    let seen = snapshot(&offers);
+    run.step("remember_offers", async {
+        profiles
+            .update(profile_id, |p| p.last_offers = seen.clone())
+            .await?;
+        Ok(())
+    })
+    .await?;
+    Ok(written)
+}
+
+/// Lanza las consultas en `web_search` en paralelo (hasta `SEARCH_CONCURRENCY` a la vez)
+/// y junta las ofertas en el orden de las consultas, fusionando las duplicadas. Cada
+/// consulta es un paso con checkpoint; las que fallan quedan en el informe de la tarea y
+/// solo se falla si fallan todas.
+async fn search_all(
+    client: &Client,
+    task_id: &str,
+    run: &mut TaskRun,
+    queries: &[String],
+) -> Result<Vec<JobOffer>> {
+    let total = queries.len();
+    let mut results = Vec::with_capacity(total);
+    for i in 0..total {
+        results.push(run.checkpoint::<Vec<JobOffer>>(&search_step(i))?);
+    }
+    let mut done = results.iter().filter(|r| r.is_some()).count();
+    progress(
+        client,
+        task_id,
+        &format!("Buscando ofertas: {done}/{total} consultas completadas"),
+    )
+    .await;
+
+    let pending: Vec<usize> = (0..total).filter(|&i| results[i].is_none()).collect();
+    let mut searches = futures::stream::iter(pending)
+        .map(|i| async move {
+            let started_at_ms = now_ms();
+            let found = search_offers(client, task_id, &queries[i]).await;
+            (i, started_at_ms, found)
+        })
+        .buffer_unordered(search_concurrency());
+
+    let mut failures = Vec::new();
+    while let Some((i, started_at_ms, found)) = searches.next().await {
+        match found {
+            Ok(found) => {
+                run.save_step(&search_step(i), &found, started_at_ms)
+                    .await?;
+                results[i] = Some(found);
+            }
+            Err(e) => {
+                warn!("Tarea {task_id}: búsqueda '{}' fallida: {e:#}", queries[i]);
+                failures.push(SearchFailure {
+                    query: queries[i].clone(),
+                    error: format!("{e:#}"),
+                });
+            }
+        }
+        done += 1;
+        let failed = if failures.is_empty() {
+            String::new()
+        } else {
+            format!(" ({} fallidas)", failures.len())
+        };
+        progress(
+            client,
+            task_id,
+            &format!("Buscando ofertas: {done}/{total} consultas completadas{failed}"),
+        )
+        .await;
+    }
+
+    let failed = failures.len();
+    let first_error = failures.first().map(|f| f.error.clone());
+    run.update_report(|report| report.search_failures = failures)
+        .await?;
+    if let Some(error) = first_error.filter(|_| failed == total) {
+        return Err(anyhow!(
+            "fallaron las {total} búsquedas; la primera: {error}"
+        ));
+    }
+
+    let found: Vec<JobOffer> = results.into_iter().flatten().flatten().collect();
+    let found_count = found.len();
+    let offers = dedup_offers(found);
+    progress(
+        client,
+        task_id,
+        &format!(
+            "{} ofertas distintas de {found_count} resultados",
+            offers.len()
+        ),
+    )
+    .await;
+    Ok(offers)
+}
+
+fn search_step(i: usize) -> String {
+    format!("web_search:{i}")
+}
+
+fn search_concurrency() -> usize {
+    env::var("SEARCH_CONCURRENCY")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(DEFAULT_SEARCH_CONCURRENCY)
+        .max(1)
+}
+
+/// Descarta las ofertas que no pasan los filtros y anota cada exclusión, con su motivo,
+/// en el informe de la tarea.
+async fn apply_filters(
+    client: &Client,
+    task_id: &str,
+    run: &mut TaskRun,
+    filters: &OfferFilters,
+    offers: Vec<JobOffer>,
+) -> Result<Vec<JobOffer>> {
+    if filters.is_empty() {
+        return Ok(offers);
+    }
+    let mut kept = Vec::with_capacity(offers.len());
+    let mut excluded = Vec::new();
+    for offer in offers {
+        match filters.check(&offer) {
+            Ok(()) => kept.push(offer),
+            Err(reason) => {
+                info!("Tarea {task_id}: descartada '{}': {reason}", offer.title);
+                excluded.push(ExcludedOffer {
+                    title: offer.title,
+                    source_url: offer.source_url,
+                    reason,
+                });
+            }
+        }
+    }
+    progress(
+        client,
+        task_id,
+        &format!(
+            "{} ofertas descartadas por los filtros, quedan {}",
+            excluded.len(),
+            kept.len()
+        ),
+    )
+    .await;
+    run.update_report(|report| report.excluded_offers = excluded)
+        .await?;
+    Ok(kept)
+}
+
+/// Puntúa las ofertas frente al perfil del CV y las ordena de mejor a peor encaje. Sin
+/// perfil se quedan en el orden de búsqueda.
+fn rank_offers(task_id: &str, cv_profile: Option<&CvProfile>, offers: &mut [JobOffer]) {
+    let Some(cv_profile) = cv_profile else {
+        return;
+    };
+    score_offers(cv_profile, offers);
+    sort_by_score(offers);
+    if let Some(best) = offers
+        .first()
+        .and_then(|o| o.score.as_ref().map(|s| (o, s)))
+    {
+        info!(
+            "Tarea {task_id}: mejor oferta '{}' ({}/100): {}",
+            best.0.title, best.1.score, best.1.summary
+        );
+    }
+}
+
+/// Mensaje de estado con las ofertas de cada estado y los primeros títulos nuevos.
+fn diff_summary(offers: &[JobOffer]) -> String {
+    const SHOWN: usize = 5;
+    let count = |status: OfferStatus| offers.iter().filter(|o| o.status == Some(status)).count();
+    let counts = format!(
+        "{} nuevas, {} modificadas, {} siguen abiertas y {} desaparecidas",
+        count(OfferStatus::New),
+        count(OfferStatus::Changed),
+        count(OfferStatus::Open),
+        count(OfferStatus::Gone),
+    );
+    let new: Vec<&str> = offers
+        .iter()
+        .filter(|o| o.status == Some(OfferStatus::New))
+        .map(|o| o.title.as_str())
+        .collect();
+    if new.is_empty() {
+        return format!("Sin ofertas nuevas: {counts}");
+    }
+    let more = new.len().saturating_sub(SHOWN);
+    format!(
+        "{counts}. Nuevas: {}{}",
+        new[..new.len().min(SHOWN)].join("; "),
+        if more > 0 {
+            format!(" y {more} más")
+        } else {
+            String::new()
+        }
+    )
+}
+
+/// Extrae el `CvProfile` del texto del CV. Si el LLM no consigue un perfil válido la
+/// tarea sigue sin él y el motivo queda en el informe.
+async fn cv_profile_step(
+    client: &Client,
+    task_id: &str,
+    run: &mut TaskRun,
+    cv_text: &str,
+) -> Result<Option<CvProfile>> {
+    progress(client, task_id, "Extrayendo el perfil estructurado del CV").await;
+    match run
+        .step(
+            CV_PROFILE_STEP,
+            extract_cv_profile(client, task_id, cv_text),
+        )
+        .await
+    {
+        Ok(profile) => {
+            info!(
+                "Tarea {task_id}: perfil con {} habilidades, {} puestos, seniority {:?}",
+                profile.skills.len(),
+                profile.roles.len(),
+                profile.seniority
+            );
+            Ok(Some(profile))
+        }
+        Err(e) => {
+            warn!("Tarea {task_id}: sin perfil estructurado: {e:#}");
+            let warning = format!("perfil estructurado del CV: {e:#}");
+            run.update_report(|report| {
+                if !report.warnings.contains(&warning) {
+                    report.warnings.push(warning);
+                }
+            })
+            .await?;
+            Ok(None)
+        }
+    }
+}
+
+/// Consultas de búsqueda: generadas a partir del perfil estructurado o, si no lo hay,
+/// propuestas por el LLM al analizar el texto del CV.
+async fn queries_step(
+    client: &Client,
+    task_id: &str,
+    run: &mut TaskRun,
+    cv_profile: Option<&CvProfile>,
+    cv_text: &str,
+) -> Result<Vec<String>> {
+    let queries = match cv_profile {
+        Some(cv_profile) => {
+            progress(client, task_id, "Generando consultas de búsqueda").await;
+            run.step(
+                "generate_queries",
+                generate_queries(client, task_id, cv_profile),
+            )
+            .await?
+        }
+        None => {
+            progress(client, task_id, "Analizando el CV con el LLM").await;
+            let analysis = run
+                .step("analyze_cv", analyze_cv(client, task_id, cv_text))
+                .await?;
+            info!("Tarea {task_id}: perfil: {}", analysis.profile);
+            analysis.queries
+        }
+    };
+    info!("Tarea {task_id}: consultas: {}", queries.join(" | "));
+    Ok(queries)
+}
+
+async fn extract_cv_text(client: &Client, task: &InitialTask) -> Result<String> {
+    let pdf_b64 = general_purpose::STANDARD.encode(&task.pdf_content);
+    let extracted = call_tool_json(
+        client,
+        &task.task_id,
+        "pdf_extractor",
+        json!({ "pdf_data_base64": pdf_b64 }),
+    )
+    .await
+    .context("extracción del texto del CV")?;
+    let cv_text = extracted
+        .get("text")
+        .and_then(|t| t.as_str())
+        .ok_or_else(|| anyhow!("respuesta de 'pdf_extractor' sin campo 'text'"))?;
+    if cv_text.trim().is_empty() {
+        return Err(anyhow!("el CV no contiene texto extraíble"));
+    }
+    Ok(cv_text.to_string())
+}
+
+async fn search_offers(client: &Client, task_id: &str, query: &str) -> Result<Vec<JobOffer>> {
+    let found = call_tool_json(client, task_id, "web_search", json!({ "query": query }))
+        .await
+        .with_context(|| format!("búsqueda '{query}'"))?;
+    Ok(offers_from_search(&found))
+}
+
+async fn write_excel(
+    client: &Client,
+    task_id: &str,
+    path: &str,
+    offers: &[JobOffer],
+    gaps: &[GapReport],
+) -> Result<String> {
+    let written = call_tool_json(
+        client,
+        task_id,
+        "excel_writer",
+        json!({ "path": path, "jobs": offers, "gaps": gaps }),
+    )
+    .await
+    .context("escritura del Excel")?;
+    Ok(written
+        .get("path")
+        .and_then(|p| p.as_str())
+        .unwrap_or(path)
+        .to_string())
+}
+
+async fn analyze_cv(client: &Client, task_id: &str, cv_text: &str) -> Result<ProfileAndQueries> {
+    let max = max_queries();
+    let prompt = prompts::render(
+        "cv_analysis",
+        task_id,
+        &json!({ "max": max, "cv_text": cv_text }),
+    )?;
+    let schema = json!({
+        "type": "object",
+        "properties": {
+            "profile": { "type": "string" },
+            "queries": { "type": "array", "items": { "type": "string" }, "minItems": 1 }
+        },
+        "required": ["profile", "queries"]
+    });
+    let json = call_llm_json(client, task_id, &prompt, &schema)
+        .await
+        .context("análisis del CV con el LLM")?;
+    let mut parsed: ProfileAndQueries = serde_json::from_value(json)?;
+
+    parsed.queries = dedup_queries(parsed.queries, max);
+    if parsed.queries.is_empty() {
+        return Err(anyhow!("el LLM no propuso ninguna consulta de búsqueda"));
+    }
+    Ok(parsed)
+}
+
+/// Convierte la salida de `web_search` (`{"results": [{"title", "link"}]}`) en ofertas.
+fn offers_from_search(found: &serde_json::Value) -> Vec<JobOffer> {
+    found
+        .get("results")
+        .and_then(|r| r.as_array())
+        .map(|results| {
+            results
+                .iter()
+                .filter_map(|r| {
+                    let link = r.get("link")?.as_str()?;
+                    Some(JobOffer {
+                        title: r
+                            .get("title")
+                            .and_then(|t| t.as_str())
+                            .unwrap_or_default()
+                            .to_string(),
+                        source_url: link.to_string(),
+                        ..Default::default()
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Texto de la página de la oferta `offers[index]`, guardado como paso `offer_page:{index}`
+/// para que las cartas y el análisis de carencias la descarguen una sola vez. Si no se
+/// puede descargar se devuelve vacío (y se reintenta en la siguiente ejecución).
+pub async fn offer_page(
+    client: &Client,
+    task_id: &str,
+    run: &mut TaskRun,
+    index: usize,
+    offer: &JobOffer,
+) -> String {
+    let fetch = async {
+        let page = call_tool_json(
+            client,
+            task_id,
+            "web_page",
+            json!({ "url": offer.source_url, "max_chars": OFFER_PAGE_CHARS }),
+        )
+        .await?;
+        Ok(page
+            .get("text")
+            .and_then(|t| t.as_str())
+            .unwrap_or_default()
+            .to_string())
+    };
+    match run.step(&format!("offer_page:{index}"), fetch).await {
+        Ok(page) => page,
+        Err(e) => {
+            warn!(
+                "Tarea {task_id}: sin página de '{}', se usa solo el título: {e:#}",
+                offer.source_url
+            );
+            String::new()
+        }
+    }
+}
+
+/// Carpeta de salida de los archivos generados (`OUTPUT_DIR`).
+pub fn output_dir() -> String {
+    env::var("OUTPUT_DIR").unwrap_or_else(|_| ".".to_string())
+}
+
+fn output_path(task_id: &str) -> String {
+    Path::new(&output_dir())
+        .join(format!("ofertas_{task_id}.xlsx"))
+        .to_string_lossy()
+        .into_owned()
+}
@@ -0,0 +1,555 @@
+// agent_coordinator/src/planner.rs
+//! Planificador paso a paso: el LLM propone un plan JSON con herramientas del
+//! Agente de Herramientas, se valida contra su catálogo (`list_tools`) y se ejecuta
+//! en orden, pasando las salidas de unos pasos a los siguientes.
+//!
+//! Referencias que pueden usarse dentro de los argumentos de un paso:
+//! - `{{cv_base64}}`, `{{task_id}}`, `{{output_dir}}`
+//! - `{{steps.N}}` o `{{steps.N.campo.subcampo}}`: salida de un paso anterior (N empieza en 0)
+
+use anyhow::{anyhow, Context, Result};
+use async_nats::Client;
+use base64::{engine::general_purpose, Engine as _};
+use common::prompts::{self, RenderedPrompt};
+use common::task::InitialTask;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use tracing::info;
+
+use crate::agents::{
+    call_llm, call_llm_checked, call_tool, call_tool_json, extract_json_object, Feedback,
+};
+use crate::pipeline::output_dir;
+use crate::status::progress;
+use crate::store::TaskRun;
+
+/// Paso especial que no es del Agente de Herramientas: genera texto con el LLM.
+const LLM_TOOL: &str = "llm";
+const MAX_PLAN_STEPS: usize = 12;
+
+/// Herramientas que escriben en el argumento `path`: solo pueden hacerlo dentro de
+/// `OUTPUT_DIR`.
+const WRITER_TOOLS: &[&str] = &["file_writer", "excel_writer", "docx_writer"];
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Plan {
+    pub steps: Vec<PlanStep>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlanStep {
+    pub tool: String,
+    #[serde(default)]
+    pub args: Value,
+    #[serde(default)]
+    pub description: String,
+}
+
+/// Entrada del catálogo devuelto por `list_tools`.
+#[derive(Deserialize, Debug, Clone)]
+struct ToolInfo {
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    parameters: Value,
+}
+
+/// Planifica y ejecuta la tarea; devuelve la última ruta generada o la última salida.
+///
+/// El plan y la salida de cada paso se guardan en `run`, así que una tarea reanudada
+/// sigue con el mismo plan desde el primer paso sin completar.
+pub async fn run_planned_task(
+    client: &Client,
+    task: &InitialTask,
+    goal: &str,
+    run: &mut TaskRun,
+) -> Result<String> {
+    let task_id = &task.task_id;
+
+    progress(client, task_id, "Planificando pasos con el LLM").await;
+    let plan: Plan = run
+        .step("plan", async {
+            let tools = list_tools(client, task_id).await?;
+            if tools.is_empty() {
+                return Err(anyhow!(
+                    "el Agente de Herramientas no expone herramientas (¿falta --features toolkit?)"
+                ));
+            }
+            make_plan(client, task_id, goal, &tools).await
+        })
+        .await?;
+    info!("Tarea {task_id}: plan con {} pasos", plan.steps.len());
+
+    let vars = HashMap::from([
+        (
+            "cv_base64",
+            general_purpose::STANDARD.encode(&task.pdf_content),
+        ),
+        ("task_id", task_id.clone()),
+        ("output_dir", output_dir()),
+    ]);
+
+    let total = plan.steps.len();
+    let mut outputs: Vec<Value> = Vec::with_capacity(total);
+    for (i, step) in plan.steps.iter().enumerate() {
+        let label = if step.description.is_empty() {
+            step.tool.clone()
+        } else {
+            format!("{} ({})", step.description, step.tool)
+        };
+        progress(client, task_id, &format!("Paso {}/{total}: {label}", i + 1)).await;
+
+        let output = run
+            .step(&format!("step:{i}"), async {
+                let args = resolve_refs(&step.args, &vars, &outputs)
+                    .with_context(|| format!("paso {i}: argumentos"))?;
+                check_output_path(&step.tool, &args, Path::new(&output_dir()))
+                    .with_context(|| format!("paso {i} ({})", step.tool))?;
+                run_step(client, task_id, &step.tool, args)
+                    .await
+                    .with_context(|| format!("paso {i} ({})", step.tool))
+            })
+            .await?;
+        outputs.push(output);
+    }
+
+    Ok(final_result(&outputs))
+}
+
+async fn list_tools(client: &Client, task_id: &str) -> Result<Vec<ToolInfo>> {
+    let catalog = call_tool_json(client, task_id, "list_tools", json!({}))
+        .await
+        .context("consulta del catálogo de herramientas")?;
+    let tools = catalog.get("tools").cloned().unwrap_or(Value::Null);
+    Ok(serde_json::from_value(tools)?)
+}
+
+/// Pide al LLM un plan con el esquema de [`plan_schema`] y lo revalida; si es inválido,
+/// vuelve a pedirlo con los errores encontrados, como mucho `PLANNER_MAX_ATTEMPTS` veces.
+async fn make_plan(client: &Client, task_id: &str, goal: &str, tools: &[ToolInfo]) -> Result<Plan> {
+    call_llm_checked(
+        client,
+        task_id,
+        "planificación",
+        "PLANNER_MAX_ATTEMPTS",
+        &plan_schema(tools),
+        |feedback| planning_prompt(task_id, goal, tools, feedback),
+        |plan| validate_plan(plan, tools),
+    )
+    .await
+}
+
+/// Forma del plan: entre 1 y `MAX_PLAN_STEPS` pasos con herramientas del catálogo.
+fn plan_schema(tools: &[ToolInfo]) -> Value {
+    let names: Vec<&str> = tools
+        .iter()
+        .map(|t| t.name.as_str())
+        .chain([LLM_TOOL])
+        .collect();
+    json!({
+        "type": "object",
+        "properties": {
+            "steps": {
+                "type": "array",
+                "minItems": 1,
+                "maxItems": MAX_PLAN_STEPS,
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "tool": { "enum": names },
+                        "description": { "type": "string" },
+                        "args": { "type": "object" }
+                    },
+                    "required": ["tool", "args"]
+                }
+            }
+        },
+        "required": ["steps"]
+    })
+}
+
+fn planning_prompt(
+    task_id: &str,
+    goal: &str,
+    tools: &[ToolInfo],
+    feedback: Option<&Feedback>,
+) -> Result<RenderedPrompt> {
+    let mut catalog: Vec<String> = tools
+        .iter()
+        .map(|t| {
+            format!(
+                "{}: {}; argumentos: {}",
+                t.name, t.description, t.parameters
+            )
+        })
+        .collect();
+    catalog.push(format!(
+        "{LLM_TOOL}: Genera texto con el LLM a partir de un prompt; argumentos: {}",
+        json!({
+            "type": "object",
+            "properties": { "prompt": { "type": "string" } },
+            "required": ["prompt"]
+        })
+    ));
+    // Las referencias van como variable: en el texto de la plantilla `{{...}}` se
+    // tomaría por una variable de la propia plantilla
+    let references = [
+        "{{cv_base64}}: CV en PDF codificado en Base64",
+        "{{task_id}}: identificador de la tarea",
+        "{{output_dir}}: carpeta de salida; los ficheros solo pueden escribirse dentro de ella",
+        "{{steps.N}} o {{steps.N.campo}}: salida de un paso anterior (N empieza en 0)",
+    ];
+    let (previous, errors) = feedback.cloned().unzip();
+    Ok(prompts::render(
+        "plan",
+        task_id,
+        &json!({
+            "goal": goal,
+            "tools": catalog,
+            "references": references,
+            "max_steps": MAX_PLAN_STEPS,
+            "previous": previous,
+            "errors": errors,
+        }),
+    )?)
+}
+
+/// Devuelve la lista de problemas del plan (vacía si es válido).
+fn validate_plan(plan: &Plan, tools: &[ToolInfo]) -> Vec<String> {
+    let mut errors = Vec::new();
+    if plan.steps.is_empty() {
+        errors.push("el plan no tiene pasos".to_string());
+    }
+    if plan.steps.len() > MAX_PLAN_STEPS {
+        errors.push(format!(
+            "el plan tiene {} pasos; el máximo es {MAX_PLAN_STEPS}",
+            plan.steps.len()
+        ));
+    }
+
+    for (i, step) in plan.steps.iter().enumerate() {
+        let Some(args) = step.args.as_object() else {
+            errors.push(format!("paso {i}: 'args' debe ser un objeto JSON"));
+            continue;
+        };
+
+        let required: Vec<&str> = if step.tool == LLM_TOOL {
+            vec!["prompt"]
+        } else if let Some(tool) = tools.iter().find(|t| t.name == step.tool) {
+            tool.parameters
+                .get("required")
+                .and_then(|r| r.as_array())
+                .map(|r| r.iter().filter_map(|v| v.as_str()).collect())
+                .unwrap_or_default()
+        } else {
+            errors.push(format!("paso {i}: herramienta desconocida '{}'", step.tool));
+            continue;
+        };
+        for field in required {
+            if !args.contains_key(field) {
+                errors.push(format!(
+                    "paso {i}: falta el argumento obligatorio '{field}' de '{}'",
+                    step.tool
+                ));
+            }
+        }
+
+        for reference in collect_refs(&step.args) {
+            if let Some(problem) = check_ref(&reference, i) {
+                errors.push(format!("paso {i}: {problem}"));
+            }
+        }
+    }
+    errors
+}
+
+fn check_ref(reference: &str, step: usize) -> Option<String> {
+    match reference.strip_prefix("steps.") {
+        Some(rest) => match rest.split('.').next().and_then(|n| n.parse::<usize>().ok()) {
+            Some(n) if n < step => None,
+            Some(n) => Some(format!(
+                "'{{{{{reference}}}}}' usa el paso {n}, que no se ha ejecutado todavía"
+            )),
+            None => Some(format!("referencia mal formada '{{{{{reference}}}}}'")),
+        },
+        None if ["cv_base64", "task_id", "output_dir"].contains(&reference) => None,
+        None => Some(format!("variable desconocida '{{{{{reference}}}}}'")),
+    }
+}
+
+/// Devuelve las referencias `{{...}}` (sin llaves) que aparecen en un texto.
+fn placeholders(text: &str) -> Vec<(usize, usize, &str)> {
+    let mut found = Vec::new();
+    let mut pos = 0;
+    while let Some(start) = text[pos..].find("{{").map(|s| s + pos) {
+        let Some(end) = text[start + 2..].find("}}").map(|e| e + start + 2) else {
+            break;
+        };
+        found.push((start, end + 2, text[start + 2..end].trim()));
+        pos = end + 2;
+    }
+    found
+}
+
+fn collect_refs(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(s) => placeholders(s)
+            .into_iter()
+            .map(|(_, _, name)| name.to_string())
+            .collect(),
+        Value::Array(items) => items.iter().flat_map(collect_refs).collect(),
+        Value::Object(map) => map.values().flat_map(collect_refs).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Sustituye las referencias de los argumentos. Si un string es exactamente una
+/// referencia se conserva el tipo JSON de la salida; si no, se interpola como texto.
+fn resolve_refs(value: &Value, vars: &HashMap<&str, String>, outputs: &[Value]) -> Result<Value> {
+    Ok(match value {
+        Value::String(s) => {
+            let refs = placeholders(s);
+            match refs.as_slice() {
+                [(0, end, name)] if *end == s.len() => lookup(name, vars, outputs)?,
+                [] => value.clone(),
+                _ => {
+                    let mut text = String::with_capacity(s.len());
+                    let mut last = 0;
+                    for (start, end, name) in refs {
+                        text.push_str(&s[last..start]);
+                        text.push_str(&value_as_text(&lookup(name, vars, outputs)?));
+                        last = end;
+                    }
+                    text.push_str(&s[last..]);
+                    Value::String(text)
+                }
+            }
+        }
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|v| resolve_refs(v, vars, outputs))
+                .collect::<Result<_>>()?,
+        ),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| Ok((k.clone(), resolve_refs(v, vars, outputs)?)))
+                .collect::<Result<_>>()?,
+        ),
+        other => other.clone(),
+    })
+}
+
+fn lookup(name: &str, vars: &HashMap<&str, String>, outputs: &[Value]) -> Result<Value> {
+    let Some(path) = name.strip_prefix("steps.") else {
+        return vars
+            .get(name)
+            .map(|v| Value::String(v.clone()))
+            .ok_or_else(|| anyhow!("variable desconocida '{name}'"));
+    };
+
+    let mut parts = path.split('.');
+    let index: usize = parts
+        .next()
+        .and_then(|n| n.parse().ok())
+        .ok_or_else(|| anyhow!("referencia mal formada '{name}'"))?;
+    let mut current = outputs
+        .get(index)
+        .ok_or_else(|| anyhow!("el paso {index} no tiene salida"))?;
+    for part in parts {
+        current = match current {
+            Value::Object(map) => map.get(part),
+            Value::Array(items) => part.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        }
+        .ok_or_else(|| anyhow!("la salida del paso {index} no tiene '{part}' ({name})"))?;
+    }
+    Ok(current.clone())
+}
+
+fn value_as_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Las herramientas de [`WRITER_TOOLS`] solo pueden escribir dentro de `dir`.
+fn check_output_path(tool: &str, args: &Value, dir: &Path) -> Result<()> {
+    if !WRITER_TOOLS.contains(&tool) {
+        return Ok(());
+    }
+    let path = args
+        .get("path")
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow!("falta la ruta 'path'"))?;
+    if !resolve_path(Path::new(path))?.starts_with(resolve_path(dir)?) {
+        return Err(anyhow!(
+            "la ruta '{path}' está fuera del directorio de salida '{}'",
+            dir.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Ruta absoluta sin `.` ni `..`; la parte que ya existe se canonicaliza para seguir
+/// los enlaces simbólicos.
+fn resolve_path(path: &Path) -> Result<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in std::path::absolute(path)?.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            other => normalized.push(other),
+        }
+    }
+    let mut existing = normalized.as_path();
+    let mut missing = Vec::new();
+    while !existing.exists() {
+        let (Some(parent), Some(name)) = (existing.parent(), existing.file_name()) else {
+            break;
+        };
+        missing.push(name);
+        existing = parent;
+    }
+    let mut resolved = existing.canonicalize()?;
+    resolved.extend(missing.iter().rev());
+    Ok(resolved)
+}
+
+async fn run_step(client: &Client, task_id: &str, tool: &str, args: Value) -> Result<Value> {
+    let text = if tool == LLM_TOOL {
+        let prompt = args.get("prompt").map(value_as_text).unwrap_or_default();
+        call_llm(client, task_id, &RenderedPrompt::from(prompt)).await?
+    } else {
+        call_tool(client, task_id, tool, args).await?
+    };
+    Ok(parse_output(text))
+}
+
+/// Las salidas JSON se conservan como JSON para poder referenciar sus campos.
+fn parse_output(text: String) -> Value {
+    if let Ok(value) = serde_json::from_str::<Value>(text.trim()) {
+        return value;
+    }
+    extract_json_object(&text)
+        .and_then(|json| serde_json::from_str::<Value>(json).ok())
+        .unwrap_or(Value::String(text))
+}
+
+/// Resultado de la tarea: la última ruta generada o, si no hay, la última salida.
+fn final_result(outputs: &[Value]) -> String {
+    outputs
+        .iter()
+        .rev()
+        .find_map(|o| o.get("path").and_then(|p| p.as_str()).map(str::to_string))
+        .or_else(|| outputs.last().map(value_as_text))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::schema::validate;
+
+    fn tools() -> Vec<ToolInfo> {
+        vec![ToolInfo {
+            name: "pdf_parser".to_string(),
+            description: "Extrae el texto de un PDF".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": { "pdf_base64": { "type": "string" } },
+                "required": ["pdf_base64"]
+            }),
+        }]
+    }
+
+    #[test]
+    fn planning_prompt_comes_from_the_plan_template() {
+        let prompt = planning_prompt("t1", "Analiza el CV", &tools(), None).unwrap();
+        assert_eq!(prompt.version, "plan@v1");
+        assert!(prompt.text.contains("Objetivo: Analiza el CV"));
+        assert!(prompt
+            .text
+            .contains("- pdf_parser: Extrae el texto de un PDF; argumentos: {"));
+        assert!(prompt.text.contains("- llm: Genera texto con el LLM"));
+        assert!(prompt.text.contains("- {{steps.N}} o {{steps.N.campo}}"));
+        assert!(prompt.text.contains("como máximo 12 pasos"));
+        assert!(!prompt.text.contains("rechazado"));
+
+        let feedback = (
+            "{\"steps\": []}".to_string(),
+            vec!["el plan no tiene pasos".to_string()],
+        );
+        let retry = planning_prompt("t1", "Analiza el CV", &tools(), Some(&feedback)).unwrap();
+        assert!(retry.text.contains(
+            "Tu plan anterior fue rechazado:\n{\"steps\": []}\nErrores:\n- el plan no tiene pasos\nCorrige el plan."
+        ));
+    }
+
+    #[test]
+    fn schema_limits_tools_and_steps() {
+        let schema = plan_schema(&tools());
+        let plan = json!({ "steps": [
+            { "tool": "pdf_parser", "args": { "pdf_base64": "{{cv_base64}}" } },
+            { "tool": "llm", "args": { "prompt": "Resume {{steps.0.text}}" } }
+        ]});
+        assert!(validate(&schema, &plan).is_empty());
+
+        let unknown = json!({ "steps": [{ "tool": "rm", "args": {} }] });
+        assert_eq!(
+            validate(&schema, &unknown),
+            vec![r#"/steps/0/tool: "rm" no es uno de "pdf_parser", "llm""#]
+        );
+
+        let step = json!({ "tool": "llm", "args": { "prompt": "x" } });
+        let long = json!({ "steps": vec![step; MAX_PLAN_STEPS + 1] });
+        assert_eq!(
+            validate(&schema, &long),
+            vec![format!(
+                "/steps: como mucho {MAX_PLAN_STEPS} elementos y tiene {}",
+                MAX_PLAN_STEPS + 1
+            )]
+        );
+    }
+
+    #[test]
+    fn writers_stay_inside_the_output_dir() {
+        let dir = std::env::temp_dir().join(format!("planner_out_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let write = |path: PathBuf| json!({ "path": path, "content": "x" });
+
+        let inside = dir.join("informes").join("resumen.md");
+        assert!(check_output_path("file_writer", &write(inside), &dir).is_ok());
+        let escape = dir.join("..").join("fuera.md");
+        let err = check_output_path("file_writer", &write(escape), &dir).unwrap_err();
+        assert!(err.to_string().contains("fuera del directorio de salida"));
+        assert!(check_output_path("docx_writer", &write("/etc/passwd".into()), &dir).is_err());
+        assert!(check_output_path("excel_writer", &json!({}), &dir).is_err());
+        // Las herramientas que no escriben no se comprueban
+        assert!(check_output_path("web_search", &write("/etc".into()), &dir).is_ok());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn plans_are_checked_against_the_catalog() {
+        let plan: Plan = serde_json::from_value(json!({ "steps": [
+            { "tool": "pdf_parser", "args": {} },
+            { "tool": "llm", "args": { "prompt": "{{steps.1}} {{foo}}" } }
+        ]}))
+        .unwrap();
+        assert_eq!(
+            validate_plan(&plan, &tools()),
+            vec![
+                "paso 0: falta el argumento obligatorio 'pdf_base64' de 'pdf_parser'",
+                "paso 1: '{{steps.1}}' usa el paso 1, que no se ha ejecutado todavía",
+                "paso 1: variable desconocida '{{foo}}'",
+            ]
+        );
+    }
+}
@@ -0,0 +1,283 @@
+// agent_coordinator/src/profiles.rs
+//! Perfiles de búsqueda guardados (bucket KV `coordinator_profiles`) y su API
+//! request/reply:
+//!
+//! - `profiles.save`:   `SearchProfile` en JSON → guarda o reemplaza el perfil
+//! - `profiles.list`:   → `{"profiles": [...]}` con la próxima ejecución de cada uno
+//! - `profiles.delete`: `profile_id` (texto o `{"profile_id": ...}`) → `{"deleted": ...}`
+//! - `profiles.run`:    `profile_id` → encola una ejecución inmediata (`{"task_id": ...}`)
+//!
+//! El planificador (`scheduler`) encola cada perfil según su expresión cron.
+
+use anyhow::{anyhow, Result};
+use async_nats::jetstream::{self, kv};
+use async_nats::{Client, Message};
+use bytes::Bytes;
+use common::kv::{now_ms, open_bucket, parse_id, validate_key};
+use common::messaging::{
+    NATS_PROFILES_DELETE_SUBJECT, NATS_PROFILES_LIST_SUBJECT, NATS_PROFILES_RUN_SUBJECT,
+    NATS_PROFILES_SAVE_SUBJECT,
+};
+use common::offers::OfferFilters;
+use futures::{StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::{error, info};
+
+use crate::history::SeenOffer;
+use crate::scheduler;
+
+pub const PROFILES_BUCKET: &str = "coordinator_profiles";
+
+/// Búsqueda guardada que se repite según `schedule`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchProfile {
+    pub profile_id: String,
+    #[serde(default)]
+    pub name: String,
+    /// Texto del CV (p. ej. la salida de `pdf_extractor`).
+    #[serde(default)]
+    pub cv_text: Option<String>,
+    /// Perfil profesional ya extraído; se usa si no hay `cv_text`.
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// Consultas fijas. Si está vacío se generan con el LLM a partir del CV o perfil.
+    #[serde(default)]
+    pub queries: Vec<String>,
+    #[serde(default)]
+    pub filters: OfferFilters,
+    /// Excel de salida; por defecto `OUTPUT_DIR/ofertas_{task_id}.xlsx`.
+    #[serde(default)]
+    pub output_path: Option<String>,
+    /// Expresión cron en hora local: `min hora día mes día_semana` (o con segundos delante).
+    pub schedule: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+
+    // Estado mantenido por el Coordinador (se ignora al guardar)
+    #[serde(default)]
+    pub created_at_ms: u64,
+    #[serde(default)]
+    pub last_run_ms: Option<u64>,
+    #[serde(default)]
+    pub last_task_id: Option<String>,
+    /// Ofertas vistas en la última ejecución completada.
+    #[serde(default)]
+    pub last_offers: Vec<SeenOffer>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Serialize, Debug)]
+struct ProfileSummary {
+    profile_id: String,
+    name: String,
+    schedule: String,
+    enabled: bool,
+    queries: Vec<String>,
+    filters: OfferFilters,
+    output_path: Option<String>,
+    last_run_ms: Option<u64>,
+    last_task_id: Option<String>,
+    last_offer_count: usize,
+    next_run_ms: Option<u64>,
+}
+
+/// Acceso al bucket KV de perfiles.
+#[derive(Clone)]
+pub struct ProfileStore {
+    kv: kv::Store,
+}
+
+impl ProfileStore {
+    /// Abre el bucket, creándolo si no existe.
+    pub async fn open(js: &jetstream::Context) -> Result<Self> {
+        let kv = open_bucket(
+            js,
+            PROFILES_BUCKET,
+            "Perfiles de búsqueda programada del Coordinador",
+        )
+        .await?;
+        Ok(Self { kv })
+    }
+
+    pub async fn load(&self, profile_id: &str) -> Result<Option<SearchProfile>> {
+        match self.kv.get(profile_id).await? {
+            Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn save(&self, profile: &SearchProfile) -> Result<()> {
+        self.kv
+            .put(&profile.profile_id, serde_json::to_vec(profile)?.into())
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete(&self, profile_id: &str) -> Result<()> {
+        self.kv.delete(profile_id).await?;
+        Ok(())
+    }
+
+    pub async fn all(&self) -> Result<Vec<SearchProfile>> {
+        let keys: Vec<String> = self.kv.keys().await?.try_collect().await?;
+        let profiles = futures::stream::iter(keys)
+            .filter_map(|key| async move { self.load(&key).await.ok().flatten() })
+            .collect()
+            .await;
+        Ok(profiles)
+    }
+
+    /// Modifica un perfil con control de revisión, para no pisar cambios concurrentes
+    /// del planificador y de las tareas. Devuelve `None` si el perfil ya no existe.
+    pub async fn update<F>(&self, profile_id: &str, f: F) -> Result<Option<SearchProfile>>
+    where
+        F: Fn(&mut SearchProfile),
+    {
+        loop {
+            let Some(entry) = self.kv.entry(profile_id).await? else {
+                return Ok(None);
+            };
+            if entry.operation != kv::Operation::Put {
+                return Ok(None);
+            }
+            let mut profile: SearchProfile = serde_json::from_slice(&entry.value)?;
+            f(&mut profile);
+            match self
+                .kv
+                .update(
+                    profile_id,
+                    serde_json::to_vec(&profile)?.into(),
+                    entry.revision,
+                )
+                .await
+            {
+                Ok(_) => return Ok(Some(profile)),
+                Err(e) if e.kind() == kv::UpdateErrorKind::WrongLastRevision => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+/// Atiende los subjects `profiles.*` hasta que se cierre la conexión.
+pub async fn serve(client: Client, js: jetstream::Context, profiles: ProfileStore) -> Result<()> {
+    let save = client.subscribe(NATS_PROFILES_SAVE_SUBJECT).await?;
+    let list = client.subscribe(NATS_PROFILES_LIST_SUBJECT).await?;
+    let delete = client.subscribe(NATS_PROFILES_DELETE_SUBJECT).await?;
+    let run = client.subscribe(NATS_PROFILES_RUN_SUBJECT).await?;
+
+    let mut requests = futures::stream::select_all([save, list, delete, run]);
+    while let Some(msg) = requests.next().await {
+        let response = match handle(&js, &profiles, &msg).await {
+            Ok(value) => value.to_string(),
+            Err(e) => format!("ERROR: {e:#}"),
+        };
+        if let Some(reply) = msg.reply {
+            if let Err(e) = client
+                .publish(reply, Bytes::from(response.into_bytes()))
+                .await
+            {
+                error!("No se pudo responder a {}: {e}", msg.subject);
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn handle(js: &jetstream::Context, profiles: &ProfileStore, msg: &Message) -> Result<Value> {
+    match msg.subject.as_str() {
+        NATS_PROFILES_SAVE_SUBJECT => {
+            let profile: SearchProfile = serde_json::from_slice(&msg.payload)
+                .map_err(|e| anyhow!("el payload no es un perfil válido: {e}"))?;
+            let saved = save(profiles, profile).await?;
+            Ok(json!({
+                "profile_id": saved.profile_id,
+                "next_run_ms": scheduler::next_run_ms(&saved),
+            }))
+        }
+        NATS_PROFILES_LIST_SUBJECT => {
+            let mut all = profiles.all().await?;
+            all.sort_by(|a, b| a.profile_id.cmp(&b.profile_id));
+            let summaries: Vec<ProfileSummary> = all.into_iter().map(summary).collect();
+            Ok(json!({ "profiles": summaries }))
+        }
+        NATS_PROFILES_DELETE_SUBJECT => {
+            let profile_id = load(profiles, &msg.payload).await?.profile_id;
+            profiles.delete(&profile_id).await?;
+            info!("Perfil {profile_id} eliminado");
+            Ok(json!({ "deleted": profile_id }))
+        }
+        NATS_PROFILES_RUN_SUBJECT => {
+            let profile = load(profiles, &msg.payload).await?;
+            let task_id = scheduler::enqueue(js, profiles, &profile).await?;
+            Ok(json!({ "task_id": task_id }))
+        }
+        other => Err(anyhow!("subject no soportado '{other}'")),
+    }
+}
+
+/// Valida y guarda un perfil, conservando el estado de ejecuciones anteriores.
+async fn save(profiles: &ProfileStore, mut profile: SearchProfile) -> Result<SearchProfile> {
+    validate_key("profile_id", &profile.profile_id)?;
+    scheduler::parse_schedule(&profile.schedule)?;
+    profile.queries.retain(|q| !q.trim().is_empty());
+    let has_text = [&profile.cv_text, &profile.profile]
+        .into_iter()
+        .flatten()
+        .any(|t| !t.trim().is_empty());
+    if profile.queries.is_empty() && !has_text {
+        return Err(anyhow!(
+            "el perfil necesita 'queries' o un 'cv_text'/'profile' del que generarlas"
+        ));
+    }
+
+    match profiles.load(&profile.profile_id).await? {
+        Some(previous) => {
+            profile.created_at_ms = previous.created_at_ms;
+            profile.last_run_ms = previous.last_run_ms;
+            profile.last_task_id = previous.last_task_id;
+            profile.last_offers = previous.last_offers;
+        }
+        None => {
+            profile.created_at_ms = now_ms();
+            profile.last_run_ms = None;
+            profile.last_task_id = None;
+            profile.last_offers = Vec::new();
+        }
+    }
+    profiles.save(&profile).await?;
+    info!(
+        "Perfil {} guardado (schedule '{}')",
+        profile.profile_id, profile.schedule
+    );
+    Ok(profile)
+}
+
+async fn load(profiles: &ProfileStore, payload: &[u8]) -> Result<SearchProfile> {
+    let profile_id =
+        parse_id(payload, "profile_id").ok_or_else(|| anyhow!("falta el profile_id"))?;
+    profiles
+        .load(&profile_id)
+        .await?
+        .ok_or_else(|| anyhow!("perfil '{profile_id}' no encontrado"))
+}
+
+fn summary(profile: SearchProfile) -> ProfileSummary {
+    ProfileSummary {
+        next_run_ms: scheduler::next_run_ms(&profile),
+        last_offer_count: profile.last_offers.len(),
+        profile_id: profile.profile_id,
+        name: profile.name,
+        schedule: profile.schedule,
+        enabled: profile.enabled,
+        queries: profile.queries,
+        filters: profile.filters,
+        output_path: profile.output_path,
+        last_run_ms: profile.last_run_ms,
+        last_task_id: profile.last_task_id,
+    }
+}
@@ -0,0 +1,372 @@
+// agent_coordinator/src/queries.rs
+//! Generación de consultas de búsqueda variadas a partir del `CvProfile`.
+//!
+//! Se piden al LLM consultas que combinen sinónimos del puesto, habilidades clave,
+//! ubicación / remoto y redacción en español e inglés. Si el LLM falla o propone
+//! pocas, se completan con consultas construidas por reglas. Al final se descartan
+//! las casi idénticas y se limita el total a `SEARCH_MAX_QUERIES`.
+
+use anyhow::{anyhow, Context, Result};
+use async_nats::Client;
+use common::cv::{CvProfile, RemotePreference, Seniority};
+use common::offers::text::normalize;
+use common::prompts;
+use serde::Deserialize;
+use serde_json::json;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::env;
+use tracing::warn;
+
+use crate::agents::call_llm_json;
+use crate::dedup::dice;
+
+/// Consultas máximas por tarea por defecto (`SEARCH_MAX_QUERIES`).
+const DEFAULT_MAX_QUERIES: usize = 15;
+
+/// Por debajo de este número de consultas del LLM se completan con las de reglas.
+const MIN_LLM_QUERIES: usize = 3;
+
+/// Similitud (Dice sobre palabras) a partir de la cual dos consultas son la misma.
+const NEAR_DUPLICATE: f64 = 0.8;
+
+/// Puestos y palabras de puesto en español con su equivalente en inglés.
+const ROLE_TRANSLATIONS: &[(&str, &str)] = &[
+    ("desarrollador", "developer"),
+    ("desarrolladora", "developer"),
+    ("programador", "programmer"),
+    ("programadora", "programmer"),
+    ("ingeniero de software", "software engineer"),
+    ("ingeniera de software", "software engineer"),
+    ("ingeniero", "engineer"),
+    ("ingeniera", "engineer"),
+    ("analista de datos", "data analyst"),
+    ("analista", "analyst"),
+    ("cientifico de datos", "data scientist"),
+    ("cientifica de datos", "data scientist"),
+    ("jefe de proyecto", "project manager"),
+    ("jefa de proyecto", "project manager"),
+    ("administrador de sistemas", "system administrator"),
+    ("administradora de sistemas", "system administrator"),
+    ("disenador", "designer"),
+    ("disenadora", "designer"),
+    ("consultor", "consultant"),
+    ("consultora", "consultant"),
+    ("tecnico", "technician"),
+    ("tecnica", "technician"),
+    ("arquitecto", "architect"),
+    ("arquitecta", "architect"),
+];
+
+#[derive(Deserialize)]
+struct GeneratedQueries {
+    queries: Vec<String>,
+}
+
+/// Consultas máximas por tarea (`SEARCH_MAX_QUERIES`).
+pub fn max_queries() -> usize {
+    env::var("SEARCH_MAX_QUERIES")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(DEFAULT_MAX_QUERIES)
+        .max(1)
+}
+
+/// Consultas para el perfil: las del LLM, completadas con las de reglas si hacen falta,
+/// sin casi duplicados y como mucho `max_queries()`.
+pub async fn generate_queries(
+    client: &Client,
+    task_id: &str,
+    profile: &CvProfile,
+) -> Result<Vec<String>> {
+    let max = max_queries();
+    let mut queries = match llm_queries(client, task_id, profile, max).await {
+        Ok(queries) => dedup_queries(queries, max),
+        Err(e) => {
+            warn!("Tarea {task_id}: consultas por reglas, el LLM falló: {e:#}");
+            Vec::new()
+        }
+    };
+    if queries.len() < MIN_LLM_QUERIES.min(max) {
+        queries.extend(rule_queries(profile));
+        queries = dedup_queries(queries, max);
+    }
+    if queries.is_empty() {
+        return Err(anyhow!(
+            "no se pudo generar ninguna consulta: el perfil no tiene puestos ni habilidades"
+        ));
+    }
+    Ok(queries)
+}
+
+async fn llm_queries(
+    client: &Client,
+    task_id: &str,
+    profile: &CvProfile,
+    max: usize,
+) -> Result<Vec<String>> {
+    let prompt = prompts::render(
+        "query_gen",
+        task_id,
+        &json!({ "min": MIN_LLM_QUERIES, "max": max, "profile": profile }),
+    )?;
+    let schema = json!({
+        "type": "object",
+        "properties": {
+            "queries": {
+                "type": "array",
+                "items": { "type": "string", "minLength": 1 },
+                "minItems": 1
+            }
+        },
+        "required": ["queries"]
+    });
+    let json = call_llm_json(client, task_id, &prompt, &schema)
+        .await
+        .context("generación de consultas con el LLM")?;
+    let parsed: GeneratedQueries = serde_json::from_value(json)?;
+    Ok(parsed.queries)
+}
+
+/// Consultas construidas solo con el perfil: puesto (y su traducción) con ubicación o
+/// remoto, y habilidades principales con el puesto.
+fn rule_queries(profile: &CvProfile) -> Vec<String> {
+    let level = match profile.seniority {
+        Seniority::Junior => "junior",
+        Seniority::Senior => "senior",
+        Seniority::Lead => "lead",
+        Seniority::Unknown | Seniority::Mid => "",
+    };
+    let city = profile
+        .location
+        .as_deref()
+        .and_then(|l| l.split(',').next())
+        .map(str::trim)
+        .filter(|c| !c.is_empty());
+    let places: Vec<(&str, &str)> = match (profile.remote, city) {
+        (RemotePreference::Remote, _) | (_, None) => vec![("remoto", "remote")],
+        (RemotePreference::Any, Some(city)) => vec![(city, city), ("remoto", "remote")],
+        (_, Some(city)) => vec![(city, city)],
+    };
+
+    let mut skills: Vec<_> = profile.skills.iter().collect();
+    skills.sort_by_key(|s| Reverse((s.years.unwrap_or(0.0) * 10.0) as u32));
+    let skills: Vec<&str> = skills.iter().take(3).map(|s| s.name.trim()).collect();
+
+    let mut roles: Vec<String> = profile
+        .roles
+        .iter()
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty())
+        .take(3)
+        .collect();
+    if roles.is_empty() {
+        if let Some(skill) = skills.first() {
+            roles.push(format!("desarrollador {skill}"));
+        }
+    }
+
+    let mut queries = Vec::new();
+    for role in &roles {
+        let english = translate_role(role);
+        for (es, en) in &places {
+            queries.push(format!("{level} {role} {es}"));
+            if let Some(english) = &english {
+                queries.push(format!("{level} {english} {en}"));
+            }
+        }
+    }
+    if let Some(role) = roles.first() {
+        let english = translate_role(role).unwrap_or_else(|| role.clone());
+        let (es, en) = places[0];
+        for pair in skills.windows(2) {
+            queries.push(format!("{} {} {role} {es}", pair[0], pair[1]));
+        }
+        for skill in &skills {
+            queries.push(format!("{skill} {english} {en}"));
+        }
+    }
+    queries
+}
+
+/// Traducción al inglés de las palabras de puesto conocidas, si hay alguna.
+fn translate_role(role: &str) -> Option<String> {
+    let mut text = format!(" {} ", normalize(role));
+    let mut translated = false;
+    for (es, en) in ROLE_TRANSLATIONS {
+        let word = format!(" {es} ");
+        if text.contains(&word) {
+            text = text.replace(&word, &format!(" {en} "));
+            translated = true;
+        }
+    }
+    translated.then(|| text.trim().to_string())
+}
+
+/// Quita espacios sobrantes, consultas vacías y casi duplicadas (mismas palabras o
+/// similitud ≥ 0,8) manteniendo el orden, y se queda con las `max` primeras.
+pub fn dedup_queries(queries: Vec<String>, max: usize) -> Vec<String> {
+    let mut kept: Vec<(String, HashSet<String>)> = Vec::new();
+    for query in queries {
+        let query = query.split_whitespace().collect::<Vec<_>>().join(" ");
+        let words: HashSet<String> = normalize(&query)
+            .split_whitespace()
+            .map(str::to_string)
+            .collect();
+        if words.is_empty() || kept.iter().any(|(_, w)| dice(w, &words) >= NEAR_DUPLICATE) {
+            continue;
+        }
+        kept.push((query, words));
+        if kept.len() == max {
+            break;
+        }
+    }
+    kept.into_iter().map(|(query, _)| query).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::cv::Skill;
+
+    fn skill(name: &str, years: Option<f32>) -> Skill {
+        Skill {
+            name: name.to_string(),
+            years,
+        }
+    }
+
+    /// Consultas de reglas con los espacios simplificados (sin nivel queda uno delante).
+    fn rules(profile: &CvProfile) -> Vec<String> {
+        rule_queries(profile)
+            .iter()
+            .map(|q| q.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect()
+    }
+
+    #[test]
+    fn rule_queries_combine_role_places_and_top_skills() {
+        let profile = CvProfile {
+            skills: vec![
+                skill("SQL", None),
+                skill("Go", Some(2.0)),
+                skill("Rust", Some(5.0)),
+                skill("Docker", Some(1.0)),
+            ],
+            roles: vec!["Desarrollador backend".into()],
+            location: Some("Sevilla, España".into()),
+            remote: RemotePreference::Any,
+            seniority: Seniority::Senior,
+            ..Default::default()
+        };
+        assert_eq!(
+            rules(&profile),
+            [
+                "senior Desarrollador backend Sevilla",
+                "senior developer backend Sevilla",
+                "senior Desarrollador backend remoto",
+                "senior developer backend remote",
+                "Rust Go Desarrollador backend Sevilla",
+                "Go Docker Desarrollador backend Sevilla",
+                "Rust developer backend Sevilla",
+                "Go developer backend Sevilla",
+                "Docker developer backend Sevilla",
+            ]
+        );
+    }
+
+    #[test]
+    fn rule_queries_pick_places_from_the_remote_preference() {
+        let onsite = CvProfile {
+            roles: vec!["Jardinero".into()],
+            location: Some("Bilbao".into()),
+            remote: RemotePreference::Onsite,
+            ..Default::default()
+        };
+        assert_eq!(rules(&onsite), ["Jardinero Bilbao"]);
+
+        let remote = CvProfile {
+            location: Some("Bilbao".into()),
+            remote: RemotePreference::Remote,
+            ..onsite.clone()
+        };
+        assert_eq!(rules(&remote), ["Jardinero remoto"]);
+
+        let nowhere = CvProfile {
+            location: None,
+            remote: RemotePreference::Onsite,
+            ..onsite
+        };
+        assert_eq!(rules(&nowhere), ["Jardinero remoto"]);
+    }
+
+    #[test]
+    fn rule_queries_fall_back_to_the_main_skill_without_roles() {
+        let profile = CvProfile {
+            skills: vec![skill("Python", None)],
+            seniority: Seniority::Junior,
+            ..Default::default()
+        };
+        assert_eq!(
+            rules(&profile),
+            [
+                "junior desarrollador Python remoto",
+                "junior developer python remote",
+                "Python developer python remote",
+            ]
+        );
+        assert!(rule_queries(&CvProfile::default()).is_empty());
+    }
+
+    #[test]
+    fn translate_role_prefers_the_longest_known_phrase() {
+        assert_eq!(
+            translate_role("Desarrollador Backend").as_deref(),
+            Some("developer backend")
+        );
+        assert_eq!(
+            translate_role("Ingeniera de Software").as_deref(),
+            Some("software engineer")
+        );
+        assert_eq!(
+            translate_role("Científico de datos junior").as_deref(),
+            Some("data scientist junior")
+        );
+        assert_eq!(
+            translate_role("Ingeniero de caminos").as_deref(),
+            Some("engineer de caminos")
+        );
+        assert_eq!(translate_role("Jardinero"), None);
+        assert_eq!(translate_role("Backend developer"), None);
+    }
+
+    #[test]
+    fn dedup_queries_drops_near_duplicates_and_keeps_order() {
+        let queries = vec![
+            "  rust   developer  madrid ".to_string(),
+            "Rust Developer Madrid".to_string(),
+            "madrid rust developer".to_string(),
+            "".to_string(),
+            "   ".to_string(),
+            "desarrollador rust madrid".to_string(),
+            // Dice 0,86 con la anterior: casi duplicada
+            "Desarrollador Rust en Madrid".to_string(),
+            // Dice 0,75: se conserva
+            "desarrollador rust senior en madrid".to_string(),
+            "python data engineer remote".to_string(),
+        ];
+        assert_eq!(
+            dedup_queries(queries.clone(), 10),
+            [
+                "rust developer madrid",
+                "desarrollador rust madrid",
+                "desarrollador rust senior en madrid",
+                "python data engineer remote",
+            ]
+        );
+        assert_eq!(
+            dedup_queries(queries, 2),
+            ["rust developer madrid", "desarrollador rust madrid"]
+        );
+    }
+}
@@ -0,0 +1,596 @@
+// agent_coordinator/src/queue.rs
+//! Cola durable de tareas sobre JetStream.
+//!
+//! Las `InitialTask` se publican en `agents.coordinator.tasks` (stream `COORDINATOR_TASKS`,
+//! retención work-queue) y se consumen con ack explícito. Si una tarea falla se hace NAK
+//! con espera y JetStream la reentrega; al agotar `max_deliver` se copia a
+//! `agents.coordinator.dlq` (stream `COORDINATOR_DLQ`) y se termina la entrega.
+//!
+//! Al arrancar, [`recover_orphans`] revisa las tareas sin terminar cuyo mensaje ya no está
+//! en la cola (p. ej. el proceso cayó durante su última entrega): se vuelven a encolar una
+//! vez y, si se pierden de nuevo, van a la DLQ.
+
+use anyhow::Result;
+use async_nats::jetstream::{
+    self,
+    consumer::{pull, AckPolicy, PullConsumer},
+    message::AckKind,
+    stream::{self, RetentionPolicy, StorageType},
+};
+use async_nats::{header, Client, HeaderMap};
+use bytes::Bytes;
+use common::cancel::InFlight;
+use common::kv::now_ms;
+use common::messaging::{NATS_COORDINATOR_DLQ_SUBJECT, NATS_COORDINATOR_TASKS_SUBJECT};
+use common::task::{InitialTask, TaskStatus};
+use futures::future::Aborted;
+use futures::StreamExt;
+use std::collections::HashSet;
+use std::env;
+use std::future::Future;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+use crate::cancel::CANCEL_REASON;
+use crate::profiles::ProfileStore;
+use crate::status::{progress, publish_status};
+use crate::store::{validate_task_id, TaskRecord, TaskRun, TaskStore};
+
+pub const TASKS_STREAM: &str = "COORDINATOR_TASKS";
+pub const DLQ_STREAM: &str = "COORDINATOR_DLQ";
+const CONSUMER_NAME: &str = "coordinator";
+
+/// Veces que una tarea huérfana se vuelve a encolar antes de enviarla a la DLQ.
+const MAX_REQUEUES: u32 = 1;
+
+/// Parámetros de la cola (configurables por ENV).
+#[derive(Debug, Clone)]
+pub struct QueueSettings {
+    /// Tiempo sin ack antes de que JetStream reentregue (`COORDINATOR_ACK_WAIT_SECS`).
+    pub ack_wait: Duration,
+    /// Entregas máximas de una tarea antes de ir a la DLQ (`COORDINATOR_MAX_DELIVER`).
+    pub max_deliver: i64,
+    /// Espera antes de reintentar una tarea fallida (`COORDINATOR_RETRY_DELAY_SECS`).
+    pub retry_delay: Duration,
+    /// Tareas procesadas a la vez (`COORDINATOR_MAX_CONCURRENT_TASKS`).
+    pub concurrency: usize,
+}
+
+impl QueueSettings {
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            env::var(name)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(default)
+        }
+        Self {
+            ack_wait: Duration::from_secs(var("COORDINATOR_ACK_WAIT_SECS", 60u64).max(2)),
+            max_deliver: var("COORDINATOR_MAX_DELIVER", 3i64).max(1),
+            retry_delay: Duration::from_secs(var("COORDINATOR_RETRY_DELAY_SECS", 10u64)),
+            concurrency: var("COORDINATOR_MAX_CONCURRENT_TASKS", 4usize).max(1),
+        }
+    }
+}
+
+/// Crea (si no existen) los streams de tareas y DLQ y el consumer durable.
+pub async fn setup(js: &jetstream::Context, settings: &QueueSettings) -> Result<PullConsumer> {
+    let tasks = js
+        .get_or_create_stream(stream::Config {
+            name: TASKS_STREAM.to_string(),
+            subjects: vec![NATS_COORDINATOR_TASKS_SUBJECT.to_string()],
+            retention: RetentionPolicy::WorkQueue,
+            storage: StorageType::File,
+            ..Default::default()
+        })
+        .await?;
+    js.get_or_create_stream(stream::Config {
+        name: DLQ_STREAM.to_string(),
+        subjects: vec![NATS_COORDINATOR_DLQ_SUBJECT.to_string()],
+        storage: StorageType::File,
+        ..Default::default()
+    })
+    .await?;
+
+    let consumer = tasks
+        .get_or_create_consumer(CONSUMER_NAME, consumer_config(settings))
+        .await?;
+    Ok(consumer)
+}
+
+/// Consumer durable de la cola. Con `max_ack_pending` el servidor no entrega más tareas
+/// de las que se procesan a la vez: una tarea que esperase en el cliente sin heartbeat
+/// se reentregaría al pasar `ack_wait` y se ejecutaría dos veces.
+fn consumer_config(settings: &QueueSettings) -> pull::Config {
+    pull::Config {
+        durable_name: Some(CONSUMER_NAME.to_string()),
+        ack_policy: AckPolicy::Explicit,
+        ack_wait: settings.ack_wait,
+        max_deliver: settings.max_deliver,
+        max_ack_pending: settings.concurrency as i64,
+        ..Default::default()
+    }
+}
+
+/// Encola una tarea. El `task_id` se usa como `Nats-Msg-Id`, así que un reenvío
+/// de la misma tarea dentro de la ventana de duplicados no se procesa dos veces.
+pub async fn submit(js: &jetstream::Context, task: &InitialTask) -> Result<()> {
+    publish_task(js, task, &task.task_id).await
+}
+
+async fn publish_task(js: &jetstream::Context, task: &InitialTask, msg_id: &str) -> Result<()> {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::NATS_MESSAGE_ID, msg_id);
+    js.publish_with_headers(
+        NATS_COORDINATOR_TASKS_SUBJECT,
+        headers,
+        serde_json::to_vec(task)?.into(),
+    )
+    .await?
+    .await?;
+    Ok(())
+}
+
+/// Qué hacer al arrancar con una tarea sin terminar.
+#[derive(Debug, PartialEq, Eq)]
+enum Recovery {
+    /// Su mensaje sigue en la cola: JetStream la reentregará.
+    Queued,
+    Requeue,
+    DeadLetter(String),
+}
+
+fn recovery(record: &TaskRecord, queued: &HashSet<String>) -> Recovery {
+    if queued.contains(&record.task_id) {
+        Recovery::Queued
+    } else if record.task.is_none() {
+        Recovery::DeadLetter("el mensaje se perdió y no hay copia de la tarea".to_string())
+    } else if record.requeued >= MAX_REQUEUES {
+        Recovery::DeadLetter(format!(
+            "el mensaje se perdió de nuevo tras volver a encolarla {} veces",
+            record.requeued
+        ))
+    } else {
+        Recovery::Requeue
+    }
+}
+
+/// Vuelve a encolar (o envía a la DLQ y marca como fallidas) las tareas sin terminar
+/// cuyo mensaje ya no está en la cola. Sin esto, una tarea que cae en su última entrega
+/// se quedaría en curso para siempre.
+pub async fn recover_orphans(
+    client: &Client,
+    js: &jetstream::Context,
+    store: &TaskStore,
+) -> Result<()> {
+    let unfinished = store.unfinished().await?;
+    if unfinished.is_empty() {
+        return Ok(());
+    }
+    let queued = queued_task_ids(js).await?;
+    for mut record in unfinished {
+        let task_id = record.task_id.clone();
+        match recovery(&record, &queued) {
+            Recovery::Queued => info!(
+                "Tarea {task_id} pendiente con {} pasos completados; se reanudará al reentregarse",
+                record.steps.len()
+            ),
+            Recovery::Requeue => {
+                let Some(task) = record.task.as_ref().map(|t| t.restore()).transpose()? else {
+                    continue;
+                };
+                record.requeued += 1;
+                store.save(&record).await?;
+                // `Nats-Msg-Id` distinto: el original puede seguir en la ventana de duplicados
+                let msg_id = format!("{task_id}-requeue-{}", record.requeued);
+                publish_task(js, &task, &msg_id).await?;
+                warn!("Tarea {task_id} sin mensaje en la cola; se ha vuelto a encolar");
+            }
+            Recovery::DeadLetter(reason) => {
+                error!("Tarea {task_id} huérfana: {reason}");
+                let payload = match record.task.as_ref().map(|t| t.restore()).transpose() {
+                    Ok(Some(task)) => serde_json::to_vec(&task)?.into(),
+                    _ => Bytes::new(),
+                };
+                dead_letter(js, payload, Some(&task_id), 0, &reason).await;
+                let status = TaskStatus::Failed(reason);
+                store
+                    .save(&TaskRecord {
+                        status: status.clone(),
+                        updated_at_ms: now_ms(),
+                        ..record
+                    })
+                    .await?;
+                publish_status(client, &task_id, status).await;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Si la tarea `task_id` sigue en el stream de la cola.
+pub async fn is_queued(js: &jetstream::Context, task_id: &str) -> Result<bool> {
+    Ok(queued_task_ids(js).await?.contains(task_id))
+}
+
+/// `task_id` de las tareas que siguen en el stream de la cola.
+async fn queued_task_ids(js: &jetstream::Context) -> Result<HashSet<String>> {
+    let mut stream = js.get_stream(TASKS_STREAM).await?;
+    let state = stream.info().await?.state.clone();
+    let mut ids = HashSet::new();
+    if state.messages == 0 {
+        return Ok(ids);
+    }
+    for seq in state.first_sequence..=state.last_sequence {
+        // Los huecos son mensajes ya confirmados
+        let Ok(msg) = stream.get_raw_message(seq).await else {
+            continue;
+        };
+        if let Ok(task) = serde_json::from_slice::<InitialTask>(&msg.payload) {
+            ids.insert(task.task_id);
+        }
+    }
+    Ok(ids)
+}
+
+/// Consume la cola indefinidamente, procesando hasta `concurrency` tareas a la vez.
+pub async fn consume(
+    client: Client,
+    js: jetstream::Context,
+    store: TaskStore,
+    profiles: ProfileStore,
+    inflight: InFlight,
+    consumer: PullConsumer,
+    settings: QueueSettings,
+) -> Result<()> {
+    // Pedir solo `concurrency` mensajes por lote también limita un consumer creado antes
+    // con otro `max_ack_pending`
+    let messages = consumer
+        .stream()
+        .max_messages_per_batch(settings.concurrency)
+        .messages()
+        .await?;
+    messages
+        .for_each_concurrent(settings.concurrency, |msg| {
+            let (client, js, store, profiles, inflight, settings) =
+                (&client, &js, &store, &profiles, &inflight, &settings);
+            async move {
+                match msg {
+                    Ok(msg) => handle(client, js, store, profiles, inflight, msg, settings).await,
+                    Err(e) => error!("Error recibiendo tareas de JetStream: {e}"),
+                }
+            }
+        })
+        .await;
+    Ok(())
+}
+
+async fn handle(
+    client: &Client,
+    js: &jetstream::Context,
+    store: &TaskStore,
+    profiles: &ProfileStore,
+    inflight: &InFlight,
+    msg: jetstream::Message,
+    settings: &QueueSettings,
+) {
+    let delivered = msg.info().map(|i| i.delivered).unwrap_or(1);
+
+    let task: InitialTask = match serde_json::from_slice(&msg.payload) {
+        Ok(task) => task,
+        Err(e) => {
+            // Un payload corrupto no va a mejorar con reintentos
+            error!("Mensaje de la cola sin InitialTask válida: {e}");
+            dead_letter(
+                js,
+                msg.payload.clone(),
+                None,
+                delivered,
+                &format!("payload no válido: {e}"),
+            )
+            .await;
+            ack(&msg, AckKind::Term).await;
+            return;
+        }
+    };
+    let task_id = task.task_id.clone();
+    if let Err(e) = validate_task_id(&task_id) {
+        error!("{e}");
+        dead_letter(js, msg.payload.clone(), None, delivered, &e.to_string()).await;
+        ack(&msg, AckKind::Term).await;
+        return;
+    }
+
+    let mut run = match store.begin(&task).await {
+        Ok(run) => run,
+        Err(e) => {
+            warn!("No se pudo cargar el estado de la tarea {task_id}: {e:#}");
+            ack(&msg, AckKind::Nak(Some(settings.retry_delay))).await;
+            return;
+        }
+    };
+    if run.record().is_finished() {
+        // Reentrega de una tarea que ya terminó (p. ej. el ack se perdió en un reinicio)
+        info!("Tarea {task_id} ya finalizada; se descarta la reentrega");
+        ack(&msg, AckKind::Ack).await;
+        return;
+    }
+
+    if run.completed_steps() > 0 {
+        progress(
+            client,
+            &task_id,
+            &format!("Reanudada en el paso {}", run.completed_steps() + 1),
+        )
+        .await;
+    } else if delivered > 1 {
+        progress(
+            client,
+            &task_id,
+            &format!("Reintento {delivered}/{}", settings.max_deliver),
+        )
+        .await;
+    }
+
+    let result = with_heartbeat(
+        &msg,
+        settings.ack_wait,
+        run_cancellable(
+            inflight,
+            &task_id,
+            cancelled_in_store(store, &task_id),
+            crate::execute_task(client, &task, profiles, &mut run),
+        ),
+    )
+    .await;
+    let result = match result {
+        Ok(result) => result,
+        Err(Aborted) => {
+            info!("Tarea {task_id} cancelada");
+            let status = TaskStatus::Cancelled(CANCEL_REASON.to_string());
+            save_final(&mut run, status.clone()).await;
+            publish_status(client, &task_id, status).await;
+            ack(&msg, AckKind::Term).await;
+            return;
+        }
+    };
+    match result {
+        Ok(result) => {
+            info!("Tarea {task_id} completada: {result}");
+            let status = TaskStatus::Completed(result);
+            save_final(&mut run, status.clone()).await;
+            publish_status(client, &task_id, status).await;
+            ack(&msg, AckKind::Ack).await;
+        }
+        Err(e) if delivered < settings.max_deliver => {
+            warn!("Tarea {task_id} fallida (intento {delivered}): {e:#}");
+            progress(
+                client,
+                &task_id,
+                &format!(
+                    "Fallo en el intento {delivered}/{}, se reintentará: {e:#}",
+                    settings.max_deliver
+                ),
+            )
+            .await;
+            ack(&msg, AckKind::Nak(Some(settings.retry_delay))).await;
+        }
+        Err(e) => {
+            error!("Tarea {task_id} fallida definitivamente: {e:#}");
+            let status = TaskStatus::Failed(format!("{e:#}"));
+            save_final(&mut run, status.clone()).await;
+            publish_status(client, &task_id, status).await;
+            dead_letter(
+                js,
+                msg.payload.clone(),
+                Some(&task_id),
+                delivered,
+                &format!("{e:#}"),
+            )
+            .await;
+            ack(&msg, AckKind::Term).await;
+        }
+    }
+}
+
+/// Ejecuta `fut` registrada en `inflight` bajo `task_id`. Una cancelación que llega
+/// después de `TaskStore::begin` pero antes del registro solo queda en el KV, así que
+/// tras registrarse se comprueba `cancelled` y, si es `true`, no se ejecuta.
+async fn run_cancellable<C, F>(
+    inflight: &InFlight,
+    task_id: &str,
+    cancelled: C,
+    fut: F,
+) -> Result<F::Output, Aborted>
+where
+    C: Future<Output = bool>,
+    F: Future,
+{
+    inflight
+        .run(task_id, async {
+            if cancelled.await {
+                return Err(Aborted);
+            }
+            Ok(fut.await)
+        })
+        .await
+        .and_then(|output| output)
+}
+
+async fn cancelled_in_store(store: &TaskStore, task_id: &str) -> bool {
+    match store.load(task_id).await {
+        Ok(Some(record)) => matches!(record.status, TaskStatus::Cancelled(_)),
+        Ok(None) => false,
+        Err(e) => {
+            warn!("No se pudo comprobar si la tarea {task_id} se canceló: {e:#}");
+            false
+        }
+    }
+}
+
+/// Ejecuta `fut` enviando `AckKind::Progress` cada `ack_wait / 2` para que JetStream
+/// no reentregue una tarea larga que sigue en marcha.
+async fn with_heartbeat<F: Future>(
+    msg: &jetstream::Message,
+    ack_wait: Duration,
+    fut: F,
+) -> F::Output {
+    let mut ticker = tokio::time::interval(ack_wait / 2);
+    ticker.tick().await; // el primer tick es inmediato
+    tokio::pin!(fut);
+    loop {
+        tokio::select! {
+            out = &mut fut => return out,
+            _ = ticker.tick() => {
+                if let Err(e) = msg.ack_with(AckKind::Progress).await {
+                    warn!("No se pudo extender el ack de la tarea: {e}");
+                }
+            }
+        }
+    }
+}
+
+async fn save_final(run: &mut TaskRun, status: TaskStatus) {
+    if let Err(e) = run.finish(status).await {
+        error!(
+            "No se pudo guardar el estado final de la tarea {}: {e:#}",
+            run.record().task_id
+        );
+    }
+}
+
+async fn ack(msg: &jetstream::Message, kind: AckKind) {
+    if let Err(e) = msg.ack_with(kind).await {
+        error!("No se pudo confirmar el mensaje de la cola: {e}");
+    }
+}
+
+/// Copia el mensaje a la DLQ con el motivo y el número de entregas en cabeceras.
+async fn dead_letter(
+    js: &jetstream::Context,
+    payload: Bytes,
+    task_id: Option<&str>,
+    delivered: i64,
+    reason: &str,
+) {
+    let mut headers = HeaderMap::new();
+    if let Some(id) = task_id {
+        headers.insert("Task-Id", id);
+    }
+    headers.insert("Dead-Letter-Reason", reason);
+    headers.insert("Delivered", delivered.to_string().as_str());
+
+    let published = match js
+        .publish_with_headers(NATS_COORDINATOR_DLQ_SUBJECT, headers, payload)
+        .await
+    {
+        Ok(ack) => ack.await.map(|_| ()).map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    if let Err(e) = published {
+        error!("No se pudo publicar en la DLQ: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{SavedTask, TaskReport};
+
+    fn task(task_id: &str) -> InitialTask {
+        serde_json::from_value(serde_json::json!({
+            "task_id": task_id,
+            "task_name": "demo",
+            "pdf_content": [37, 80, 68, 70],
+        }))
+        .unwrap()
+    }
+
+    fn record(task_id: &str, saved: bool, requeued: u32) -> TaskRecord {
+        TaskRecord {
+            task_id: task_id.to_string(),
+            task_name: "demo".to_string(),
+            status: TaskStatus::InProgress("En ejecución".to_string()),
+            steps: Vec::new(),
+            offers: None,
+            report: TaskReport::default(),
+            task: saved.then(|| SavedTask::new(&task(task_id))),
+            requeued,
+            created_at_ms: 0,
+            updated_at_ms: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn cancellations_before_registration_are_not_lost() {
+        let inflight = InFlight::new();
+        let ran = std::sync::atomic::AtomicBool::new(false);
+        let work = async { ran.store(true, std::sync::atomic::Ordering::SeqCst) };
+
+        // Cancelada en el KV antes de registrarse: no se ejecuta
+        let result = run_cancellable(&inflight, "t1", async { true }, work).await;
+        assert_eq!(result, Err(Aborted));
+        assert!(!ran.load(std::sync::atomic::Ordering::SeqCst));
+
+        // Cancelada una vez registrada: se aborta
+        let result = run_cancellable(&inflight, "t1", async { false }, async {
+            inflight.cancel("t1");
+            tokio::task::yield_now().await;
+        })
+        .await;
+        assert_eq!(result, Err(Aborted));
+
+        assert_eq!(
+            run_cancellable(&inflight, "t1", async { false }, async { 7 }).await,
+            Ok(7)
+        );
+    }
+
+    #[test]
+    fn consumer_holds_at_most_concurrency_tasks() {
+        let settings = QueueSettings {
+            ack_wait: Duration::from_secs(60),
+            max_deliver: 3,
+            retry_delay: Duration::from_secs(10),
+            concurrency: 4,
+        };
+        let config = consumer_config(&settings);
+        assert_eq!(config.max_ack_pending, 4);
+        assert_eq!(config.ack_wait, Duration::from_secs(60));
+        assert_eq!(config.max_deliver, 3);
+    }
+
+    #[test]
+    fn task_still_in_queue_is_left_for_redelivery() {
+        let queued = HashSet::from(["t1".to_string()]);
+        assert_eq!(recovery(&record("t1", true, 0), &queued), Recovery::Queued);
+        assert_eq!(recovery(&record("t1", false, 5), &queued), Recovery::Queued);
+    }
+
+    #[test]
+    fn orphan_is_requeued_once_then_dead_lettered() {
+        let queued = HashSet::new();
+        assert_eq!(recovery(&record("t1", true, 0), &queued), Recovery::Requeue);
+        assert!(matches!(
+            recovery(&record("t1", true, MAX_REQUEUES), &queued),
+            Recovery::DeadLetter(_)
+        ));
+    }
+
+    #[test]
+    fn orphan_without_saved_task_is_dead_lettered() {
+        assert!(matches!(
+            recovery(&record("t1", false, 0), &HashSet::new()),
+            Recovery::DeadLetter(_)
+        ));
+    }
+
+    #[test]
+    fn saved_task_restores_the_pdf() {
+        let saved = SavedTask::new(&task("t1"));
+        assert!(saved.task.pdf_content.is_empty());
+        let restored = saved.restore().unwrap();
+        assert_eq!(restored.task_id, "t1");
+        assert_eq!(restored.pdf_content, b"%PDF");
+    }
+}
@@ -0,0 +1,224 @@
+// agent_coordinator/src/scheduler.rs
+//! Planificador de búsquedas recurrentes: revisa los perfiles guardados cada
+//! `SCHEDULER_TICK` y encola en la cola de tareas los que toca ejecutar.
+//!
+//! Si el Coordinador estuvo parado cuando tocaba una ejecución, se lanza una sola al
+//! arrancar (no una por cada ejecución perdida).
+
+use anyhow::{anyhow, Result};
+use async_nats::jetstream;
+use chrono::{DateTime, Local};
+use common::kv::now_ms;
+use common::task::InitialTask;
+use cron::Schedule;
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::{error, info};
+
+use crate::profiles::{ProfileStore, SearchProfile};
+use crate::queue;
+
+/// Cada cuánto se comprueba si algún perfil debe ejecutarse.
+const SCHEDULER_TICK: Duration = Duration::from_secs(30);
+
+/// Comprueba periódicamente los perfiles y encola los que estén pendientes.
+pub async fn run(js: jetstream::Context, profiles: ProfileStore) -> Result<()> {
+    let mut ticker = tokio::time::interval(SCHEDULER_TICK);
+    loop {
+        ticker.tick().await;
+        let all = match profiles.all().await {
+            Ok(all) => all,
+            Err(e) => {
+                error!("No se pudieron leer los perfiles programados: {e:#}");
+                continue;
+            }
+        };
+        let now = now_ms();
+        for profile in all {
+            if next_run_ms(&profile).is_none_or(|next| next > now) {
+                continue;
+            }
+            if let Err(e) = enqueue(&js, &profiles, &profile).await {
+                error!(
+                    "No se pudo encolar el perfil programado {}: {e:#}",
+                    profile.profile_id
+                );
+            }
+        }
+    }
+}
+
+/// Encola una ejecución del perfil y la anota como su última ejecución.
+pub async fn enqueue(
+    js: &jetstream::Context,
+    profiles: &ProfileStore,
+    profile: &SearchProfile,
+) -> Result<String> {
+    let now = now_ms();
+    let task_id = format!("{}-{now}", profile.profile_id);
+    let task = InitialTask {
+        task_id: task_id.clone(),
+        task_name: format!("Búsqueda programada '{}'", display_name(profile)),
+        pdf_content: Vec::new(),
+        goal: None,
+        profile_id: Some(profile.profile_id.clone()),
+        filters: profile.filters.clone(),
+        cover_letters: None,
+        gap_analysis: None,
+    };
+
+    // Se anota antes de encolar: si el encolado falla se pierde una ejecución, pero no se
+    // encola la misma dos veces en el siguiente tick.
+    profiles
+        .update(&profile.profile_id, |p| {
+            p.last_run_ms = Some(now);
+            p.last_task_id = Some(task_id.clone());
+        })
+        .await?
+        .ok_or_else(|| anyhow!("perfil '{}' no encontrado", profile.profile_id))?;
+    queue::submit(js, &task).await?;
+    info!(
+        "Perfil {} encolado como tarea {task_id}",
+        profile.profile_id
+    );
+    Ok(task_id)
+}
+
+/// Próxima ejecución (ms Unix) de un perfil activo: la primera ocurrencia de su
+/// `schedule` posterior a la última ejecución (o a su creación).
+pub fn next_run_ms(profile: &SearchProfile) -> Option<u64> {
+    if !profile.enabled {
+        return None;
+    }
+    let schedule = parse_schedule(&profile.schedule).ok()?;
+    let since = profile.last_run_ms.unwrap_or(profile.created_at_ms);
+    let since = DateTime::from_timestamp_millis(since as i64)?.with_timezone(&Local);
+    let next = schedule.after(&since).next()?;
+    u64::try_from(next.timestamp_millis()).ok()
+}
+
+/// Acepta cron estándar de 5 campos (`min hora día mes día_semana`, con el día de la
+/// semana de 0 a 7 y 0 y 7 = domingo) o el formato del crate `cron` con segundos (6 o 7
+/// campos, donde el 1 es el domingo).
+pub fn parse_schedule(expr: &str) -> Result<Schedule> {
+    let expr = expr.trim();
+    let fields: Vec<&str> = expr.split_whitespace().collect();
+    let expr = if let [minute, hour, day, month, weekday] = fields[..] {
+        format!(
+            "0 {minute} {hour} {day} {month} {}",
+            cron_weekdays(weekday)?
+        )
+    } else {
+        expr.to_string()
+    };
+    Schedule::from_str(&expr).map_err(|e| anyhow!("expresión cron no válida '{expr}': {e}"))
+}
+
+/// Traduce el día de la semana de cron estándar (0-7, domingo = 0 o 7) a la numeración
+/// del crate `cron` (1-7, domingo = 1). Los nombres (`Mon-Fri`) se dejan como están.
+fn cron_weekdays(field: &str) -> Result<String> {
+    let day = |text: &str| -> Result<u32> {
+        match text.parse::<u32>() {
+            Ok(n) if n <= 7 => Ok(n % 7 + 1),
+            _ => Err(anyhow!(
+                "día de la semana '{text}' no válido (0-7 o nombre)"
+            )),
+        }
+    };
+    let mut parts = Vec::new();
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, Some(step)),
+            None => (item, None),
+        };
+        let numeric = range.chars().all(|c| c.is_ascii_digit() || c == '-');
+        if range == "*" || range.is_empty() || !numeric {
+            parts.push(item.to_string());
+            continue;
+        }
+        let step = step.map(|s| format!("/{s}")).unwrap_or_default();
+        match range.split_once('-') {
+            None => parts.push(format!("{}{step}", day(range)?)),
+            Some((from, to)) => {
+                let (from_n, to_n) = (from.parse::<u32>().ok(), to.parse::<u32>().ok());
+                // `N-7` termina en domingo, que en el crate es el 1: se parte en dos
+                if to_n == Some(7) && from_n.is_some_and(|n| n > 0) {
+                    if !step.is_empty() {
+                        return Err(anyhow!(
+                            "día de la semana '{item}' no válido: usa 0 para el domingo con '/'"
+                        ));
+                    }
+                    parts.push(format!("{}-7,1", day(from)?));
+                } else if to_n == Some(7) {
+                    parts.push(format!("1-7{step}"));
+                } else {
+                    parts.push(format!("{}-{}{step}", day(from)?, day(to)?));
+                }
+            }
+        }
+    }
+    Ok(parts.join(","))
+}
+
+fn display_name(profile: &SearchProfile) -> &str {
+    if profile.name.trim().is_empty() {
+        &profile.profile_id
+    } else {
+        &profile.name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Datelike, TimeZone, Utc, Weekday};
+    use std::collections::BTreeSet;
+
+    /// Días de la semana de las ejecuciones de las próximas dos semanas.
+    fn weekdays(expr: &str) -> BTreeSet<u32> {
+        let start = Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap();
+        parse_schedule(expr)
+            .unwrap()
+            .after(&start)
+            .take_while(|t| (*t - start).num_days() < 14)
+            .map(|t| t.weekday().num_days_from_sunday())
+            .collect()
+    }
+
+    fn days(list: &[Weekday]) -> BTreeSet<u32> {
+        list.iter().map(|d| d.num_days_from_sunday()).collect()
+    }
+
+    #[test]
+    fn numeric_weekdays_follow_standard_cron() {
+        use Weekday::*;
+        assert_eq!(weekdays("0 8 * * 1-5"), days(&[Mon, Tue, Wed, Thu, Fri]));
+        assert_eq!(weekdays("0 8 * * 0"), days(&[Sun]));
+        assert_eq!(weekdays("0 8 * * 7"), days(&[Sun]));
+        assert_eq!(weekdays("0 8 * * 5-7"), days(&[Fri, Sat, Sun]));
+        assert_eq!(weekdays("0 8 * * 0,6"), days(&[Sun, Sat]));
+        assert_eq!(weekdays("0 8 * * 1/2"), days(&[Mon, Wed, Fri]));
+    }
+
+    #[test]
+    fn named_weekdays_are_unchanged() {
+        use Weekday::*;
+        assert_eq!(
+            weekdays("0 8 * * Mon-Fri"),
+            days(&[Mon, Tue, Wed, Thu, Fri])
+        );
+        assert_eq!(weekdays("0 8 * * *").len(), 7);
+        assert_eq!(weekdays("0 8 * * 0-7").len(), 7);
+    }
+
+    #[test]
+    fn six_field_expressions_use_the_crate_numbering() {
+        assert_eq!(weekdays("0 0 8 * * 1"), days(&[Weekday::Sun]));
+    }
+
+    #[test]
+    fn invalid_weekdays_are_rejected() {
+        assert!(parse_schedule("0 8 * * 8").is_err());
+        assert!(parse_schedule("0 8 * * 3-7/2").is_err());
+    }
+}
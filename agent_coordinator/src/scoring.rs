@@ -0,0 +1,503 @@
+// agent_coordinator/src/scoring.rs
+//! Puntuación explicable de cada oferta frente al `CvProfile` del candidato.
+//!
+//! Las ofertas solo traen título, empresa y ubicación, así que la puntuación se calcula
+//! con reglas sobre ese texto y no con el LLM: el mismo perfil y la misma oferta dan
+//! siempre la misma puntuación, y cada parte dice de dónde salen sus puntos.
+//!
+//! | Parte       | Máx. | Regla                                                        |
+//! |-------------|------|--------------------------------------------------------------|
+//! | Habilidades | 50   | habilidades del perfil que menciona la oferta frente a las que pide y faltan; si no menciona ninguna, encaje del puesto con los del perfil |
+//! | Seniority   | 20   | nivel del título (junior, senior, lead...) frente al del perfil |
+//! | Ubicación   | 20   | remoto / ciudad del perfil frente a la preferencia de modalidad |
+//! | Idiomas     | 10   | idiomas que pide la oferta y habla el candidato              |
+
+use common::cv::{CvProfile, RemotePreference, Seniority};
+use common::offers::text::{mentions, normalize, offer_text, seniority, work_mode};
+use common::offers::{JobOffer, MatchPart, MatchScore};
+
+const SKILLS_MAX: u8 = 50;
+const SENIORITY_MAX: u8 = 20;
+const LOCATION_MAX: u8 = 20;
+const LANGUAGE_MAX: u8 = 10;
+
+/// Habilidades técnicas habituales en los títulos de las ofertas. Si la oferta menciona
+/// alguna que no está en el perfil, cuenta como habilidad que falta.
+const KNOWN_SKILLS: &[&str] = &[
+    "rust",
+    "golang",
+    "python",
+    "java",
+    "kotlin",
+    "scala",
+    "javascript",
+    "typescript",
+    "node",
+    "react",
+    "angular",
+    "vue",
+    "c++",
+    "c#",
+    "net",
+    "php",
+    "ruby",
+    "rails",
+    "django",
+    "flask",
+    "spring",
+    "sql",
+    "postgresql",
+    "mysql",
+    "mongodb",
+    "redis",
+    "kafka",
+    "docker",
+    "kubernetes",
+    "aws",
+    "azure",
+    "gcp",
+    "terraform",
+    "linux",
+    "devops",
+    "machine learning",
+    "swift",
+    "android",
+    "ios",
+    "flutter",
+    "sap",
+    "salesforce",
+    "power bi",
+    "tableau",
+    "figma",
+    "seo",
+];
+
+/// Idiomas que puede pedir una oferta y cómo pueden aparecer escritos.
+const LANGUAGES: &[(&str, &[&str])] = &[
+    ("inglés", &["ingles", "english"]),
+    ("español", &["espanol", "castellano", "spanish"]),
+    ("francés", &["frances", "french"]),
+    ("alemán", &["aleman", "german", "deutsch"]),
+    ("portugués", &["portugues", "portuguese"]),
+    ("italiano", &["italiano", "italian"]),
+    ("catalán", &["catalan"]),
+    ("euskera", &["euskera", "basque"]),
+    ("chino", &["chino", "chinese", "mandarin"]),
+];
+
+/// Puntúa cada oferta frente al perfil.
+pub fn score_offers(profile: &CvProfile, offers: &mut [JobOffer]) {
+    for offer in offers {
+        offer.score = Some(score_offer(profile, offer));
+    }
+}
+
+/// Índices de las `top` primeras ofertas (ya ordenadas por puntuación) con al menos
+/// `min_score`. Sin `min_score` también entran las ofertas sin puntuar.
+pub fn shortlist(offers: &[JobOffer], top: usize, min_score: Option<u8>) -> Vec<usize> {
+    offers
+        .iter()
+        .enumerate()
+        .filter(|(_, o)| {
+            min_score.is_none_or(|min| o.score.as_ref().is_some_and(|s| s.score >= min))
+        })
+        .map(|(i, _)| i)
+        .take(top)
+        .collect()
+}
+
+pub fn score_offer(profile: &CvProfile, offer: &JobOffer) -> MatchScore {
+    let text = offer_text(offer);
+
+    let (skills, skills_matched, skills_missing) = skills_part(profile, &text);
+    let seniority = seniority_part(profile, &text);
+    let location = location_part(profile, offer, &text);
+    let language = language_part(profile, &text);
+
+    let parts = [&skills, &seniority, &location, &language];
+    let score = parts.iter().map(|p| p.points).sum::<u8>().min(100);
+    let summary = parts
+        .iter()
+        .map(|p| p.reason.as_str())
+        .collect::<Vec<_>>()
+        .join("; ");
+    MatchScore {
+        score,
+        summary,
+        skills_matched,
+        skills_missing,
+        skills,
+        seniority,
+        location,
+        language,
+    }
+}
+
+fn skills_part(profile: &CvProfile, text: &str) -> (MatchPart, Vec<String>, Vec<String>) {
+    let candidate: Vec<String> = profile
+        .skills
+        .iter()
+        .map(|s| normalize(&s.name))
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    let matched: Vec<String> = profile
+        .skills
+        .iter()
+        .filter(|s| mentions(text, &normalize(&s.name)))
+        .map(|s| s.name.clone())
+        .collect();
+    let missing: Vec<String> = KNOWN_SKILLS
+        .iter()
+        .filter(|term| mentions(text, term))
+        .filter(|term| {
+            !candidate
+                .iter()
+                .any(|skill| mentions(skill, term) || mentions(term, skill))
+        })
+        .map(|term| term.to_string())
+        .collect();
+
+    let asked = matched.len() + missing.len();
+    let skills = if asked > 0 {
+        let points = share(SKILLS_MAX, matched.len(), asked);
+        let mut reason = format!("habilidades {}/{asked}", matched.len());
+        if !matched.is_empty() {
+            reason.push_str(&format!(" ({})", matched.join(", ")));
+        }
+        if !missing.is_empty() {
+            reason.push_str(&format!(", faltan {}", missing.join(", ")));
+        }
+        part(points, SKILLS_MAX, reason)
+    } else if let Some(role) = profile
+        .roles
+        .iter()
+        .find(|r| role_matches(text, &normalize(r)))
+    {
+        part(
+            share(SKILLS_MAX, 7, 10),
+            SKILLS_MAX,
+            format!("sin habilidades concretas, el puesto encaja con '{role}'"),
+        )
+    } else {
+        part(
+            share(SKILLS_MAX, 3, 10),
+            SKILLS_MAX,
+            "la oferta no menciona habilidades del perfil".to_string(),
+        )
+    };
+    (skills, matched, missing)
+}
+
+fn seniority_part(profile: &CvProfile, text: &str) -> MatchPart {
+    let offered = seniority(text);
+    let neutral = share(SENIORITY_MAX, 7, 10);
+    match (offered, profile.seniority) {
+        (None, _) => part(neutral, SENIORITY_MAX, "sin nivel indicado".to_string()),
+        (Some(offered), Seniority::Unknown) => part(
+            neutral,
+            SENIORITY_MAX,
+            format!("nivel {} (perfil sin nivel)", offered.as_str()),
+        ),
+        (Some(offered), own) => {
+            let distance = (offered as i32 - own as i32).abs();
+            let (points, fit) = match distance {
+                0 => (SENIORITY_MAX, "encaja"),
+                1 => (share(SENIORITY_MAX, 1, 2), "cercano"),
+                _ => (0, "no encaja"),
+            };
+            part(
+                points,
+                SENIORITY_MAX,
+                format!("nivel {} {fit} con {}", offered.as_str(), own.as_str()),
+            )
+        }
+    }
+}
+
+fn location_part(profile: &CvProfile, offer: &JobOffer, text: &str) -> MatchPart {
+    let mode = work_mode(text);
+    let remote = mode == Some(RemotePreference::Remote);
+    let hybrid = mode == Some(RemotePreference::Hybrid);
+    let city = profile
+        .location
+        .as_deref()
+        .and_then(|l| l.split(',').next())
+        .map(normalize)
+        .filter(|c| !c.is_empty());
+    let in_city = city.as_deref().is_some_and(|c| mentions(text, c));
+    let city_name = || {
+        profile
+            .location
+            .as_deref()
+            .and_then(|l| l.split(',').next())
+            .unwrap_or_default()
+            .trim()
+            .to_string()
+    };
+
+    let (points, reason) = if remote {
+        match profile.remote {
+            RemotePreference::Onsite => (
+                share(LOCATION_MAX, 1, 2),
+                "remoto, prefiere presencial".to_string(),
+            ),
+            _ => (LOCATION_MAX, "remoto".to_string()),
+        }
+    } else if in_city {
+        let mode = if hybrid { "híbrido " } else { "" };
+        match profile.remote {
+            RemotePreference::Remote if !hybrid => (
+                share(LOCATION_MAX, 6, 10),
+                format!("en {}, prefiere remoto", city_name()),
+            ),
+            _ => (LOCATION_MAX, format!("{mode}en {}", city_name())),
+        }
+    } else if offer.location.trim().is_empty() {
+        (
+            share(LOCATION_MAX, 1, 2),
+            "ubicación no indicada".to_string(),
+        )
+    } else if city.is_some() {
+        (0, format!("fuera de {}", city_name()))
+    } else {
+        (
+            share(LOCATION_MAX, 1, 2),
+            "perfil sin ubicación".to_string(),
+        )
+    };
+    part(points, LOCATION_MAX, reason)
+}
+
+fn language_part(profile: &CvProfile, text: &str) -> MatchPart {
+    let spoken: Vec<String> = profile
+        .languages
+        .iter()
+        .map(|l| normalize(&l.language))
+        .collect();
+    let asked: Vec<(&str, bool)> = LANGUAGES
+        .iter()
+        .filter(|(_, names)| names.iter().any(|n| mentions(text, n)))
+        .map(|(name, names)| {
+            let speaks = spoken.iter().any(|s| names.contains(&s.as_str()));
+            (*name, speaks)
+        })
+        .collect();
+
+    if asked.is_empty() {
+        return part(
+            LANGUAGE_MAX,
+            LANGUAGE_MAX,
+            "sin idiomas pedidos".to_string(),
+        );
+    }
+    let spoken_count = asked.iter().filter(|(_, speaks)| *speaks).count();
+    let points = share(LANGUAGE_MAX, spoken_count, asked.len());
+    let missing: Vec<&str> = asked
+        .iter()
+        .filter(|(_, speaks)| !speaks)
+        .map(|(name, _)| *name)
+        .collect();
+    let reason = if missing.is_empty() {
+        let names: Vec<&str> = asked.iter().map(|(name, _)| *name).collect();
+        format!("habla {}", names.join(", "))
+    } else {
+        format!("no consta {}", missing.join(", "))
+    };
+    part(points, LANGUAGE_MAX, reason)
+}
+
+/// El título comparte con el puesto al menos la mitad de sus palabras significativas.
+fn role_matches(text: &str, role: &str) -> bool {
+    let words: Vec<&str> = role.split_whitespace().filter(|w| w.len() > 2).collect();
+    if words.is_empty() {
+        return false;
+    }
+    let found = words.iter().filter(|w| mentions(text, w)).count();
+    found * 2 >= words.len()
+}
+
+/// `num/den` de `max` puntos, redondeado hacia abajo.
+fn share(max: u8, num: usize, den: usize) -> u8 {
+    (max as usize * num / den) as u8
+}
+
+fn part(points: u8, max: u8, reason: String) -> MatchPart {
+    MatchPart {
+        points,
+        max,
+        reason,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::cv::{Skill, SpokenLanguage};
+
+    fn profile() -> CvProfile {
+        CvProfile {
+            skills: ["Rust", "Python"]
+                .iter()
+                .map(|name| Skill {
+                    name: name.to_string(),
+                    years: None,
+                })
+                .collect(),
+            roles: vec!["Desarrollador backend".to_string()],
+            languages: vec![SpokenLanguage {
+                language: "Inglés".to_string(),
+                level: Some("C1".to_string()),
+            }],
+            location: Some("Madrid, España".to_string()),
+            seniority: Seniority::Senior,
+            ..Default::default()
+        }
+    }
+
+    fn offer(title: &str, location: &str) -> JobOffer {
+        JobOffer {
+            title: title.to_string(),
+            company: "Acme".to_string(),
+            location: location.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn full_match_scores_every_part() {
+        let score = score_offer(&profile(), &offer("Senior Rust Developer inglés", "Remoto"));
+        assert_eq!(score.skills.points, SKILLS_MAX);
+        assert_eq!(score.seniority.points, SENIORITY_MAX);
+        assert_eq!(score.location.points, LOCATION_MAX);
+        assert_eq!(score.language.points, LANGUAGE_MAX);
+        assert_eq!(score.score, 100);
+        assert_eq!(score.skills_matched, vec!["Rust"]);
+        assert!(score.skills_missing.is_empty());
+    }
+
+    #[test]
+    fn skills_share_matched_against_missing() {
+        let score = score_offer(&profile(), &offer("Rust Kubernetes Developer", "Madrid"));
+        assert_eq!(score.skills.points, 25);
+        assert_eq!(score.skills_missing, vec!["kubernetes"]);
+        assert!(score.skills.reason.contains("faltan kubernetes"));
+
+        let role = score_offer(&profile(), &offer("Desarrollador Backend", "Madrid"));
+        assert_eq!(role.skills.points, 35);
+        let unrelated = score_offer(&profile(), &offer("Dependiente", "Madrid"));
+        assert_eq!(unrelated.skills.points, 15);
+    }
+
+    #[test]
+    fn seniority_depends_on_distance() {
+        let points = |title: &str| score_offer(&profile(), &offer(title, "Madrid")).seniority;
+        assert_eq!(points("Senior Rust").points, 20);
+        assert_eq!(points("Mid Rust").points, 10);
+        assert_eq!(points("Junior Rust").points, 0);
+        assert_eq!(points("Rust").points, 14);
+
+        let unknown = CvProfile {
+            seniority: Seniority::Unknown,
+            ..profile()
+        };
+        assert_eq!(
+            score_offer(&unknown, &offer("Junior Rust", "Madrid"))
+                .seniority
+                .points,
+            14
+        );
+    }
+
+    #[test]
+    fn location_follows_city_and_remote_preference() {
+        let points = |p: &CvProfile, location: &str| {
+            score_offer(p, &offer("Rust", location)).location.points
+        };
+        let base = profile();
+        assert_eq!(points(&base, "Madrid"), 20);
+        assert_eq!(points(&base, "Barcelona"), 0);
+        assert_eq!(points(&base, ""), 10);
+        assert_eq!(points(&base, "Remote"), 20);
+
+        let onsite = CvProfile {
+            remote: RemotePreference::Onsite,
+            ..profile()
+        };
+        assert_eq!(points(&onsite, "Remoto"), 10);
+
+        let remote = CvProfile {
+            remote: RemotePreference::Remote,
+            ..profile()
+        };
+        assert_eq!(points(&remote, "Madrid"), 12);
+        assert_eq!(points(&remote, "Madrid (híbrido)"), 20);
+    }
+
+    #[test]
+    fn languages_count_only_the_ones_asked() {
+        let none = score_offer(&profile(), &offer("Rust", "Madrid"));
+        assert_eq!(none.language.points, LANGUAGE_MAX);
+
+        let spoken = score_offer(&profile(), &offer("Rust English", "Madrid"));
+        assert_eq!(spoken.language.points, LANGUAGE_MAX);
+
+        let half = score_offer(&profile(), &offer("Rust inglés y alemán", "Madrid"));
+        assert_eq!(half.language.points, 5);
+        assert_eq!(half.language.reason, "no consta alemán");
+    }
+
+    #[test]
+    fn empty_profile_or_offer_gets_neutral_points() {
+        let empty = score_offer(&CvProfile::default(), &JobOffer::default());
+        assert_eq!(empty.skills.points, 15);
+        assert_eq!(empty.seniority.points, 14);
+        assert_eq!(empty.location.points, 10);
+        assert_eq!(empty.language.points, 10);
+        assert_eq!(empty.score, 49);
+
+        let no_profile = score_offer(&CvProfile::default(), &offer("Senior Rust", "Madrid"));
+        assert_eq!(no_profile.skills.points, 0);
+        assert_eq!(no_profile.location.reason, "perfil sin ubicación");
+        assert_eq!(no_profile.score, 34);
+
+        let no_offer = score_offer(&profile(), &JobOffer::default());
+        assert_eq!(no_offer.location.reason, "ubicación no indicada");
+        assert!(no_offer.score <= 100);
+    }
+
+    #[test]
+    fn score_stays_within_bounds() {
+        let worst = score_offer(&profile(), &offer("Junior Java alemán", "Barcelona"));
+        assert_eq!(worst.score, 0);
+
+        let titles = [
+            "Senior Rust Python Developer English remoto",
+            "Lead Kotlin Scala Java Go alemán francés",
+            "",
+            "Rust Rust Rust senior senior inglés inglés",
+        ];
+        for title in titles {
+            let score = score_offer(&profile(), &offer(title, "Madrid"));
+            assert!(score.score <= 100, "{title}: {}", score.score);
+            for part in [
+                &score.skills,
+                &score.seniority,
+                &score.location,
+                &score.language,
+            ] {
+                assert!(part.points <= part.max, "{title}: {}", part.reason);
+            }
+            let sum: u8 = [
+                &score.skills,
+                &score.seniority,
+                &score.location,
+                &score.language,
+            ]
+            .iter()
+            .map(|p| p.points)
+            .sum();
+            assert_eq!(score.score, sum);
+        }
+    }
+}
@@ -0,0 +1,29 @@
+// agent_coordinator/src/status.rs
+//! Publicación de actualizaciones de estado de las tareas en `agents.status`.
+
+use async_nats::Client;
+use common::messaging::NATS_UI_STATUS_SUBJECT;
+use common::task::{TaskStatus, TaskUpdate};
+use tracing::{error, info};
+
+/// Publica un paso intermedio (`TaskStatus::InProgress`) y lo deja en el log.
+pub async fn progress(client: &Client, task_id: &str, msg: &str) {
+    info!("Tarea {task_id}: {msg}");
+    publish_status(client, task_id, TaskStatus::InProgress(msg.to_string())).await;
+}
+
+/// Publica un [`TaskUpdate`] en `agents.status`.
+pub async fn publish_status(client: &Client, task_id: &str, status: TaskStatus) {
+    let update = TaskUpdate {
+        task_id: task_id.to_string(),
+        status,
+    };
+    match serde_json::to_vec(&update) {
+        Ok(payload) => {
+            if let Err(e) = client.publish(NATS_UI_STATUS_SUBJECT, payload.into()).await {
+                error!("No se pudo publicar el estado de la tarea {task_id}: {e}");
+            }
+        }
+        Err(e) => error!("No se pudo serializar el estado de la tarea {task_id}: {e}"),
+    }
+}
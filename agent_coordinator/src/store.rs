@@ -0,0 +1,309 @@
+// agent_coordinator/src/store.rs
+//! Estado persistente de las tareas en un bucket KV de JetStream (`coordinator_tasks`).
+//!
+//! Cada tarea guarda, bajo su `task_id`, el estado y el resultado de cada paso
+//! completado. Si el proceso se reinicia, JetStream reentrega la tarea de la cola y
+//! el pipeline salta los pasos que ya tienen checkpoint. El registro guarda también una
+//! copia de la `InitialTask` para volver a encolarla si su mensaje ya no está en la cola
+//! (ver `queue::recover_orphans`).
+
+use anyhow::Result;
+use async_nats::jetstream::{self, kv};
+use base64::{engine::general_purpose, Engine as _};
+use common::kv::{now_ms, open_bucket, validate_key};
+use common::offers::JobOffer;
+use common::task::{InitialTask, TaskStatus};
+use futures::{StreamExt, TryStreamExt};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::future::Future;
+
+pub const TASKS_BUCKET: &str = "coordinator_tasks";
+
+/// Resultado guardado de un paso completado.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StepCheckpoint {
+    pub name: String,
+    pub output: Value,
+    pub started_at_ms: u64,
+    pub finished_at_ms: u64,
+}
+
+/// Informe de una tarea: incidencias que no la hacen fallar.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TaskReport {
+    /// Búsquedas que fallaron en la última ejecución; la tarea sigue con el resto.
+    #[serde(default)]
+    pub search_failures: Vec<SearchFailure>,
+    /// Pasos opcionales que no se pudieron completar (p. ej. el perfil estructurado).
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    /// Ofertas descartadas por los filtros de la tarea, con el motivo.
+    #[serde(default)]
+    pub excluded_offers: Vec<ExcludedOffer>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ExcludedOffer {
+    pub title: String,
+    pub source_url: String,
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchFailure {
+    pub query: String,
+    pub error: String,
+}
+
+/// Copia de la tarea encolada. El PDF va en Base64: en la `InitialTask` es un array JSON
+/// de números, varias veces más grande.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SavedTask {
+    /// La tarea con `pdf_content` vacío.
+    pub task: InitialTask,
+    pub pdf_base64: String,
+}
+
+impl SavedTask {
+    pub fn new(task: &InitialTask) -> Self {
+        SavedTask {
+            task: InitialTask {
+                pdf_content: Vec::new(),
+                ..task.clone()
+            },
+            pdf_base64: general_purpose::STANDARD.encode(&task.pdf_content),
+        }
+    }
+
+    pub fn restore(&self) -> Result<InitialTask> {
+        Ok(InitialTask {
+            pdf_content: general_purpose::STANDARD.decode(&self.pdf_base64)?,
+            ..self.task.clone()
+        })
+    }
+}
+
+/// Estado persistido de una tarea.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TaskRecord {
+    pub task_id: String,
+    pub task_name: String,
+    pub status: TaskStatus,
+    pub steps: Vec<StepCheckpoint>,
+    /// Ofertas finales de la tarea (las que se escriben en el Excel).
+    #[serde(default)]
+    pub offers: Option<Vec<JobOffer>>,
+    #[serde(default)]
+    pub report: TaskReport,
+    /// La tarea tal como se encoló (los registros antiguos no la tienen).
+    #[serde(default)]
+    pub task: Option<SavedTask>,
+    /// Veces que se ha vuelto a encolar al arrancar porque su mensaje se había perdido.
+    #[serde(default)]
+    pub requeued: u32,
+    pub created_at_ms: u64,
+    pub updated_at_ms: u64,
+}
+
+impl TaskRecord {
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self.status,
+            TaskStatus::Completed(_) | TaskStatus::Failed(_) | TaskStatus::Cancelled(_)
+        )
+    }
+}
+
+/// Acceso al bucket KV de tareas.
+#[derive(Clone)]
+pub struct TaskStore {
+    kv: kv::Store,
+}
+
+impl TaskStore {
+    /// Abre el bucket, creándolo si no existe.
+    pub async fn open(js: &jetstream::Context) -> Result<Self> {
+        let kv = open_bucket(
+            js,
+            TASKS_BUCKET,
+            "Checkpoints de las tareas del Coordinador",
+        )
+        .await?;
+        Ok(Self { kv })
+    }
+
+    pub async fn load(&self, task_id: &str) -> Result<Option<TaskRecord>> {
+        match self.kv.get(task_id).await? {
+            Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn save(&self, record: &TaskRecord) -> Result<()> {
+        self.kv
+            .put(&record.task_id, serde_json::to_vec(record)?.into())
+            .await?;
+        Ok(())
+    }
+
+    /// Devuelve todas las tareas guardadas.
+    pub async fn all(&self) -> Result<Vec<TaskRecord>> {
+        let keys: Vec<String> = self.kv.keys().await?.try_collect().await?;
+        let records = futures::stream::iter(keys)
+            .filter_map(|key| async move { self.load(&key).await.ok().flatten() })
+            .collect()
+            .await;
+        Ok(records)
+    }
+
+    /// Tareas que no llegaron a un estado final (candidatas a reanudarse).
+    pub async fn unfinished(&self) -> Result<Vec<TaskRecord>> {
+        let mut records = self.all().await?;
+        records.retain(|r| !r.is_finished());
+        Ok(records)
+    }
+
+    /// Marca como cancelada una tarea que aún no ha empezado (o que sigue en la cola sin
+    /// registro), para que se descarte al salir de la cola. Devuelve `false` si ya había
+    /// terminado.
+    pub async fn mark_cancelled(&self, task_id: &str, reason: &str) -> Result<bool> {
+        let now = now_ms();
+        let mut record = self.load(task_id).await?.unwrap_or(TaskRecord {
+            task_id: task_id.to_string(),
+            task_name: String::new(),
+            status: TaskStatus::Idle,
+            steps: Vec::new(),
+            offers: None,
+            report: TaskReport::default(),
+            task: None,
+            requeued: 0,
+            created_at_ms: now,
+            updated_at_ms: now,
+        });
+        if record.is_finished() {
+            return Ok(false);
+        }
+        record.status = TaskStatus::Cancelled(reason.to_string());
+        record.updated_at_ms = now;
+        self.save(&record).await?;
+        Ok(true)
+    }
+
+    /// Carga (o crea) el registro de una tarea para ejecutarla con checkpoints.
+    pub async fn begin(&self, task: &InitialTask) -> Result<TaskRun> {
+        let now = now_ms();
+        let mut record = self.load(&task.task_id).await?.unwrap_or(TaskRecord {
+            task_id: task.task_id.clone(),
+            task_name: task.task_name.clone(),
+            status: TaskStatus::Idle,
+            steps: Vec::new(),
+            offers: None,
+            report: TaskReport::default(),
+            task: None,
+            requeued: 0,
+            created_at_ms: now,
+            updated_at_ms: now,
+        });
+        if !record.is_finished() {
+            if record.task.is_none() {
+                record.task = Some(SavedTask::new(task));
+            }
+            record.status = TaskStatus::InProgress("En ejecución".to_string());
+            record.updated_at_ms = now;
+            self.save(&record).await?;
+        }
+        Ok(TaskRun {
+            store: self.clone(),
+            record,
+        })
+    }
+}
+
+/// Ejecución de una tarea con checkpoints por paso.
+pub struct TaskRun {
+    store: TaskStore,
+    record: TaskRecord,
+}
+
+impl TaskRun {
+    pub fn record(&self) -> &TaskRecord {
+        &self.record
+    }
+
+    /// Número de pasos ya completados (y guardados) de la tarea.
+    pub fn completed_steps(&self) -> usize {
+        self.record.steps.len()
+    }
+
+    /// Ejecuta un paso con nombre único dentro de la tarea. Si ya tiene checkpoint se
+    /// devuelve el resultado guardado sin ejecutar `fut`; si no, se ejecuta y se guarda.
+    pub async fn step<T, F>(&mut self, name: &str, fut: F) -> Result<T>
+    where
+        T: Serialize + DeserializeOwned,
+        F: Future<Output = Result<T>>,
+    {
+        if let Some(done) = self.checkpoint(name)? {
+            return Ok(done);
+        }
+
+        let started_at_ms = now_ms();
+        let output = fut.await?;
+        self.save_step(name, &output, started_at_ms).await?;
+        Ok(output)
+    }
+
+    /// Resultado guardado de un paso, si ya se completó.
+    pub fn checkpoint<T: DeserializeOwned>(&self, name: &str) -> Result<Option<T>> {
+        match self.record.steps.iter().find(|s| s.name == name) {
+            Some(done) => Ok(Some(serde_json::from_value(done.output.clone())?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Guarda el resultado de un paso ejecutado fuera de [`TaskRun::step`] (p. ej. varios
+    /// pasos en paralelo, que no pueden tomar `&mut self` a la vez).
+    pub async fn save_step<T: Serialize>(
+        &mut self,
+        name: &str,
+        output: &T,
+        started_at_ms: u64,
+    ) -> Result<()> {
+        let finished_at_ms = now_ms();
+        self.record.steps.push(StepCheckpoint {
+            name: name.to_string(),
+            output: serde_json::to_value(output)?,
+            started_at_ms,
+            finished_at_ms,
+        });
+        self.record.updated_at_ms = finished_at_ms;
+        self.store.save(&self.record).await
+    }
+
+    /// Modifica y guarda el informe de la tarea.
+    pub async fn update_report<F: FnOnce(&mut TaskReport)>(&mut self, f: F) -> Result<()> {
+        f(&mut self.record.report);
+        self.record.updated_at_ms = now_ms();
+        self.store.save(&self.record).await
+    }
+
+    /// Guarda la lista final de ofertas, consultable después con `tasks.result`.
+    pub async fn set_offers(&mut self, offers: &[JobOffer]) -> Result<()> {
+        self.record.offers = Some(offers.to_vec());
+        self.record.updated_at_ms = now_ms();
+        self.store.save(&self.record).await
+    }
+
+    /// Guarda el estado final (o intermedio) de la tarea.
+    pub async fn finish(&mut self, status: TaskStatus) -> Result<()> {
+        self.record.status = status;
+        self.record.updated_at_ms = now_ms();
+        self.store.save(&self.record).await
+    }
+}
+
+/// Los `task_id` se usan como clave KV: solo se admiten `[A-Za-z0-9_=-]`.
+pub fn validate_task_id(task_id: &str) -> Result<()> {
+    Ok(validate_key("task_id", task_id)?)
+}
@@ -0,0 +1,293 @@
+// agent_coordinator/src/task_api.rs
+//! API de consulta de tareas (request/reply, JSON) sobre el bucket `coordinator_tasks`:
+//!
+//! - `tasks.list`:   `{"status": "completed", "from": "2026-10-01", "to": 1760000000000}`
+//!   (todos los campos opcionales; fechas como `YYYY-MM-DD` o milisegundos Unix)
+//! - `tasks.get`:    `task_id` (texto o `{"task_id": ...}`) → estado, pasos, tiempos e informe
+//! - `tasks.result`: `task_id` → ruta del archivo generado, ofertas y perfil del CV en JSON
+//!
+//! Los errores se responden como texto `ERROR: ...`, igual que el resto de agentes.
+
+use anyhow::{anyhow, Result};
+use async_nats::{Client, Message};
+use bytes::Bytes;
+use chrono::NaiveDate;
+use common::cv::CvProfile;
+use common::kv::parse_id;
+use common::messaging::{
+    NATS_TASKS_GET_SUBJECT, NATS_TASKS_LIST_SUBJECT, NATS_TASKS_RESULT_SUBJECT,
+};
+use common::offers::JobOffer;
+use common::task::TaskStatus;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::error;
+
+use crate::pipeline::CV_PROFILE_STEP;
+use crate::store::{TaskRecord, TaskReport, TaskStore};
+
+#[derive(Deserialize, Debug, Default)]
+struct ListFilter {
+    /// `idle`, `in_progress`, `completed`, `failed` o `cancelled`
+    status: Option<String>,
+    /// Creadas a partir de esta fecha (inclusive)
+    from: Option<Value>,
+    /// Creadas hasta esta fecha (inclusive; un `YYYY-MM-DD` cubre el día entero)
+    to: Option<Value>,
+}
+
+#[derive(Serialize, Debug)]
+struct TaskSummary {
+    task_id: String,
+    task_name: String,
+    state: &'static str,
+    status: TaskStatus,
+    steps_completed: usize,
+    created_at_ms: u64,
+    updated_at_ms: u64,
+}
+
+#[derive(Serialize, Debug)]
+struct StepTiming {
+    name: String,
+    started_at_ms: u64,
+    finished_at_ms: u64,
+    duration_ms: u64,
+}
+
+#[derive(Serialize, Debug)]
+struct TaskDetail {
+    #[serde(flatten)]
+    summary: TaskSummary,
+    duration_ms: u64,
+    steps: Vec<StepTiming>,
+    report: TaskReport,
+}
+
+#[derive(Serialize, Debug)]
+struct TaskResult {
+    task_id: String,
+    state: &'static str,
+    status: TaskStatus,
+    /// Ruta del archivo generado (solo si la tarea se completó)
+    path: Option<String>,
+    offers: Option<Vec<JobOffer>>,
+    /// Perfil estructurado extraído del CV, si se llegó a extraer
+    cv_profile: Option<CvProfile>,
+}
+
+/// Atiende los tres subjects de consulta hasta que se cierre la conexión.
+pub async fn serve(client: Client, store: TaskStore) -> Result<()> {
+    let list = client.subscribe(NATS_TASKS_LIST_SUBJECT).await?;
+    let get = client.subscribe(NATS_TASKS_GET_SUBJECT).await?;
+    let result = client.subscribe(NATS_TASKS_RESULT_SUBJECT).await?;
+
+    let mut requests = futures::stream::select_all([list, get, result]);
+    while let Some(msg) = requests.next().await {
+        let response = match handle(&store, &msg).await {
+            Ok(value) => value.to_string(),
+            Err(e) => format!("ERROR: {e:#}"),
+        };
+        if let Some(reply) = msg.reply {
+            if let Err(e) = client
+                .publish(reply, Bytes::from(response.into_bytes()))
+                .await
+            {
+                error!("No se pudo responder a {}: {e}", msg.subject);
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn handle(store: &TaskStore, msg: &Message) -> Result<Value> {
+    match msg.subject.as_str() {
+        NATS_TASKS_LIST_SUBJECT => list(store, &msg.payload).await,
+        NATS_TASKS_GET_SUBJECT => {
+            let record = load(store, &msg.payload).await?;
+            Ok(serde_json::to_value(detail(record))?)
+        }
+        NATS_TASKS_RESULT_SUBJECT => {
+            let record = load(store, &msg.payload).await?;
+            Ok(serde_json::to_value(result(record))?)
+        }
+        other => Err(anyhow!("subject no soportado '{other}'")),
+    }
+}
+
+async fn list(store: &TaskStore, payload: &[u8]) -> Result<Value> {
+    let filter: ListFilter = if payload.iter().all(u8::is_ascii_whitespace) {
+        ListFilter::default()
+    } else {
+        serde_json::from_slice(payload)?
+    };
+    let status = filter.status.as_deref().map(str::to_ascii_lowercase);
+    let from = filter
+        .from
+        .as_ref()
+        .map(|v| parse_date(v, false))
+        .transpose()?;
+    let to = filter
+        .to
+        .as_ref()
+        .map(|v| parse_date(v, true))
+        .transpose()?;
+
+    let mut records = store.all().await?;
+    records.retain(|r| {
+        status.as_deref().is_none_or(|s| state_name(&r.status) == s)
+            && in_range(r.created_at_ms, from, to)
+    });
+    records.sort_by_key(|r| std::cmp::Reverse(r.created_at_ms));
+
+    let tasks: Vec<TaskSummary> = records.into_iter().map(summary).collect();
+    Ok(serde_json::json!({ "tasks": tasks }))
+}
+
+async fn load(store: &TaskStore, payload: &[u8]) -> Result<TaskRecord> {
+    let task_id = parse_id(payload, "task_id").ok_or_else(|| anyhow!("falta el task_id"))?;
+    store
+        .load(&task_id)
+        .await?
+        .ok_or_else(|| anyhow!("tarea '{task_id}' no encontrada"))
+}
+
+fn summary(record: TaskRecord) -> TaskSummary {
+    TaskSummary {
+        state: state_name(&record.status),
+        steps_completed: record.steps.len(),
+        task_id: record.task_id,
+        task_name: record.task_name,
+        status: record.status,
+        created_at_ms: record.created_at_ms,
+        updated_at_ms: record.updated_at_ms,
+    }
+}
+
+fn detail(mut record: TaskRecord) -> TaskDetail {
+    let steps = std::mem::take(&mut record.steps)
+        .into_iter()
+        .map(|s| StepTiming {
+            duration_ms: s.finished_at_ms.saturating_sub(s.started_at_ms),
+            name: s.name,
+            started_at_ms: s.started_at_ms,
+            finished_at_ms: s.finished_at_ms,
+        })
+        .collect();
+    TaskDetail {
+        duration_ms: record.updated_at_ms.saturating_sub(record.created_at_ms),
+        report: std::mem::take(&mut record.report),
+        summary: summary(record),
+        steps,
+    }
+}
+
+fn result(record: TaskRecord) -> TaskResult {
+    let cv_profile = record
+        .steps
+        .iter()
+        .find(|s| s.name == CV_PROFILE_STEP)
+        .and_then(|s| serde_json::from_value(s.output.clone()).ok());
+    TaskResult {
+        cv_profile,
+        state: state_name(&record.status),
+        path: match &record.status {
+            TaskStatus::Completed(path) => Some(path.clone()),
+            _ => None,
+        },
+        task_id: record.task_id,
+        status: record.status,
+        offers: record.offers,
+    }
+}
+
+fn state_name(status: &TaskStatus) -> &'static str {
+    match status {
+        TaskStatus::Idle => "idle",
+        TaskStatus::InProgress(_) => "in_progress",
+        TaskStatus::Completed(_) => "completed",
+        TaskStatus::Failed(_) => "failed",
+        TaskStatus::Cancelled(_) => "cancelled",
+    }
+}
+
+/// Fecha como milisegundos Unix o `YYYY-MM-DD` (UTC). Con `end_of_day`, un día
+/// completo se interpreta como su último milisegundo.
+fn parse_date(value: &Value, end_of_day: bool) -> Result<u64> {
+    if let Some(ms) = value.as_u64() {
+        return Ok(ms);
+    }
+    let text = value
+        .as_str()
+        .ok_or_else(|| anyhow!("fecha no válida: {value}"))?;
+    let date = NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d")
+        .map_err(|_| anyhow!("fecha no válida '{text}' (usa YYYY-MM-DD)"))?;
+    let time = if end_of_day {
+        date.and_hms_milli_opt(23, 59, 59, 999)
+    } else {
+        date.and_hms_milli_opt(0, 0, 0, 0)
+    };
+    let ms = time.map(|t| t.and_utc().timestamp_millis()).unwrap_or(-1);
+    u64::try_from(ms).map_err(|_| anyhow!("fecha fuera de rango '{text}'"))
+}
+
+/// `from` y `to` incluidos.
+fn in_range(created_at_ms: u64, from: Option<u64>, to: Option<u64>) -> bool {
+    from.is_none_or(|f| created_at_ms >= f) && to.is_none_or(|t| created_at_ms <= t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn dates_are_utc_days_or_milliseconds() {
+        assert_eq!(parse_date(&json!("1970-01-02"), false).unwrap(), 86_400_000);
+        assert_eq!(
+            parse_date(&json!(" 2026-10-01 "), false).unwrap(),
+            1_790_812_800_000
+        );
+        assert_eq!(
+            parse_date(&json!("2024-02-29"), true).unwrap(),
+            parse_date(&json!("2024-03-01"), false).unwrap() - 1
+        );
+        assert_eq!(
+            parse_date(&json!(1_760_000_000_000u64), true).unwrap(),
+            1_760_000_000_000
+        );
+    }
+
+    #[test]
+    fn impossible_dates_are_rejected() {
+        for text in [
+            "2026-02-31",
+            "2025-02-29",
+            "2026-13-01",
+            "2026-10",
+            "ayer",
+            "1969-12-31",
+        ] {
+            assert!(parse_date(&json!(text), false).is_err(), "{text}");
+        }
+        assert!(parse_date(&json!(-5), false).is_err());
+        assert_eq!(
+            parse_date(&json!("2026-02-31"), false)
+                .unwrap_err()
+                .to_string(),
+            "fecha no válida '2026-02-31' (usa YYYY-MM-DD)"
+        );
+    }
+
+    #[test]
+    fn to_includes_the_whole_day() {
+        let from = parse_date(&json!("2026-10-01"), false).unwrap();
+        let to = parse_date(&json!("2026-10-01"), true).unwrap();
+        assert!(in_range(from, Some(from), Some(to)));
+        assert!(in_range(to, Some(from), Some(to)));
+        assert!(!in_range(to + 1, Some(from), Some(to)));
+        assert!(!in_range(from - 1, Some(from), Some(to)));
+        assert!(in_range(0, None, None));
+    }
+}
@@ -0,0 +1,456 @@
+// agent_llm/src/cache.rs
+//! Caché en disco de respuestas del modelo.
+//!
+//! La clave es el SHA-256 de todo lo que decide la respuesta: modelo (o cadena de
+//! proveedores), instrucción de sistema, historial, prompt, parámetros (temperatura,
+//! máximo de tokens, formato, `json_schema`) y versiones de plantilla. Cada respuesta se
+//! guarda como `LlmResponse` protobuf en `LLM_CACHE_DIR/<2 primeros>/<clave>.pb`.
+//!
+//! ENV:
+//! - LLM_CACHE            (`on` por defecto; `off` la desactiva)
+//! - LLM_CACHE_DIR        (por defecto: ./llm_cache)
+//! - LLM_CACHE_TTL_SECS   (por defecto: 604800, una semana)
+//! - LLM_CACHE_MAX_MB     (por defecto: 256; al pasarse se borran las más antiguas)
+//!
+//! No se cachean las peticiones en streaming, con `tools` ni con `bypass_cache`. Los
+//! contadores se consultan en `agents.llm.cache.stats`.
+
+use crate::providers::Providers;
+use anyhow::Result;
+use async_nats::Client;
+use bytes::Bytes;
+use common::messaging::messages::{LlmRequest, LlmResponse};
+use common::messaging::NATS_LLM_CACHE_STATS_SUBJECT;
+use futures::StreamExt;
+use prost::Message as _;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, error, info, warn};
+
+const DEFAULT_DIR: &str = "llm_cache";
+const DEFAULT_TTL_SECS: u64 = 7 * 24 * 3600;
+const DEFAULT_MAX_MB: u64 = 256;
+
+/// Al pasarse del tamaño máximo se borra hasta quedar en esta fracción, para no
+/// recorrer el directorio en cada escritura.
+const PRUNE_TARGET: f64 = 0.9;
+
+/// Numera los temporales de `write_atomic`, para que dos escrituras simultáneas de la
+/// misma clave no compartan fichero.
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Clone)]
+pub struct ResponseCache {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    dir: PathBuf,
+    ttl: Duration,
+    max_bytes: u64,
+    /// Tamaño aproximado del directorio (exacto tras cada limpieza).
+    size: Mutex<u64>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    stores: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl ResponseCache {
+    /// Caché configurada por el entorno, o `None` con `LLM_CACHE=off`. Borra las
+    /// entradas caducadas del directorio.
+    pub fn from_env() -> Result<Option<Self>> {
+        if env::var("LLM_CACHE").is_ok_and(|v| v.trim().eq_ignore_ascii_case("off")) {
+            return Ok(None);
+        }
+        let var = |name: &str, default: u64| {
+            env::var(name)
+                .ok()
+                .and_then(|s| s.trim().parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(default)
+        };
+        let dir = PathBuf::from(env::var("LLM_CACHE_DIR").unwrap_or_else(|_| DEFAULT_DIR.into()));
+        let cache = Self::new(
+            dir,
+            Duration::from_secs(var("LLM_CACHE_TTL_SECS", DEFAULT_TTL_SECS)),
+            var("LLM_CACHE_MAX_MB", DEFAULT_MAX_MB) * 1024 * 1024,
+        )?;
+        Ok(Some(cache))
+    }
+
+    /// Caché en `dir`. Borra las entradas caducadas o que pasan de `max_bytes`.
+    fn new(dir: PathBuf, ttl: Duration, max_bytes: u64) -> Result<Self> {
+        fs::create_dir_all(&dir)?;
+        let cache = Self {
+            inner: Arc::new(Inner {
+                dir,
+                ttl,
+                max_bytes,
+                size: Mutex::new(0),
+                hits: AtomicU64::new(0),
+                misses: AtomicU64::new(0),
+                stores: AtomicU64::new(0),
+                evictions: AtomicU64::new(0),
+            }),
+        };
+        cache.inner.prune(cache.inner.max_bytes);
+        Ok(cache)
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{} (TTL {} s, máximo {} MB)",
+            self.inner.dir.display(),
+            self.inner.ttl.as_secs(),
+            self.inner.max_bytes / (1024 * 1024)
+        )
+    }
+
+    /// Si la petición se puede responder desde la caché.
+    pub fn applies(req: &LlmRequest) -> bool {
+        !req.bypass_cache && !req.stream && req.tools.is_empty()
+    }
+
+    /// Clave de la petición: SHA-256 en hexadecimal.
+    pub fn key(providers: &Providers, req: &LlmRequest, versions: &[String]) -> String {
+        let model = if req.model.trim().is_empty() {
+            providers.describe()
+        } else {
+            req.model.clone()
+        };
+        digest(&model, req, versions)
+    }
+
+    /// Respuesta guardada con `key`, si existe y no ha caducado.
+    pub async fn get(&self, key: &str) -> Option<LlmResponse> {
+        let path = self.inner.path(key);
+        let found = match tokio::fs::metadata(&path).await {
+            Ok(meta) if self.inner.expired(&meta) => {
+                self.inner.remove(&path, meta.len()).await;
+                None
+            }
+            Ok(_) => match tokio::fs::read(&path).await {
+                Ok(bytes) => match LlmResponse::decode(bytes.as_slice()) {
+                    Ok(response) => Some(response),
+                    Err(e) => {
+                        warn!("Entrada de caché ilegible {}: {e}", path.display());
+                        self.inner.remove(&path, bytes.len() as u64).await;
+                        None
+                    }
+                },
+                Err(_) => None,
+            },
+            Err(_) => None,
+        };
+        let counter = match found {
+            Some(_) => &self.inner.hits,
+            None => &self.inner.misses,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        found
+    }
+
+    /// Guarda una respuesta correcta con `key`.
+    pub async fn put(&self, key: &str, response: &LlmResponse) {
+        let stored = LlmResponse {
+            request_id: String::new(),
+            prompt_versions: Vec::new(),
+            ..response.clone()
+        };
+        let bytes = stored.encode_to_vec();
+        let path = self.inner.path(key);
+        // Al sobrescribir una entrada, su tamaño anterior deja de contar
+        let old = tokio::fs::metadata(&path)
+            .await
+            .map_or(0, |meta| meta.len());
+        if let Err(e) = write_atomic(&path, &bytes).await {
+            warn!("No se pudo guardar en la caché {}: {e}", path.display());
+            return;
+        }
+        self.inner.stores.fetch_add(1, Ordering::Relaxed);
+
+        let size = {
+            let mut size = self.inner.size();
+            *size = size.saturating_sub(old) + bytes.len() as u64;
+            *size
+        };
+        if size > self.inner.max_bytes {
+            let inner = self.inner.clone();
+            let target = (inner.max_bytes as f64 * PRUNE_TARGET) as u64;
+            if let Err(e) = tokio::task::spawn_blocking(move || inner.prune(target)).await {
+                error!("La limpieza de la caché ha fallado: {e}");
+            }
+        }
+    }
+
+    pub fn stats(&self) -> Value {
+        let hits = self.inner.hits.load(Ordering::Relaxed);
+        let misses = self.inner.misses.load(Ordering::Relaxed);
+        let lookups = hits + misses;
+        json!({
+            "hits": hits,
+            "misses": misses,
+            "hit_rate": if lookups == 0 { 0.0 } else { hits as f64 / lookups as f64 },
+            "stores": self.inner.stores.load(Ordering::Relaxed),
+            "evictions": self.inner.evictions.load(Ordering::Relaxed),
+            "size_bytes": *self.inner.size(),
+            "max_bytes": self.inner.max_bytes,
+            "ttl_secs": self.inner.ttl.as_secs(),
+        })
+    }
+}
+
+impl Inner {
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(&key[..2]).join(format!("{key}.pb"))
+    }
+
+    fn expired(&self, meta: &fs::Metadata) -> bool {
+        meta.modified()
+            .ok()
+            .and_then(|at| at.elapsed().ok())
+            .is_some_and(|age| age > self.ttl)
+    }
+
+    async fn remove(&self, path: &Path, len: u64) {
+        if tokio::fs::remove_file(path).await.is_ok() {
+            self.evictions.fetch_add(1, Ordering::Relaxed);
+            let mut size = self.size();
+            *size = size.saturating_sub(len);
+        }
+    }
+
+    /// Borra las entradas caducadas y, de las demás, las más antiguas hasta que el
+    /// directorio ocupe como mucho `target` bytes.
+    fn prune(&self, target: u64) {
+        let mut entries: Vec<(SystemTime, u64, PathBuf)> = Vec::new();
+        let shards = fs::read_dir(&self.dir).into_iter().flatten().flatten();
+        for file in shards.flat_map(|shard| fs::read_dir(shard.path()).into_iter().flatten()) {
+            let Ok(file) = file else { continue };
+            let Ok(meta) = file.metadata() else { continue };
+            if !meta.is_file() {
+                continue;
+            }
+            let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            entries.push((modified, meta.len(), file.path()));
+        }
+        entries.sort_by_key(|(modified, _, _)| *modified);
+
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        let mut removed = 0;
+        for (modified, len, path) in entries {
+            let expired = modified.elapsed().is_ok_and(|age| age > self.ttl);
+            if !expired && total <= target {
+                // Ordenadas de más antigua a más reciente: las demás tampoco caducan
+                break;
+            }
+            if fs::remove_file(&path).is_ok() {
+                total -= len;
+                removed += 1;
+            }
+        }
+        if removed > 0 {
+            self.evictions.fetch_add(removed, Ordering::Relaxed);
+            debug!("Caché: {removed} entradas borradas, {total} bytes en uso");
+        }
+        *self.size() = total;
+    }
+
+    /// El tamaño sigue siendo válido aunque otro hilo haya fallado con el lock tomado.
+    fn size(&self) -> MutexGuard<'_, u64> {
+        self.size.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// SHA-256 en hexadecimal de todo lo que decide la respuesta a `req` con `model`.
+fn digest(model: &str, req: &LlmRequest, versions: &[String]) -> String {
+    let history: Vec<[&str; 2]> = req
+        .history
+        .iter()
+        .map(|m| [m.role.as_str(), m.content.as_str()])
+        .collect();
+    let material = json!({
+        "model": model,
+        "system_prompt": req.system_prompt,
+        "history": history,
+        "user_prompt": req.user_prompt,
+        "temperature": req.temperature,
+        "max_tokens": req.max_tokens,
+        "response_format": req.response_format,
+        "json_schema": req.json_schema,
+        "max_attempts": req.max_attempts,
+        "prompt_versions": versions,
+    });
+    format!("{:x}", Sha256::digest(material.to_string().as_bytes()))
+}
+
+/// Escribe en un temporal y lo renombra, para no dejar entradas a medias.
+async fn write_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let n = TMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let tmp = path.with_extension(format!("{}-{n}.tmp", std::process::id()));
+    tokio::fs::write(&tmp, bytes).await?;
+    tokio::fs::rename(&tmp, path).await
+}
+
+/// Atiende `agents.llm.cache.stats` hasta que se cierre la conexión.
+pub async fn serve(client: Client, cache: ResponseCache) -> Result<()> {
+    let mut requests = client.subscribe(NATS_LLM_CACHE_STATS_SUBJECT).await?;
+    info!("Caché de respuestas: {}", cache.describe());
+    while let Some(msg) = requests.next().await {
+        if let Some(reply) = msg.reply {
+            let stats = cache.stats().to_string();
+            if let Err(e) = client.publish(reply, Bytes::from(stats.into_bytes())).await {
+                error!("No se pudo responder a {}: {e}", msg.subject);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::messaging::messages::TranscriptMessage;
+
+    fn temp_cache(name: &str, ttl_secs: u64, max_bytes: u64) -> ResponseCache {
+        let dir = env::temp_dir().join(format!("llm_cache_{}_{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        ResponseCache::new(dir, Duration::from_secs(ttl_secs), max_bytes).unwrap()
+    }
+
+    fn response(content: &str) -> LlmResponse {
+        LlmResponse {
+            content: content.to_string(),
+            request_id: "r1".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn request(prompt: &str) -> LlmRequest {
+        LlmRequest {
+            user_prompt: prompt.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Pone la fecha de modificación de la entrada `key` `secs` segundos atrás.
+    fn age(cache: &ResponseCache, key: &str, secs: u64) {
+        fs::File::options()
+            .write(true)
+            .open(cache.inner.path(key))
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    #[test]
+    fn keys_depend_on_everything_that_decides_the_answer() {
+        let versions = vec!["system@v1".to_string()];
+        let key = digest("ollama:llama3", &request("hola"), &versions);
+        assert_eq!(key.len(), 64);
+        assert_eq!(key, digest("ollama:llama3", &request("hola"), &versions));
+
+        let mut hotter = request("hola");
+        hotter.temperature = Some(0.9);
+        let mut with_history = request("hola");
+        with_history.history.push(TranscriptMessage {
+            role: "user".to_string(),
+            content: "antes".to_string(),
+            ..Default::default()
+        });
+        let others = [
+            digest("openai:gpt-4o", &request("hola"), &versions),
+            digest("ollama:llama3", &request("adiós"), &versions),
+            digest("ollama:llama3", &hotter, &versions),
+            digest("ollama:llama3", &with_history, &versions),
+            digest(
+                "ollama:llama3",
+                &request("hola"),
+                &["system@v2".to_string()],
+            ),
+        ];
+        assert!(others.iter().all(|other| *other != key));
+    }
+
+    #[tokio::test]
+    async fn expired_entries_are_misses() {
+        let cache = temp_cache("ttl", 60, 1024 * 1024);
+        let key = digest("m", &request("hola"), &[]);
+        cache.put(&key, &response("respuesta")).await;
+
+        let hit = cache.get(&key).await.unwrap();
+        assert_eq!(hit.content, "respuesta");
+        assert_eq!(hit.request_id, "");
+
+        age(&cache, &key, 120);
+        assert!(cache.get(&key).await.is_none());
+        assert!(!cache.inner.path(&key).exists());
+        let stats = cache.stats();
+        assert_eq!(
+            (&stats["hits"], &stats["misses"], &stats["evictions"]),
+            (&json!(1), &json!(1), &json!(1))
+        );
+        assert_eq!(stats["size_bytes"], 0);
+        fs::remove_dir_all(&cache.inner.dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn overwriting_a_key_does_not_grow_the_size() {
+        let cache = temp_cache("overwrite", 60, 1024 * 1024);
+        let key = digest("m", &request("hola"), &[]);
+        let entry = response("respuesta");
+        cache.put(&key, &entry).await;
+        cache.put(&key, &entry).await;
+        let len = fs::metadata(cache.inner.path(&key)).unwrap().len();
+        assert_eq!(cache.stats()["size_bytes"], len);
+        assert_eq!(cache.stats()["stores"], 2);
+        fs::remove_dir_all(&cache.inner.dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn pruning_removes_the_oldest_entries() {
+        let content = "x".repeat(100);
+        let stored = LlmResponse {
+            content: content.clone(),
+            ..Default::default()
+        };
+        let len = stored.encode_to_vec().len() as u64;
+        let cache = temp_cache("prune", 3600, len * 3 - 1);
+        let keys: Vec<String> = ["a", "b", "c"]
+            .iter()
+            .map(|p| digest("m", &request(p), &[]))
+            .collect();
+
+        cache.put(&keys[0], &response(&content)).await;
+        age(&cache, &keys[0], 30);
+        cache.put(&keys[1], &response(&content)).await;
+        age(&cache, &keys[1], 20);
+        // La tercera pasa del máximo: se borra la más antigua
+        cache.put(&keys[2], &response(&content)).await;
+
+        let present: Vec<bool> = keys.iter().map(|k| cache.inner.path(k).exists()).collect();
+        assert_eq!(present, [false, true, true]);
+        let stats = cache.stats();
+        assert_eq!(stats["size_bytes"], len * 2);
+        assert_eq!(stats["evictions"], 1);
+
+        // Al abrirla de nuevo se borran también las caducadas
+        age(&cache, &keys[1], 7200);
+        let reopened =
+            ResponseCache::new(cache.inner.dir.clone(), Duration::from_secs(3600), len * 3)
+                .unwrap();
+        assert!(!reopened.inner.path(&keys[1]).exists());
+        assert_eq!(reopened.stats()["size_bytes"], len);
+        fs::remove_dir_all(&cache.inner.dir).unwrap();
+    }
+}
@@ -3,66 +3,501 @@
 //!
 //! ENV:
 //! - NATS_URL  (por defecto: nats://127.0.0.1:4222)
-//! - LLM_MODEL (por defecto: "llama3.1:8b")
+//! - LLM_PROVIDERS, LLM_PROVIDER, LLM_MODEL...: proveedores y modelos (ver `providers`)
 //!
-//! Protocolo simple (placeholder):
+//! Protocolo:
 //! - Subject: "agents.llm"
-//! - Request: payload UTF-8 con el prompt
-//! - Reply:   texto generado UTF-8
+//! - Request protobuf (cabecera `Content-Type: application/x-protobuf`): `LlmRequest` con
+//!   instrucción de sistema, prompt, modelo, temperatura, máximo de tokens, formato de
+//!   respuesta e id de petición. Reply: `LlmResponse` con el texto, el modelo, el consumo
+//!   de tokens, el proveedor que respondió y, si falló, el detalle del error (y la
+//!   cabecera `Agent-Error`).
+//! - Con `LlmRequest.json_schema`, la respuesta es JSON validado contra ese esquema; si el
+//!   modelo no lo cumple se le piden correcciones y, agotados los intentos, el error es
+//!   `invalid_output` (ver `structured`). `LLM_JSON_MAX_ATTEMPTS` (por defecto 3).
+//! - Con `LlmRequest.tools`, el modelo puede pedir ejecutar esas herramientas en
+//!   `agents.tool` hasta dar una respuesta final; `LlmResponse.transcript` lleva la
+//!   conversación completa (ver `tool_calls`). `LLM_TOOL_MAX_STEPS` (por defecto 8) y
+//!   `LLM_TOOL_TIMEOUT_SECS` (por defecto 120).
+//! - Con `LlmRequest.session_id`, el agente antepone el historial guardado de esa sesión
+//!   y guarda el prompt y la respuesta (bucket KV `llm_sessions`, API `sessions.*`; ver
+//!   `sessions`). `LlmRequest.history` permite enviar el historial sin sesión.
+//! - Con `LlmRequest.stream`, la respuesta llega por trozos al reply subject: un
+//!   `LlmChunk` por cada trozo del proveedor, numerados desde 0 en `seq`, y un último
+//!   `LlmChunk` con `done`, el consumo de tokens o el error. Cliente: `common::messaging::stream`.
+//! - Las respuestas se guardan en una caché en disco con TTL y tamaño máximo (ver
+//!   `cache`); una respuesta de la caché lleva `LlmResponse.cached`, y
+//!   `LlmRequest.bypass_cache` la evita. Contadores en "agents.llm.cache.stats".
+//! - Sin `LlmRequest.system_prompt`, la instrucción de sistema sale de la plantilla
+//!   `system` (ver `common::prompts`). Las respuestas llevan en `prompt_versions` las
+//!   plantillas usadas: la de `LlmRequest.prompt_version` y la de sistema.
+//! - Request de texto (sin cabecera, compatibilidad): payload UTF-8 con el prompt. Reply:
+//!   texto generado UTF-8, o `ERROR: ...` con la cabecera `Agent-Error`.
+//! - Cancelación: "agents.llm.cancel" con el `task_id` de la cabecera `Task-Id`
 
 use anyhow::Result;
-use bytes::Bytes;
+use async_nats::{jetstream, Subject};
+use cache::ResponseCache;
+use common::cancel::{listen_cancellations, task_id_of, InFlight};
+use common::error::{AgentError, ErrorKind};
+use common::messaging::messages::{
+    ErrorDetail, LlmChunk, LlmRequest, LlmResponse, ResponseFormat, TranscriptMessage, Usage,
+};
+use common::messaging::request::{is_protobuf, respond, respond_proto};
+use common::messaging::{NATS_LLM_CANCEL_SUBJECT, NATS_LLM_SUBJECT};
+use common::prompts;
 use futures::StreamExt;
-use genai::chat::{ChatMessage, ChatRequest};
-use genai::{Client, ClientBuilder};
+use genai::chat::{ChatMessage, ChatOptions, ChatRequest, ChatResponseFormat, ChatStreamEvent};
+use prost::Message as _;
+use providers::Providers;
+use serde_json::Value;
+use sessions::{SessionStore, SESSIONS_BUCKET};
 use std::env;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{error, info, warn};
 
-const NATS_LLM_SUBJECT: &str = "agents.llm";
+mod cache;
+mod providers;
+mod sessions;
+mod structured;
+mod tool_calls;
 
-fn build_llm_client() -> Client {
-    ClientBuilder::default().build()
+/// Rellena la instrucción de sistema con la plantilla `system` si la petición no trae
+/// una (`key` elige la variante A/B). Devuelve las versiones de plantilla de la petición.
+fn with_system_prompt(
+    mut req: LlmRequest,
+    key: Option<&str>,
+) -> Result<(LlmRequest, Vec<String>), AgentError> {
+    let mut versions = Vec::new();
+    if !req.prompt_version.is_empty() {
+        versions.push(req.prompt_version.clone());
+    }
+    if req.system_prompt.trim().is_empty() {
+        let key = key.unwrap_or(&req.request_id);
+        let system = prompts::render("system", key, &Value::Null)?;
+        req.system_prompt = system.text;
+        versions.push(system.version);
+    }
+    Ok((req, versions))
+}
+
+/// Mensajes y opciones de genai para una petición.
+fn chat_request(req: &LlmRequest) -> (ChatRequest, ChatOptions) {
+    let mut options = ChatOptions::default().with_capture_usage(true);
+    if let Some(temperature) = req.temperature {
+        options = options.with_temperature(temperature as f64);
+    }
+    if let Some(max_tokens) = req.max_tokens {
+        options = options.with_max_tokens(max_tokens);
+    }
+    if req.response_format() == ResponseFormat::Json {
+        options = options.with_response_format(ChatResponseFormat::JsonMode);
+    }
+
+    let mut messages = vec![ChatMessage::system(&req.system_prompt)];
+    messages.extend(req.history.iter().filter_map(history_message));
+    messages.push(ChatMessage::user(&req.user_prompt));
+    (ChatRequest::new(messages), options)
+}
+
+/// Mensaje de `LlmRequest.history`; del historial solo se usa el texto de los mensajes
+/// `system`, `user` y `assistant`.
+fn history_message(message: &TranscriptMessage) -> Option<ChatMessage> {
+    let content = message.content.as_str();
+    match message.role.as_str() {
+        "system" => Some(ChatMessage::system(content)),
+        "user" => Some(ChatMessage::user(content)),
+        "assistant" if !content.is_empty() => Some(ChatMessage::assistant(content)),
+        _ => None,
+    }
+}
+
+async fn llm_generate(providers: &Providers, req: &LlmRequest) -> Result<LlmResponse> {
+    let candidates = providers.for_request(&req.model)?;
+    let (chat, options) = chat_request(req);
+    let started = Instant::now();
+    let (res, provider) = providers.exec_chat(&candidates, &chat, &options).await?;
+    Ok(LlmResponse {
+        content: res.content_text_as_str().unwrap_or_default().to_string(),
+        request_id: req.request_id.clone(),
+        model: res.provider_model_iden.model_name.to_string(),
+        provider: provider.to_string(),
+        usage: Some(usage(&res.usage)),
+        duration_ms: started.elapsed().as_millis() as u64,
+        ..Default::default()
+    })
+}
+
+/// Publica cada trozo de la generación en `reply` según llega y devuelve el último
+/// `LlmChunk` (`done`, con el consumo de tokens) sin publicarlo, junto con el texto
+/// completo. `seq` queda en el número del siguiente trozo.
+async fn llm_stream(
+    providers: &Providers,
+    req: &LlmRequest,
+    nats: &async_nats::Client,
+    reply: &Subject,
+    seq: &mut u64,
+) -> Result<(LlmChunk, String)> {
+    let candidates = providers.for_request(&req.model)?;
+    let (chat, options) = chat_request(req);
+    let started = Instant::now();
+    let ((mut res, first), provider) = providers
+        .exec_chat_stream(&candidates, &chat, &options)
+        .await?;
+    let mut end = LlmChunk {
+        request_id: req.request_id.clone(),
+        done: true,
+        model: res.model_iden.model_name.to_string(),
+        provider: provider.to_string(),
+        ..Default::default()
+    };
+    let mut content = String::new();
+    let mut events = futures::stream::iter(first.into_iter().map(Ok)).chain(&mut res.stream);
+    while let Some(event) = events.next().await {
+        match event? {
+            ChatStreamEvent::Chunk(chunk) if !chunk.content.is_empty() => {
+                content.push_str(&chunk.content);
+                let chunk = LlmChunk {
+                    request_id: req.request_id.clone(),
+                    seq: *seq,
+                    delta: chunk.content,
+                    ..Default::default()
+                };
+                respond_proto(nats, Some(reply.clone()), &chunk, None).await;
+                *seq += 1;
+            }
+            ChatStreamEvent::End(stream_end) => {
+                end.usage = stream_end.captured_usage.as_ref().map(usage);
+            }
+            _ => {}
+        }
+    }
+    end.duration_ms = started.elapsed().as_millis() as u64;
+    Ok((end, content))
 }
 
-async fn llm_generate_text(client: &Client, model: &str, prompt: &str) -> Result<String> {
-    let req = ChatRequest::new(vec![
-        ChatMessage::system("Responde en español, de forma clara y concisa."),
-        ChatMessage::user(prompt),
-    ]);
-    let res = client.exec_chat(model, req, None).await?;
-    Ok(res.content_text_as_str().unwrap_or_default().to_string())
+fn usage(usage: &genai::chat::Usage) -> Usage {
+    let tokens = |n: Option<i32>| n.unwrap_or(0).max(0) as u32;
+    Usage {
+        prompt_tokens: tokens(usage.prompt_tokens),
+        completion_tokens: tokens(usage.completion_tokens),
+        total_tokens: tokens(usage.total_tokens),
+    }
+}
+
+/// Suma a `total` el consumo de una llamada más (peticiones con varias llamadas al modelo).
+fn add_usage(total: &mut Usage, used: &genai::chat::Usage) {
+    let used = usage(used);
+    total.prompt_tokens += used.prompt_tokens;
+    total.completion_tokens += used.completion_tokens;
+    total.total_tokens += used.total_tokens;
 }
 
 pub async fn run() -> Result<()> {
     let nats_url = env::var("NATS_URL").unwrap_or_else(|_| "nats://127.0.0.1:4222".to_string());
-    let model = env::var("LLM_MODEL").unwrap_or_else(|_| "llama3.1:8b".to_string());
+    let providers = Arc::new(Providers::from_env()?);
+    info!("Proveedores LLM: {}", providers.describe());
 
     let nats = async_nats::connect(&nats_url).await?;
-    let genai = build_llm_client();
+
+    // Generaciones en curso por `Task-Id`, abortables desde `agents.llm.cancel`
+    let inflight = InFlight::new();
+    tokio::spawn(listen_cancellations(
+        nats.clone(),
+        NATS_LLM_CANCEL_SUBJECT,
+        inflight.clone(),
+    ));
+
+    // Sesiones de conversación; sin JetStream el agente sigue funcionando sin ellas
+    let sessions = match SessionStore::open(&jetstream::new(nats.clone())).await {
+        Ok(store) => {
+            let (nats, api) = (nats.clone(), store.clone());
+            tokio::spawn(async move {
+                if let Err(e) = sessions::serve(nats, api).await {
+                    error!("La API de sesiones ha terminado con un error: {e:#}");
+                }
+            });
+            Some(store)
+        }
+        Err(e) => {
+            warn!(
+                "Sesiones desactivadas: no se pudo abrir el bucket KV '{SESSIONS_BUCKET}': {e:#}"
+            );
+            None
+        }
+    };
+
+    // Caché de respuestas en disco; si no se puede usar el directorio, se trabaja sin ella
+    let cache = ResponseCache::from_env().unwrap_or_else(|e| {
+        warn!("Caché de respuestas desactivada: {e:#}");
+        None
+    });
+    if let Some(cache) = cache.clone() {
+        let nats = nats.clone();
+        tokio::spawn(async move {
+            if let Err(e) = cache::serve(nats, cache).await {
+                error!("La API de la caché ha terminado con un error: {e:#}");
+            }
+        });
+    }
 
     let mut sub = nats.subscribe(NATS_LLM_SUBJECT).await?;
     while let Some(msg) = sub.next().await {
-        let prompt = match std::str::from_utf8(&msg.payload) {
-            Ok(s) => s,
-            Err(_) => {
-                if let Some(reply) = msg.reply {
-                    let _ = nats
-                        .publish(reply, Bytes::from_static(b"ERROR: payload no es UTF-8"))
-                        .await;
-                }
-                continue;
+        let (nats, providers, inflight) = (nats.clone(), providers.clone(), inflight.clone());
+        let (sessions, cache) = (sessions.clone(), cache.clone());
+        tokio::spawn(async move {
+            handle_request(
+                &nats,
+                &providers,
+                sessions.as_ref(),
+                cache.as_ref(),
+                &inflight,
+                msg,
+            )
+            .await
+        });
+    }
+    Ok(())
+}
+
+async fn handle_request(
+    nats: &async_nats::Client,
+    providers: &Providers,
+    sessions: Option<&SessionStore>,
+    cache: Option<&ResponseCache>,
+    inflight: &InFlight,
+    msg: async_nats::Message,
+) {
+    let protobuf = is_protobuf(msg.headers.as_ref());
+    let task_id = task_id_of(&msg);
+    let parsed = match parse_request(&msg, protobuf) {
+        Ok(req) => with_session(sessions, req).await,
+        Err(err) => Err(err),
+    };
+    let (req, versions) = match parsed.and_then(|req| with_system_prompt(req, task_id.as_deref())) {
+        Ok(parsed) => parsed,
+        Err(err) if protobuf => {
+            let response = error_response(String::new(), &err);
+            respond_proto(nats, msg.reply, &response, Some(err.kind())).await;
+            return;
+        }
+        Err(err) => {
+            respond(nats, msg.reply, Err(err)).await;
+            return;
+        }
+    };
+
+    if protobuf && req.stream {
+        stream_request(nats, providers, sessions, inflight, &msg, &req, versions).await;
+        return;
+    }
+
+    // La clave incluye el historial de la sesión y la instrucción de sistema ya puestos
+    let cache_key = cache
+        .filter(|_| ResponseCache::applies(&req))
+        .map(|cache| (cache, ResponseCache::key(providers, &req, &versions)));
+    let generate = async {
+        if let Some((cache, key)) = &cache_key {
+            let started = Instant::now();
+            if let Some(hit) = cache.get(key).await {
+                return Ok(LlmResponse {
+                    request_id: req.request_id.clone(),
+                    duration_ms: started.elapsed().as_millis() as u64,
+                    cached: true,
+                    ..hit
+                });
             }
-        };
+        }
+        if !req.tools.is_empty() {
+            tool_calls::llm_generate_with_tools(providers, nats, task_id.as_deref(), &req).await
+        } else if !req.json_schema.trim().is_empty() {
+            structured::llm_generate_json(providers, &req).await
+        } else {
+            llm_generate(providers, &req)
+                .await
+                .map_err(|e| AgentError::LlmApiError(format!("{e:#}")))
+        }
+    };
+    let generated = match &task_id {
+        Some(task_id) => inflight.run(task_id, generate).await.unwrap_or_else(|_| {
+            Err(AgentError::Cancelled(format!(
+                "tarea '{task_id}' cancelada"
+            )))
+        }),
+        None => generate.await,
+    };
+    if let (Ok(response), Some((cache, key))) = (&generated, &cache_key) {
+        if !response.cached && response.error.is_none() {
+            cache.put(key, response).await;
+        }
+    }
+    let compact = match &generated {
+        Ok(response) if response.error.is_none() => {
+            record_turn(sessions, &req, &response.content).await
+        }
+        _ => false,
+    };
 
-        let out = match llm_generate_text(&genai, &model, prompt).await {
-            Ok(t) => t,
-            Err(e) => format!("ERROR LLM: {e}"),
-        };
+    if !protobuf {
+        respond(nats, msg.reply, generated.map(|r| r.content)).await;
+    } else {
+        let (response, kind) = proto_reply(generated, &req.request_id, versions);
+        respond_proto(nats, msg.reply, &response, kind).await;
+    }
+    if let (true, Some(sessions)) = (compact, sessions) {
+        sessions.compact(providers, &req.session_id).await;
+    }
+}
 
-        if let Some(reply) = msg.reply {
-            let _ = nats.publish(reply, Bytes::from(out.into_bytes())).await;
+/// Genera en streaming hacia el reply subject y cierra con el último trozo o el error.
+async fn stream_request(
+    nats: &async_nats::Client,
+    providers: &Providers,
+    sessions: Option<&SessionStore>,
+    inflight: &InFlight,
+    msg: &async_nats::Message,
+    req: &LlmRequest,
+    versions: Vec<String>,
+) {
+    let Some(reply) = msg.reply.clone() else {
+        warn!("Petición en streaming sin reply subject; se ignora");
+        return;
+    };
+    let mut seq = 0;
+    let generate = async {
+        llm_stream(providers, req, nats, &reply, &mut seq)
+            .await
+            .map_err(|e| AgentError::LlmApiError(format!("{e:#}")))
+    };
+    let generated = match task_id_of(msg) {
+        Some(task_id) => inflight.run(&task_id, generate).await.unwrap_or_else(|_| {
+            Err(AgentError::Cancelled(format!(
+                "tarea '{task_id}' cancelada"
+            )))
+        }),
+        None => generate.await,
+    };
+
+    match generated {
+        Ok((mut end, content)) => {
+            let compact = record_turn(sessions, req, &content).await;
+            end.seq = seq;
+            end.prompt_versions = versions;
+            respond_proto(nats, Some(reply), &end, None).await;
+            if let (true, Some(sessions)) = (compact, sessions) {
+                sessions.compact(providers, &req.session_id).await;
+            }
+        }
+        Err(err) => {
+            let end = LlmChunk {
+                request_id: req.request_id.clone(),
+                seq,
+                done: true,
+                error: Some(error_detail(&err)),
+                ..Default::default()
+            };
+            respond_proto(nats, Some(reply), &end, Some(err.kind())).await;
         }
     }
-    Ok(())
 }
 
+/// Añade al historial de la petición el de su sesión, si lleva `session_id`.
+async fn with_session(
+    sessions: Option<&SessionStore>,
+    mut req: LlmRequest,
+) -> Result<LlmRequest, AgentError> {
+    if req.session_id.is_empty() {
+        return Ok(req);
+    }
+    let Some(sessions) = sessions else {
+        return Err(AgentError::Unavailable(format!(
+            "sesiones desactivadas (sin bucket KV '{SESSIONS_BUCKET}')"
+        )));
+    };
+    sessions.prepare(&mut req).await?;
+    Ok(req)
+}
+
+/// Guarda el prompt y la respuesta en la sesión de la petición. Devuelve si hay que
+/// compactarla (después de responder, para no retrasar la respuesta).
+async fn record_turn(sessions: Option<&SessionStore>, req: &LlmRequest, answer: &str) -> bool {
+    let Some(sessions) = sessions.filter(|_| !req.session_id.is_empty()) else {
+        return false;
+    };
+    match sessions.record(req, answer).await {
+        Ok(compact) => compact,
+        Err(e) => {
+            warn!("No se pudo guardar la sesión {}: {e:#}", req.session_id);
+            false
+        }
+    }
+}
+
+/// `LlmRequest` del payload: protobuf o, sin la cabecera, el prompt en texto.
+fn parse_request(msg: &async_nats::Message, protobuf: bool) -> Result<LlmRequest, AgentError> {
+    if protobuf {
+        let req = LlmRequest::decode(msg.payload.as_ref())?;
+        if req.user_prompt.trim().is_empty() {
+            return Err(AgentError::InvalidArguments(
+                "user_prompt está vacío".to_string(),
+            ));
+        }
+        if req.stream && !req.json_schema.trim().is_empty() {
+            return Err(AgentError::InvalidArguments(
+                "json_schema no se puede combinar con stream".to_string(),
+            ));
+        }
+        if !req.tools.is_empty() && (req.stream || !req.json_schema.trim().is_empty()) {
+            return Err(AgentError::InvalidArguments(
+                "tools no se puede combinar con stream ni con json_schema".to_string(),
+            ));
+        }
+        return Ok(req);
+    }
+    let prompt = std::str::from_utf8(&msg.payload)
+        .map_err(|_| AgentError::InvalidArguments("payload no es UTF-8".to_string()))?;
+    Ok(LlmRequest {
+        user_prompt: prompt.to_string(),
+        ..Default::default()
+    })
+}
+
+/// Respuesta protobuf y, si la generación falló, el tipo de error para la cabecera
+/// `Agent-Error`. Una respuesta generada que trae `error` (con `tools`, agotar los pasos)
+/// va sin cabecera: el cliente la decodifica entera y recibe la conversación parcial.
+fn proto_reply(
+    generated: Result<LlmResponse, AgentError>,
+    request_id: &str,
+    versions: Vec<String>,
+) -> (LlmResponse, Option<ErrorKind>) {
+    match generated {
+        Ok(response) => (
+            LlmResponse {
+                prompt_versions: versions,
+                ..response
+            },
+            None,
+        ),
+        Err(err) => (
+            error_response(request_id.to_string(), &err),
+            Some(err.kind()),
+        ),
+    }
+}
 
+fn error_response(request_id: String, err: &AgentError) -> LlmResponse {
+    LlmResponse {
+        request_id,
+        error: Some(error_detail(err)),
+        ..Default::default()
+    }
+}
+
+fn error_detail(err: &AgentError) -> ErrorDetail {
+    ErrorDetail {
+        kind: err.kind().as_str().to_string(),
+        message: err.to_string(),
+        retryable: err.is_retryable(),
+    }
+}
@@ -0,0 +1,429 @@
+// agent_llm/src/providers.rs
+//! Proveedores LLM configurados y cadena de respaldo.
+//!
+//! `LLM_PROVIDERS` es la lista ordenada `proveedor:modelo` separada por comas, p. ej.
+//! `ollama:llama3.1:8b,openai:gpt-4o-mini`. Cada petición se envía al primero; si falla
+//! o no responde en `LLM_PROVIDER_TIMEOUT_SECS`, se pasa al siguiente. Sin
+//! `LLM_PROVIDERS` se usa un solo proveedor: `LLM_PROVIDER` con `<PROVEEDOR>_MODEL` (p. ej.
+//! `OLLAMA_MODEL`) o `LLM_MODEL`, o el que genai deduce del nombre de `LLM_MODEL`.
+//!
+//! Por proveedor: `LLM_<PROVEEDOR>_ENDPOINT` cambia la URL base y `LLM_<PROVEEDOR>_API_KEY`
+//! la clave (sin ella, la variable habitual del proveedor, p. ej. `OPENAI_API_KEY`).
+
+use anyhow::{anyhow, Result};
+use futures::StreamExt;
+use genai::adapter::AdapterKind;
+use genai::chat::{ChatOptions, ChatRequest, ChatResponse, ChatStreamEvent, ChatStreamResponse};
+use genai::resolver::{AuthData, Endpoint};
+use genai::{Client, ClientBuilder, ModelIden, ServiceTarget};
+use std::env;
+use std::future::Future;
+use std::time::Duration;
+use tracing::warn;
+
+/// Modelo por defecto si no hay ninguna configuración.
+const DEFAULT_MODEL: &str = "llama3.1:8b";
+
+/// Espera máxima por defecto de cada proveedor (`LLM_PROVIDER_TIMEOUT_SECS`).
+const DEFAULT_TIMEOUT_SECS: u64 = 120;
+
+const ADAPTERS: &[AdapterKind] = &[
+    AdapterKind::OpenAI,
+    AdapterKind::Ollama,
+    AdapterKind::Anthropic,
+    AdapterKind::Cohere,
+    AdapterKind::Gemini,
+    AdapterKind::Groq,
+    AdapterKind::Xai,
+    AdapterKind::DeepSeek,
+];
+
+/// Un proveedor y modelo de la cadena, con su cliente genai ya configurado.
+pub struct Provider {
+    adapter: AdapterKind,
+    model: String,
+    client: Client,
+}
+
+impl Provider {
+    /// `proveedor:modelo`, o solo el modelo (el proveedor se deduce del nombre).
+    fn parse(spec: &str) -> Result<Self> {
+        let spec = spec.trim();
+        let (adapter, model) = match split_spec(spec) {
+            Some(named) => named,
+            None => (AdapterKind::from_model(spec)?, spec),
+        };
+        let model = model.trim();
+        if model.is_empty() {
+            return Err(anyhow!("falta el modelo en '{spec}'"));
+        }
+        Ok(Self::new(adapter, model))
+    }
+
+    fn new(adapter: AdapterKind, model: &str) -> Self {
+        let prefix = format!("LLM_{}", adapter.as_lower_str().to_uppercase());
+        let endpoint = env_value(&format!("{prefix}_ENDPOINT"));
+        let api_key = env_value(&format!("{prefix}_API_KEY"));
+        let iden = ModelIden::new(adapter, model.to_string());
+        let client = ClientBuilder::default()
+            .with_service_target_resolver_fn(move |target: ServiceTarget| {
+                Ok(ServiceTarget {
+                    endpoint: endpoint
+                        .clone()
+                        .map(Endpoint::from_owned)
+                        .unwrap_or(target.endpoint),
+                    auth: api_key
+                        .clone()
+                        .map(AuthData::from_single)
+                        .unwrap_or(target.auth),
+                    model: iden.clone(),
+                })
+            })
+            .build();
+        Provider {
+            adapter,
+            model: model.to_string(),
+            client,
+        }
+    }
+
+    /// Nombre del proveedor (`ollama`, `openai`...).
+    pub fn name(&self) -> &'static str {
+        self.adapter.as_lower_str()
+    }
+
+    /// `true` si `spec` (`proveedor:modelo` o solo el modelo) es este proveedor.
+    fn matches(&self, spec: &str) -> bool {
+        let spec = spec.trim();
+        match split_spec(spec) {
+            Some((adapter, model)) => adapter == self.adapter && model.trim() == self.model,
+            None => spec == self.model,
+        }
+    }
+}
+
+/// Cadena de proveedores en orden de preferencia.
+pub struct Providers {
+    chain: Vec<Provider>,
+    timeout: Duration,
+}
+
+impl Providers {
+    pub fn from_env() -> Result<Self> {
+        let chain = match env_value("LLM_PROVIDERS") {
+            Some(list) => list
+                .split(',')
+                .filter(|s| !s.trim().is_empty())
+                .map(Provider::parse)
+                .collect::<Result<Vec<_>>>()?,
+            None => vec![single_from_env()?],
+        };
+        if chain.is_empty() {
+            return Err(anyhow!("LLM_PROVIDERS no tiene ningún proveedor"));
+        }
+        let timeout = env_value("LLM_PROVIDER_TIMEOUT_SECS")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DEFAULT_TIMEOUT_SECS);
+        Ok(Providers {
+            chain,
+            timeout: Duration::from_secs(timeout),
+        })
+    }
+
+    /// Descripción de la cadena para el log de arranque.
+    pub fn describe(&self) -> String {
+        let chain: Vec<String> = self
+            .chain
+            .iter()
+            .map(|p| format!("{}:{}", p.name(), p.model))
+            .collect();
+        chain.join(" → ")
+    }
+
+    /// Proveedores a probar para una petición: la cadena configurada o, si la petición
+    /// pide un modelo (`proveedor:modelo` o solo el modelo), únicamente ese. Si el modelo
+    /// está en la cadena se usa el proveedor configurado, con su endpoint y su clave.
+    pub fn for_request(&self, model: &str) -> Result<Candidates<'_>> {
+        if model.trim().is_empty() {
+            return Ok(Candidates::Chain(&self.chain));
+        }
+        match self.chain.iter().find(|p| p.matches(model)) {
+            Some(provider) => Ok(Candidates::Chain(std::slice::from_ref(provider))),
+            None => Ok(Candidates::Requested(Box::new(Provider::parse(model)?))),
+        }
+    }
+
+    /// Genera la respuesta con el primer proveedor que conteste.
+    pub async fn exec_chat(
+        &self,
+        candidates: &Candidates<'_>,
+        chat: &ChatRequest,
+        options: &ChatOptions,
+    ) -> Result<(ChatResponse, &'static str)> {
+        self.failover(candidates, |p| {
+            p.client.exec_chat(&p.model, chat.clone(), Some(options))
+        })
+        .await
+    }
+
+    /// Abre el stream con el primer proveedor que envíe algo. Devuelve también los
+    /// eventos leídos hasta el primer trozo de texto: un proveedor que falla antes de
+    /// ese trozo se puede sustituir sin que el cliente vea texto mezclado.
+    pub async fn exec_chat_stream(
+        &self,
+        candidates: &Candidates<'_>,
+        chat: &ChatRequest,
+        options: &ChatOptions,
+    ) -> Result<((ChatStreamResponse, Vec<ChatStreamEvent>), &'static str)> {
+        self.failover(candidates, |p| async move {
+            let mut res = p
+                .client
+                .exec_chat_stream(&p.model, chat.clone(), Some(options))
+                .await?;
+            let mut first = Vec::new();
+            while let Some(event) = res.stream.next().await {
+                let event = event?;
+                let started = matches!(event, ChatStreamEvent::Chunk(_) | ChatStreamEvent::End(_));
+                first.push(event);
+                if started {
+                    break;
+                }
+            }
+            Ok((res, first))
+        })
+        .await
+    }
+
+    async fn failover<'p, T, F, Fut>(
+        &self,
+        candidates: &'p Candidates<'_>,
+        call: F,
+    ) -> Result<(T, &'static str)>
+    where
+        F: Fn(&'p Provider) -> Fut,
+        Fut: Future<Output = genai::Result<T>>,
+    {
+        let mut errors = Vec::new();
+        for provider in candidates.iter() {
+            let label = format!("{}:{}", provider.name(), provider.model);
+            let error = match tokio::time::timeout(self.timeout, call(provider)).await {
+                Ok(Ok(res)) => return Ok((res, provider.name())),
+                Ok(Err(e)) => e.to_string(),
+                Err(_) => format!("sin respuesta en {}s", self.timeout.as_secs()),
+            };
+            warn!("Proveedor {label} falló: {error}");
+            errors.push(format!("{label}: {error}"));
+        }
+        Err(anyhow!(
+            "ningún proveedor respondió ({})",
+            errors.join("; ")
+        ))
+    }
+}
+
+/// Proveedores de una petición concreta.
+pub enum Candidates<'a> {
+    Chain(&'a [Provider]),
+    Requested(Box<Provider>),
+}
+
+impl Candidates<'_> {
+    pub fn iter(&self) -> impl Iterator<Item = &Provider> {
+        match self {
+            Candidates::Chain(chain) => chain.iter(),
+            Candidates::Requested(provider) => std::slice::from_ref(provider.as_ref()).iter(),
+        }
+    }
+}
+
+/// Un solo proveedor con la configuración anterior a `LLM_PROVIDERS`.
+fn single_from_env() -> Result<Provider> {
+    let model = env_value("LLM_MODEL");
+    match env_value("LLM_PROVIDER") {
+        Some(name) => {
+            let adapter = adapter_by_name(&name)
+                .ok_or_else(|| anyhow!("LLM_PROVIDER '{name}' no es un proveedor conocido"))?;
+            let model = env_value(&format!("{}_MODEL", adapter.as_lower_str().to_uppercase()))
+                .or(model)
+                .unwrap_or_else(|| DEFAULT_MODEL.to_string());
+            Ok(Provider::new(adapter, &model))
+        }
+        None => Provider::parse(model.as_deref().unwrap_or(DEFAULT_MODEL)),
+    }
+}
+
+/// Proveedor y modelo de `proveedor:modelo`; `None` si no empieza por un proveedor
+/// conocido (`llama3.1:8b` es solo un modelo).
+fn split_spec(spec: &str) -> Option<(AdapterKind, &str)> {
+    spec.split_once(':')
+        .and_then(|(name, model)| Some((adapter_by_name(name)?, model)))
+}
+
+fn adapter_by_name(name: &str) -> Option<AdapterKind> {
+    let name = name.trim().to_lowercase();
+    ADAPTERS.iter().copied().find(|a| a.as_lower_str() == name)
+}
+
+fn env_value(name: &str) -> Option<String> {
+    env::var(name)
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Los tests que cambian variables de entorno no pueden ir a la vez.
+    static ENV: Mutex<()> = Mutex::new(());
+
+    const ENV_VARS: &[&str] = &["LLM_MODEL", "LLM_PROVIDER", "OLLAMA_MODEL", "OPENAI_MODEL"];
+
+    fn with_env(vars: &[(&str, &str)], test: impl FnOnce()) {
+        let _guard = ENV.lock().unwrap_or_else(|e| e.into_inner());
+        for name in ENV_VARS {
+            env::remove_var(name);
+        }
+        for (name, value) in vars {
+            env::set_var(name, value);
+        }
+        test();
+        for name in ENV_VARS {
+            env::remove_var(name);
+        }
+    }
+
+    fn spec(provider: &Provider) -> String {
+        format!("{}:{}", provider.name(), provider.model)
+    }
+
+    fn providers(specs: &[&str], timeout: Duration) -> Providers {
+        Providers {
+            chain: specs.iter().map(|s| Provider::parse(s).unwrap()).collect(),
+            timeout,
+        }
+    }
+
+    #[test]
+    fn parse_reads_the_provider_prefix_only_when_it_is_known() {
+        let named = Provider::parse(" ollama:llama3.1:8b ").unwrap();
+        assert_eq!(spec(&named), "ollama:llama3.1:8b");
+        let bare = Provider::parse("llama3.1:8b").unwrap();
+        assert_eq!(spec(&bare), "ollama:llama3.1:8b");
+        assert_eq!(
+            spec(&Provider::parse("gpt-4o-mini").unwrap()),
+            "openai:gpt-4o-mini"
+        );
+        assert_eq!(
+            spec(&Provider::parse("OpenAI:gpt-4o-mini").unwrap()),
+            "openai:gpt-4o-mini"
+        );
+        assert!(Provider::parse("openai:").is_err());
+        assert!(Provider::parse("  ").is_err());
+    }
+
+    #[test]
+    fn single_from_env_prefers_the_provider_model() {
+        with_env(&[], || {
+            assert_eq!(spec(&single_from_env().unwrap()), "ollama:llama3.1:8b");
+        });
+        with_env(&[("LLM_MODEL", "gpt-4o-mini")], || {
+            assert_eq!(spec(&single_from_env().unwrap()), "openai:gpt-4o-mini");
+        });
+        with_env(
+            &[("LLM_PROVIDER", "ollama"), ("LLM_MODEL", "mistral")],
+            || assert_eq!(spec(&single_from_env().unwrap()), "ollama:mistral"),
+        );
+        with_env(
+            &[
+                ("LLM_PROVIDER", "openai"),
+                ("LLM_MODEL", "mistral"),
+                ("OPENAI_MODEL", "gpt-4o"),
+            ],
+            || assert_eq!(spec(&single_from_env().unwrap()), "openai:gpt-4o"),
+        );
+        with_env(&[("LLM_PROVIDER", "ollama")], || {
+            assert_eq!(spec(&single_from_env().unwrap()), "ollama:llama3.1:8b");
+        });
+        with_env(&[("LLM_PROVIDER", "desconocido")], || {
+            assert!(single_from_env().is_err());
+        });
+    }
+
+    #[test]
+    fn requested_models_reuse_the_configured_provider() {
+        let providers = providers(
+            &["ollama:llama3.1:8b", "openai:gpt-4o-mini"],
+            Duration::from_secs(1),
+        );
+        let chosen = |model: &str| -> Vec<String> {
+            let candidates = providers.for_request(model).unwrap();
+            let configured = matches!(candidates, Candidates::Chain(_));
+            candidates
+                .iter()
+                .map(|p| format!("{}{}", spec(p), if configured { "" } else { " (nuevo)" }))
+                .collect()
+        };
+        assert_eq!(chosen(""), ["ollama:llama3.1:8b", "openai:gpt-4o-mini"]);
+        assert_eq!(chosen("gpt-4o-mini"), ["openai:gpt-4o-mini"]);
+        assert_eq!(chosen(" openai:gpt-4o-mini "), ["openai:gpt-4o-mini"]);
+        assert_eq!(chosen("llama3.1:8b"), ["ollama:llama3.1:8b"]);
+        assert_eq!(chosen("ollama:gpt-4o-mini"), ["ollama:gpt-4o-mini (nuevo)"]);
+        assert_eq!(
+            chosen("claude-3-haiku"),
+            ["anthropic:claude-3-haiku (nuevo)"]
+        );
+    }
+
+    #[tokio::test]
+    async fn failover_tries_the_chain_in_order() {
+        let providers = providers(
+            &[
+                "ollama:falla",
+                "ollama:lento",
+                "openai:gpt-4o-mini",
+                "ollama:sobra",
+            ],
+            Duration::from_millis(50),
+        );
+        let candidates = providers.for_request("").unwrap();
+        let tried = Mutex::new(Vec::new());
+        let (model, name) = providers
+            .failover(&candidates, |p| {
+                tried.lock().unwrap().push(p.model.clone());
+                async move {
+                    match p.model.as_str() {
+                        "falla" => Err(genai::Error::JsonModeWithoutInstruction),
+                        "lento" => {
+                            tokio::time::sleep(Duration::from_secs(5)).await;
+                            Ok(String::new())
+                        }
+                        model => Ok(model.to_string()),
+                    }
+                }
+            })
+            .await
+            .unwrap();
+        assert_eq!((model.as_str(), name), ("gpt-4o-mini", "openai"));
+        assert_eq!(*tried.lock().unwrap(), ["falla", "lento", "gpt-4o-mini"]);
+    }
+
+    #[tokio::test]
+    async fn failover_reports_every_provider_when_all_fail() {
+        let providers = providers(&["ollama:a", "openai:gpt-4o"], Duration::from_secs(1));
+        let candidates = providers.for_request("").unwrap();
+        let error = providers
+            .failover(&candidates, |_| async {
+                Err::<(), _>(genai::Error::JsonModeWithoutInstruction)
+            })
+            .await
+            .unwrap_err()
+            .to_string();
+        let a = error.find("ollama:a:").unwrap();
+        let b = error.find("openai:gpt-4o:").unwrap();
+        assert!(error.starts_with("ningún proveedor respondió"), "{error}");
+        assert!(a < b, "{error}");
+    }
+}
@@ -0,0 +1,553 @@
+// agent_llm/src/sessions.rs
+//! Sesiones de conversación (bucket KV `llm_sessions`) y su API request/reply:
+//!
+//! - `sessions.list`:   → `{"sessions": [...]}` con el tamaño y las fechas de cada una
+//! - `sessions.clear`:  `session_id` (texto o `{"session_id": ...}`) → `{"cleared": ...}`
+//! - `sessions.export`: `session_id` → la sesión completa en JSON (resumen y mensajes)
+//!
+//! Una petición con `LlmRequest.session_id` recibe como `history` el resumen de la parte
+//! antigua de la conversación y los últimos mensajes; al terminar se guardan su prompt y
+//! su respuesta. Cuando la sesión pasa de `LLM_SESSION_MAX_MESSAGES` mensajes o de
+//! `LLM_SESSION_MAX_CHARS` caracteres, los mensajes más antiguos se resumen con el LLM
+//! y la plantilla `session_summary` (o se descartan con `LLM_SESSION_COMPACTION=truncate`,
+//! o si el resumen falla).
+
+use crate::providers::Providers;
+use anyhow::{anyhow, Result};
+use async_nats::jetstream::{self, kv};
+use async_nats::{Client, Message};
+use bytes::Bytes;
+use common::error::AgentError;
+use common::kv::{now_ms, open_bucket, parse_id, validate_key};
+use common::messaging::messages::{LlmRequest, TranscriptMessage};
+use common::messaging::{
+    NATS_SESSIONS_CLEAR_SUBJECT, NATS_SESSIONS_EXPORT_SUBJECT, NATS_SESSIONS_LIST_SUBJECT,
+};
+use common::prompts;
+use futures::{StreamExt, TryStreamExt};
+use genai::chat::{ChatMessage, ChatOptions, ChatRequest};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::cmp::Reverse;
+use std::env;
+use tracing::{debug, error, info, warn};
+
+pub const SESSIONS_BUCKET: &str = "llm_sessions";
+
+/// Mensajes guardados por defecto antes de compactar (`LLM_SESSION_MAX_MESSAGES`).
+const DEFAULT_MAX_MESSAGES: usize = 20;
+
+/// Caracteres guardados por defecto antes de compactar (`LLM_SESSION_MAX_CHARS`).
+const DEFAULT_MAX_CHARS: usize = 12_000;
+
+/// Conversación guardada de una sesión.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Session {
+    pub session_id: String,
+    /// Resumen de los mensajes ya compactados.
+    #[serde(default)]
+    pub summary: String,
+    /// Mensajes que se han resumido o descartado al compactar.
+    #[serde(default)]
+    pub compacted_messages: usize,
+    #[serde(default)]
+    pub messages: Vec<SessionMessage>,
+    #[serde(default)]
+    pub created_at_ms: u64,
+    #[serde(default)]
+    pub updated_at_ms: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SessionMessage {
+    /// `user` o `assistant`.
+    pub role: String,
+    pub content: String,
+    #[serde(default)]
+    pub at_ms: u64,
+}
+
+#[derive(Serialize, Debug)]
+struct SessionSummary {
+    session_id: String,
+    messages: usize,
+    compacted_messages: usize,
+    has_summary: bool,
+    created_at_ms: u64,
+    updated_at_ms: u64,
+}
+
+impl Session {
+    /// Mensajes que recibe el modelo: el resumen (si lo hay) y los mensajes guardados.
+    fn history(&self) -> Vec<TranscriptMessage> {
+        let summary = (!self.summary.trim().is_empty()).then(|| TranscriptMessage {
+            role: "system".to_string(),
+            content: format!("Resumen de la conversación anterior:\n{}", self.summary),
+            ..Default::default()
+        });
+        summary
+            .into_iter()
+            .chain(self.messages.iter().map(|m| TranscriptMessage {
+                role: m.role.clone(),
+                content: m.content.clone(),
+                ..Default::default()
+            }))
+            .collect()
+    }
+
+    fn chars(&self) -> usize {
+        self.messages
+            .iter()
+            .map(|m| m.content.chars().count())
+            .sum()
+    }
+
+    fn over_limit(&self, limits: &Limits) -> bool {
+        self.messages.len() > limits.max_messages || self.chars() > limits.max_chars
+    }
+
+    /// Número de mensajes antiguos a compactar para volver a la mitad de los límites,
+    /// conservando al menos el último intercambio.
+    fn to_compact(&self, limits: &Limits) -> usize {
+        let mut keep = self.messages.len().min(limits.max_messages / 2).max(2);
+        while keep > 2
+            && self.messages[self.messages.len() - keep..]
+                .iter()
+                .map(|m| m.content.chars().count())
+                .sum::<usize>()
+                > limits.max_chars / 2
+        {
+            keep -= 1;
+        }
+        self.messages.len().saturating_sub(keep)
+    }
+}
+
+struct Limits {
+    max_messages: usize,
+    max_chars: usize,
+    summarize: bool,
+}
+
+impl Limits {
+    fn from_env() -> Self {
+        let var = |name: &str, default: usize| {
+            env::var(name)
+                .ok()
+                .and_then(|s| s.trim().parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(default)
+        };
+        Limits {
+            max_messages: var("LLM_SESSION_MAX_MESSAGES", DEFAULT_MAX_MESSAGES).max(2),
+            max_chars: var("LLM_SESSION_MAX_CHARS", DEFAULT_MAX_CHARS),
+            summarize: env::var("LLM_SESSION_COMPACTION")
+                .map(|mode| mode.trim() != "truncate")
+                .unwrap_or(true),
+        }
+    }
+}
+
+/// Acceso al bucket KV de sesiones.
+#[derive(Clone)]
+pub struct SessionStore {
+    kv: kv::Store,
+}
+
+impl SessionStore {
+    /// Abre el bucket, creándolo si no existe.
+    pub async fn open(js: &jetstream::Context) -> Result<Self> {
+        let kv = open_bucket(
+            js,
+            SESSIONS_BUCKET,
+            "Sesiones de conversación del Agente LLM",
+        )
+        .await?;
+        Ok(Self { kv })
+    }
+
+    async fn load(&self, session_id: &str) -> Result<Option<Session>> {
+        match self.kv.get(session_id).await? {
+            Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn all(&self) -> Result<Vec<Session>> {
+        let keys: Vec<String> = self.kv.keys().await?.try_collect().await?;
+        let sessions = futures::stream::iter(keys)
+            .filter_map(|key| async move { self.load(&key).await.ok().flatten() })
+            .collect()
+            .await;
+        Ok(sessions)
+    }
+
+    /// Antepone a `req.history` el historial de su sesión.
+    pub async fn prepare(&self, req: &mut LlmRequest) -> Result<(), AgentError> {
+        validate_key("session_id", &req.session_id)?;
+        let session = self
+            .load(&req.session_id)
+            .await
+            .map_err(|e| AgentError::Unavailable(format!("sesión '{}': {e:#}", req.session_id)))?;
+        if let Some(session) = session {
+            let mut history = session.history();
+            history.append(&mut req.history);
+            req.history = history;
+        }
+        Ok(())
+    }
+
+    /// Guarda el prompt y la respuesta de una petición en su sesión. Devuelve si la
+    /// sesión ha pasado de los límites y conviene compactarla.
+    pub async fn record(&self, req: &LlmRequest, answer: &str) -> Result<bool> {
+        let now = now_ms();
+        let turn =
+            [("user", req.user_prompt.as_str()), ("assistant", answer)].map(|(role, content)| {
+                SessionMessage {
+                    role: role.to_string(),
+                    content: content.to_string(),
+                    at_ms: now,
+                }
+            });
+        let session = update(&self.kv, &req.session_id, |session| {
+            if session.created_at_ms == 0 {
+                session.created_at_ms = now;
+            }
+            session.updated_at_ms = now;
+            session.messages.extend(turn.iter().cloned());
+        })
+        .await?;
+        Ok(session.over_limit(&Limits::from_env()))
+    }
+
+    /// Resume (o descarta) los mensajes antiguos de una sesión que pasa de los límites.
+    /// Si otra petición la modifica mientras tanto, se deja para la siguiente.
+    pub async fn compact(&self, providers: &Providers, session_id: &str) {
+        if let Err(e) = self.try_compact(providers, session_id).await {
+            warn!("No se pudo compactar la sesión {session_id}: {e:#}");
+        }
+    }
+
+    async fn try_compact(&self, providers: &Providers, session_id: &str) -> Result<()> {
+        let limits = Limits::from_env();
+        let Some((value, revision)) = self.kv.read(session_id).await? else {
+            return Ok(());
+        };
+        let mut session: Session = serde_json::from_slice(&value)?;
+        if !session.over_limit(&limits) {
+            return Ok(());
+        }
+
+        let count = session.to_compact(&limits);
+        let old: Vec<SessionMessage> = session.messages.drain(..count).collect();
+        if limits.summarize {
+            match summarize(providers, session_id, &session.summary, &old).await {
+                Ok(summary) => session.summary = summary,
+                Err(e) => warn!(
+                    "Sesión {session_id}: se descartan {count} mensajes, el resumen falló: {e:#}"
+                ),
+            }
+        }
+        session.compacted_messages += count;
+
+        let value = serde_json::to_vec(&session)?.into();
+        if self.kv.write(session_id, value, Some(revision)).await? {
+            info!("Sesión {session_id}: {count} mensajes compactados");
+        } else {
+            debug!(
+                "Sesión {session_id} modificada mientras se compactaba; se deja para la siguiente"
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Lectura y escritura de sesiones con control de revisión.
+trait RevisionedKv {
+    /// Valor y revisión de `key`, o `None` si no existe o se ha borrado.
+    async fn read(&self, key: &str) -> Result<Option<(Bytes, u64)>>;
+
+    /// Guarda `value` si `key` sigue en `revision` (o aún no existe, con `None`).
+    /// Devuelve `false` si otra petición la ha modificado antes.
+    async fn write(&self, key: &str, value: Bytes, revision: Option<u64>) -> Result<bool>;
+}
+
+impl RevisionedKv for kv::Store {
+    async fn read(&self, key: &str) -> Result<Option<(Bytes, u64)>> {
+        Ok(self
+            .entry(key)
+            .await?
+            .filter(|e| e.operation == kv::Operation::Put)
+            .map(|e| (e.value, e.revision)))
+    }
+
+    async fn write(&self, key: &str, value: Bytes, revision: Option<u64>) -> Result<bool> {
+        match revision {
+            Some(revision) => match self.update(key, value, revision).await {
+                Ok(_) => Ok(true),
+                Err(e) if e.kind() == kv::UpdateErrorKind::WrongLastRevision => Ok(false),
+                Err(e) => Err(e.into()),
+            },
+            None => match self.create(key, value).await {
+                Ok(_) => Ok(true),
+                Err(e) if e.kind() == kv::CreateErrorKind::AlreadyExists => Ok(false),
+                Err(e) => Err(e.into()),
+            },
+        }
+    }
+}
+
+/// Modifica una sesión (creándola si no existe) con control de revisión, para no
+/// perder mensajes de peticiones simultáneas: si otra la modifica antes, se vuelve a
+/// leer y a aplicar `f`.
+async fn update<K, F>(kv: &K, session_id: &str, f: F) -> Result<Session>
+where
+    K: RevisionedKv,
+    F: Fn(&mut Session),
+{
+    loop {
+        let entry = kv.read(session_id).await?;
+        let mut session = match &entry {
+            Some((value, _)) => serde_json::from_slice(value)?,
+            None => Session {
+                session_id: session_id.to_string(),
+                ..Default::default()
+            },
+        };
+        f(&mut session);
+        let value = serde_json::to_vec(&session)?.into();
+        if kv
+            .write(session_id, value, entry.map(|(_, rev)| rev))
+            .await?
+        {
+            return Ok(session);
+        }
+    }
+}
+
+/// Resumen de `old` (y del resumen anterior) para seguir la conversación sin ellos.
+async fn summarize(
+    providers: &Providers,
+    session_id: &str,
+    previous: &str,
+    old: &[SessionMessage],
+) -> Result<String> {
+    let mut conversation = String::new();
+    if !previous.trim().is_empty() {
+        conversation.push_str(&format!("Resumen anterior:\n{previous}\n\n"));
+    }
+    conversation.push_str("Conversación:\n");
+    for m in old {
+        let who = if m.role == "user" {
+            "Usuario"
+        } else {
+            "Asistente"
+        };
+        conversation.push_str(&format!("{who}: {}\n", m.content));
+    }
+
+    let instruction = prompts::render("session_summary", session_id, &Value::Null)?;
+    let chat = ChatRequest::new(vec![
+        ChatMessage::system(instruction.text),
+        ChatMessage::user(conversation),
+    ]);
+    let candidates = providers.for_request("")?;
+    let (res, _) = providers
+        .exec_chat(&candidates, &chat, &ChatOptions::default())
+        .await?;
+    let summary = res
+        .content_text_as_str()
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+    if summary.is_empty() {
+        return Err(anyhow!("el LLM devolvió un resumen vacío"));
+    }
+    Ok(summary)
+}
+
+/// Atiende los subjects `sessions.*` hasta que se cierre la conexión.
+pub async fn serve(client: Client, sessions: SessionStore) -> Result<()> {
+    let list = client.subscribe(NATS_SESSIONS_LIST_SUBJECT).await?;
+    let clear = client.subscribe(NATS_SESSIONS_CLEAR_SUBJECT).await?;
+    let export = client.subscribe(NATS_SESSIONS_EXPORT_SUBJECT).await?;
+
+    let mut requests = futures::stream::select_all([list, clear, export]);
+    while let Some(msg) = requests.next().await {
+        let response = match handle(&sessions, &msg).await {
+            Ok(value) => value.to_string(),
+            Err(e) => format!("ERROR: {e:#}"),
+        };
+        if let Some(reply) = msg.reply {
+            if let Err(e) = client
+                .publish(reply, Bytes::from(response.into_bytes()))
+                .await
+            {
+                error!("No se pudo responder a {}: {e}", msg.subject);
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn handle(sessions: &SessionStore, msg: &Message) -> Result<Value> {
+    match msg.subject.as_str() {
+        NATS_SESSIONS_LIST_SUBJECT => {
+            let mut all = sessions.all().await?;
+            all.sort_by_key(|s| Reverse(s.updated_at_ms));
+            let summaries: Vec<SessionSummary> = all
+                .into_iter()
+                .map(|s| SessionSummary {
+                    messages: s.messages.len(),
+                    compacted_messages: s.compacted_messages,
+                    has_summary: !s.summary.trim().is_empty(),
+                    created_at_ms: s.created_at_ms,
+                    updated_at_ms: s.updated_at_ms,
+                    session_id: s.session_id,
+                })
+                .collect();
+            Ok(json!({ "sessions": summaries }))
+        }
+        NATS_SESSIONS_CLEAR_SUBJECT => {
+            let session_id = load(sessions, &msg.payload).await?.session_id;
+            sessions.kv.delete(&session_id).await?;
+            info!("Sesión {session_id} borrada");
+            Ok(json!({ "cleared": session_id }))
+        }
+        NATS_SESSIONS_EXPORT_SUBJECT => {
+            Ok(serde_json::to_value(load(sessions, &msg.payload).await?)?)
+        }
+        other => Err(anyhow!("subject no soportado '{other}'")),
+    }
+}
+
+async fn load(sessions: &SessionStore, payload: &[u8]) -> Result<Session> {
+    let session_id =
+        parse_id(payload, "session_id").ok_or_else(|| anyhow!("falta el session_id"))?;
+    sessions
+        .load(&session_id)
+        .await?
+        .ok_or_else(|| anyhow!("sesión '{session_id}' no encontrada"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    fn limits(max_messages: usize, max_chars: usize) -> Limits {
+        Limits {
+            max_messages,
+            max_chars,
+            summarize: true,
+        }
+    }
+
+    fn session(sizes: &[usize]) -> Session {
+        Session {
+            session_id: "s".to_string(),
+            messages: sizes
+                .iter()
+                .enumerate()
+                .map(|(i, &n)| SessionMessage {
+                    role: if i % 2 == 0 { "user" } else { "assistant" }.to_string(),
+                    content: "x".repeat(n),
+                    at_ms: 0,
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// KV en memoria; `interfere` simula otra petición que escribe justo antes de las
+    /// primeras escrituras.
+    #[derive(Default)]
+    struct MemoryKv {
+        entries: Mutex<HashMap<String, (Bytes, u64)>>,
+        interfere: Mutex<usize>,
+        writes: Mutex<usize>,
+    }
+
+    impl RevisionedKv for MemoryKv {
+        async fn read(&self, key: &str) -> Result<Option<(Bytes, u64)>> {
+            Ok(self.entries.lock().unwrap().get(key).cloned())
+        }
+
+        async fn write(&self, key: &str, value: Bytes, revision: Option<u64>) -> Result<bool> {
+            *self.writes.lock().unwrap() += 1;
+            let mut entries = self.entries.lock().unwrap();
+            let mut interfere = self.interfere.lock().unwrap();
+            if *interfere > 0 {
+                *interfere -= 1;
+                let mut other = match entries.get(key) {
+                    Some((value, _)) => serde_json::from_slice(value)?,
+                    None => Session::default(),
+                };
+                other.messages.extend(session(&[5]).messages);
+                let rev = entries.get(key).map_or(1, |(_, rev)| rev + 1);
+                entries.insert(key.to_string(), (serde_json::to_vec(&other)?.into(), rev));
+            }
+            let current = entries.get(key).map(|(_, rev)| *rev);
+            if current != revision {
+                return Ok(false);
+            }
+            entries.insert(key.to_string(), (value, current.unwrap_or(0) + 1));
+            Ok(true)
+        }
+    }
+
+    #[test]
+    fn history_starts_with_the_summary() {
+        let mut s = session(&[3, 4]);
+        assert_eq!(s.history().len(), 2);
+        s.summary = "Hablamos del CV".to_string();
+        let history = s.history();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0].role, "system");
+        assert_eq!(
+            history[0].content,
+            "Resumen de la conversación anterior:\nHablamos del CV"
+        );
+        assert_eq!(history[1].role, "user");
+    }
+
+    #[test]
+    fn limits_count_messages_and_chars() {
+        assert!(!session(&[10; 4]).over_limit(&limits(4, 40)));
+        assert!(session(&[10; 5]).over_limit(&limits(4, 100)));
+        assert!(session(&[10; 4]).over_limit(&limits(10, 39)));
+    }
+
+    #[test]
+    fn compaction_halves_the_session_and_keeps_the_last_turn() {
+        // Por mensajes: de 10 quedan 5.
+        assert_eq!(session(&[1; 10]).to_compact(&limits(10, 1000)), 5);
+        // Por caracteres: quedan los que caben en la mitad (60 de 120).
+        assert_eq!(session(&[20; 8]).to_compact(&limits(10, 120)), 5);
+        // El último intercambio se conserva aunque no quepa.
+        assert_eq!(session(&[500; 6]).to_compact(&limits(10, 100)), 4);
+        assert_eq!(session(&[1; 2]).to_compact(&limits(2, 1000)), 0);
+    }
+
+    #[tokio::test]
+    async fn update_retries_on_revision_conflicts() {
+        let kv = MemoryKv::default();
+        let add_turn = |s: &mut Session| s.messages.extend(session(&[1, 1]).messages);
+
+        // La creación y la siguiente actualización chocan con otra petición: se
+        // reintentan sin perder sus mensajes ni los de la otra.
+        *kv.interfere.lock().unwrap() = 1;
+        let saved = update(&kv, "s", add_turn).await.unwrap();
+        assert_eq!(saved.messages.len(), 3);
+        *kv.interfere.lock().unwrap() = 1;
+        let saved = update(&kv, "s", add_turn).await.unwrap();
+        assert_eq!(saved.messages.len(), 6);
+        assert_eq!(*kv.writes.lock().unwrap(), 4);
+
+        let (value, revision) = kv.read("s").await.unwrap().unwrap();
+        let stored: Session = serde_json::from_slice(&value).unwrap();
+        assert_eq!(stored.messages.len(), 6);
+        assert_eq!(revision, 4);
+    }
+}
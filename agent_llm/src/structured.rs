@@ -0,0 +1,150 @@
+// agent_llm/src/structured.rs
+//! Respuestas JSON que cumplen un JSON Schema (`LlmRequest.json_schema`).
+//!
+//! Se pide JSON al proveedor con el esquema en la instrucción de sistema y se valida la
+//! respuesta con `common::schema`. Si no es JSON o no cumple el esquema, se repite la
+//! conversación añadiendo la respuesta y los errores para que el modelo la corrija, hasta
+//! `LlmRequest.max_attempts` veces (o `LLM_JSON_MAX_ATTEMPTS`), 10 como mucho. Agotados los intentos, la
+//! petición falla con `AgentError::InvalidOutput`.
+
+use crate::providers::Providers;
+use crate::{add_usage, chat_request};
+use common::error::AgentError;
+use common::messaging::messages::{LlmRequest, LlmResponse, ResponseFormat, Usage};
+use common::schema::validate;
+use genai::chat::ChatMessage;
+use serde_json::Value;
+use std::env;
+use std::time::Instant;
+use tracing::warn;
+
+/// Intentos por defecto (`LLM_JSON_MAX_ATTEMPTS`).
+const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+
+/// Tope de intentos, pida lo que pida la petición: cada uno es una llamada al proveedor.
+const MAX_ATTEMPTS_LIMIT: u32 = 10;
+
+/// Errores de validación que se devuelven al modelo en cada reintento.
+const MAX_REPORTED_ERRORS: usize = 10;
+
+pub async fn llm_generate_json(
+    providers: &Providers,
+    req: &LlmRequest,
+) -> Result<LlmResponse, AgentError> {
+    let schema: Value = serde_json::from_str(&req.json_schema)
+        .map_err(|e| AgentError::InvalidArguments(format!("json_schema no es JSON válido: {e}")))?;
+    let max_attempts = max_attempts(req.max_attempts);
+
+    let candidates = providers
+        .for_request(&req.model)
+        .map_err(|e| AgentError::InvalidArguments(format!("{e:#}")))?;
+    let system = &req.system_prompt;
+    let (mut chat, options) = chat_request(&LlmRequest {
+        system_prompt: format!(
+            "{system}\n\nResponde únicamente con JSON, sin texto ni bloques de código \
+             alrededor, que cumpla este JSON Schema:\n{schema}"
+        ),
+        response_format: ResponseFormat::Json as i32,
+        ..req.clone()
+    });
+
+    let started = Instant::now();
+    let mut total = Usage::default();
+    let mut errors = Vec::new();
+    for attempt in 1..=max_attempts {
+        let (res, provider) = providers
+            .exec_chat(&candidates, &chat, &options)
+            .await
+            .map_err(|e| AgentError::LlmApiError(format!("{e:#}")))?;
+        add_usage(&mut total, &res.usage);
+
+        let content = res.content_text_as_str().unwrap_or_default().to_string();
+        errors = match serde_json::from_str::<Value>(extract_json(&content)) {
+            Ok(value) => validate(&schema, &value),
+            Err(e) => vec![format!("no es JSON válido: {e}")],
+        };
+        if errors.is_empty() {
+            let value: Value = serde_json::from_str(extract_json(&content))?;
+            return Ok(LlmResponse {
+                content: value.to_string(),
+                request_id: req.request_id.clone(),
+                model: res.provider_model_iden.model_name.to_string(),
+                provider: provider.to_string(),
+                usage: Some(total),
+                duration_ms: started.elapsed().as_millis() as u64,
+                attempts: attempt,
+                ..Default::default()
+            });
+        }
+
+        warn!(
+            "Intento {attempt}/{max_attempts}: la respuesta no cumple el esquema ({})",
+            errors.join("; ")
+        );
+        let reported: Vec<&str> = errors
+            .iter()
+            .take(MAX_REPORTED_ERRORS)
+            .map(String::as_str)
+            .collect();
+        chat = chat
+            .append_message(ChatMessage::assistant(content))
+            .append_message(ChatMessage::user(format!(
+                "Tu respuesta no cumple el JSON Schema:\n- {}\n\
+                 Devuelve de nuevo la respuesta completa corregida, solo el JSON.",
+                reported.join("\n- ")
+            )));
+    }
+
+    Err(AgentError::InvalidOutput(format!(
+        "la respuesta no cumple el JSON Schema tras {max_attempts} intentos: {}",
+        errors.join("; ")
+    )))
+}
+
+/// El JSON de la respuesta, sin bloques de código ni texto alrededor.
+/// Intentos pedidos (0 = `LLM_JSON_MAX_ATTEMPTS`), entre 1 y `MAX_ATTEMPTS_LIMIT`.
+fn max_attempts(requested: u32) -> u32 {
+    match requested {
+        0 => env::var("LLM_JSON_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(DEFAULT_MAX_ATTEMPTS),
+        n => n,
+    }
+    .clamp(1, MAX_ATTEMPTS_LIMIT)
+}
+
+fn extract_json(content: &str) -> &str {
+    let start = content.find(['{', '[']);
+    let end = content.rfind(['}', ']']);
+    match (start, end) {
+        (Some(start), Some(end)) if start < end => &content[start..=end],
+        _ => content.trim(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requested_attempts_are_capped() {
+        assert_eq!(max_attempts(1), 1);
+        assert_eq!(max_attempts(5), 5);
+        assert_eq!(max_attempts(MAX_ATTEMPTS_LIMIT), MAX_ATTEMPTS_LIMIT);
+        assert_eq!(max_attempts(1_000_000), MAX_ATTEMPTS_LIMIT);
+        assert_eq!(max_attempts(u32::MAX), MAX_ATTEMPTS_LIMIT);
+    }
+
+    #[test]
+    fn extract_json_drops_text_and_code_fences_around_the_value() {
+        assert_eq!(extract_json(r#"{"a": 1}"#), r#"{"a": 1}"#);
+        assert_eq!(
+            extract_json("Aquí tienes:\n```json\n{\"a\": [1, 2]}\n```\nEspero que sirva."),
+            r#"{"a": [1, 2]}"#
+        );
+        assert_eq!(extract_json("```\n[1, 2]\n```"), "[1, 2]");
+        assert_eq!(extract_json("  sin json  "), "sin json");
+        assert_eq!(extract_json("} al revés {"), "} al revés {");
+    }
+}
@@ -0,0 +1,338 @@
+// agent_llm/src/tool_calls.rs
+//! Llamadas del modelo a herramientas (`LlmRequest.tools`).
+//!
+//! Se ofrecen al modelo las herramientas de la petición. Las que solo traen el nombre (sin
+//! descripción ni parámetros) se completan con el catálogo de `list_tools` (ver
+//! `common::messaging::tools::tool_definitions`). Cada vez que el modelo pide ejecutar
+//! alguna, el agente envía el comando a `agents.tool` con el `Task-Id` de la petición y
+//! le devuelve el resultado, o el error para que pueda corregir los argumentos. El bucle
+//! termina cuando el modelo responde sin pedir herramientas o tras `max_tool_steps`
+//! llamadas al modelo (`LLM_TOOL_MAX_STEPS`). La respuesta lleva la conversación completa
+//! en `transcript`; si se agotan los pasos, también el error `task_failed` en `error`.
+
+use crate::providers::Providers;
+use crate::{add_usage, chat_request, error_detail};
+use async_nats::{Client, HeaderMap};
+use common::error::{AgentError, ErrorKind};
+use common::messaging::messages::{
+    LlmRequest, LlmResponse, ToolDefinition, ToolInvocation, TranscriptMessage, Usage,
+};
+use common::messaging::request::{request, RetryPolicy};
+use common::messaging::tools::tool_definitions;
+use common::messaging::{NATS_TOOL_SUBJECT, TASK_ID_HEADER};
+use genai::chat::{MessageContent, Tool, ToolCall, ToolResponse};
+use serde_json::{Map, Value};
+use std::env;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// Llamadas al modelo por defecto (`LLM_TOOL_MAX_STEPS`).
+const DEFAULT_MAX_STEPS: u32 = 8;
+
+/// Timeout por defecto de cada herramienta (`LLM_TOOL_TIMEOUT_SECS`).
+const DEFAULT_TOOL_TIMEOUT_SECS: u64 = 120;
+
+/// Caracteres del resultado de una herramienta que se pasan al modelo.
+const MAX_RESULT_CHARS: usize = 20_000;
+
+pub async fn llm_generate_with_tools(
+    providers: &Providers,
+    nats: &Client,
+    task_id: Option<&str>,
+    req: &LlmRequest,
+) -> Result<LlmResponse, AgentError> {
+    let max_steps = match req.max_tool_steps {
+        0 => env::var("LLM_TOOL_MAX_STEPS")
+            .ok()
+            .and_then(|s| s.trim().parse().ok())
+            .unwrap_or(DEFAULT_MAX_STEPS),
+        n => n,
+    }
+    .max(1);
+    let candidates = providers
+        .for_request(&req.model)
+        .map_err(|e| AgentError::InvalidArguments(format!("{e:#}")))?;
+
+    let mut headers = HeaderMap::new();
+    if let Some(task_id) = task_id {
+        headers.insert(TASK_ID_HEADER, task_id);
+    }
+    let policy = tool_policy();
+    let tools = with_catalog(nats, &headers, &req.tools)
+        .await?
+        .iter()
+        .map(genai_tool)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (chat, options) = chat_request(req);
+    let mut chat = chat.with_tools(tools);
+    let mut transcript = vec![message("system", &req.system_prompt)];
+    transcript.extend(req.history.iter().cloned());
+    transcript.push(message("user", &req.user_prompt));
+    let started = Instant::now();
+    let mut response = LlmResponse {
+        request_id: req.request_id.clone(),
+        ..Default::default()
+    };
+    let mut total = Usage::default();
+
+    for _ in 0..max_steps {
+        let (res, provider) = providers
+            .exec_chat(&candidates, &chat, &options)
+            .await
+            .map_err(|e| AgentError::LlmApiError(format!("{e:#}")))?;
+        add_usage(&mut total, &res.usage);
+        response.model = res.provider_model_iden.model_name.to_string();
+        response.provider = provider.to_string();
+
+        let calls = match res.content {
+            Some(MessageContent::ToolCalls(calls)) if !calls.is_empty() => calls,
+            content => {
+                let content = content
+                    .and_then(MessageContent::text_into_string)
+                    .unwrap_or_default();
+                transcript.push(message("assistant", &content));
+                return Ok(LlmResponse {
+                    content,
+                    usage: Some(total),
+                    duration_ms: started.elapsed().as_millis() as u64,
+                    transcript,
+                    ..response
+                });
+            }
+        };
+
+        response.tool_steps += 1;
+        transcript.push(TranscriptMessage {
+            role: "assistant".to_string(),
+            tool_calls: calls.iter().map(invocation).collect(),
+            ..Default::default()
+        });
+        chat = chat.append_message(calls.clone());
+        for call in calls {
+            debug!("Herramienta {} con {}", call.fn_name, call.fn_arguments);
+            let (content, is_error) = match run_tool(nats, &headers, &policy, req, &call).await {
+                Ok(result) => (truncate(result), false),
+                Err(e) if e.kind() == ErrorKind::Cancelled => return Err(e),
+                Err(e) => {
+                    warn!("La herramienta '{}' falló: {e}", call.fn_name);
+                    (format!("ERROR: {e}"), true)
+                }
+            };
+            transcript.push(TranscriptMessage {
+                role: "tool".to_string(),
+                content: content.clone(),
+                tool_call_id: call.call_id.clone(),
+                is_error,
+                ..Default::default()
+            });
+            chat = chat.append_message(ToolResponse::new(call.call_id, content));
+        }
+    }
+
+    Ok(unfinished(
+        LlmResponse {
+            usage: Some(total),
+            duration_ms: started.elapsed().as_millis() as u64,
+            transcript,
+            ..response
+        },
+        max_steps,
+    ))
+}
+
+/// Respuesta cuando se agotan los pasos: la conversación hasta ahí con el error
+/// `task_failed`. Se envía como respuesta normal, sin cabecera `Agent-Error`, para que el
+/// cliente reciba el `transcript`.
+fn unfinished(response: LlmResponse, max_steps: u32) -> LlmResponse {
+    let error = AgentError::TaskFailed(format!(
+        "el modelo no dio una respuesta final en {max_steps} pasos"
+    ));
+    LlmResponse {
+        error: Some(error_detail(&error)),
+        ..response
+    }
+}
+
+/// Completa con el catálogo de `list_tools` las herramientas que solo traen el nombre.
+async fn with_catalog(
+    nats: &Client,
+    headers: &HeaderMap,
+    tools: &[ToolDefinition],
+) -> Result<Vec<ToolDefinition>, AgentError> {
+    let bare: Vec<&str> = tools
+        .iter()
+        .filter(|t| is_bare(t))
+        .map(|t| t.name.as_str())
+        .collect();
+    if bare.is_empty() {
+        return Ok(tools.to_vec());
+    }
+    let catalog = tool_definitions(nats, headers.clone(), &bare).await?;
+    Ok(complete(tools, &catalog))
+}
+
+fn is_bare(tool: &ToolDefinition) -> bool {
+    tool.description.trim().is_empty() && tool.parameters_json.trim().is_empty()
+}
+
+fn complete(tools: &[ToolDefinition], catalog: &[ToolDefinition]) -> Vec<ToolDefinition> {
+    tools
+        .iter()
+        .map(|tool| {
+            catalog
+                .iter()
+                .find(|c| is_bare(tool) && c.name == tool.name)
+                .unwrap_or(tool)
+                .clone()
+        })
+        .collect()
+}
+
+/// Ejecuta en `agents.tool` una herramienta pedida por el modelo y devuelve su respuesta.
+async fn run_tool(
+    nats: &Client,
+    headers: &HeaderMap,
+    policy: &RetryPolicy,
+    req: &LlmRequest,
+    call: &ToolCall,
+) -> Result<String, AgentError> {
+    if !req.tools.iter().any(|t| t.name == call.fn_name) {
+        return Err(AgentError::ToolNotFound(call.fn_name.clone()));
+    }
+    // Algunos proveedores envían los argumentos como texto JSON
+    let arguments = match &call.fn_arguments {
+        Value::String(text) => serde_json::from_str(text)?,
+        other => other.clone(),
+    };
+    let mut payload = match arguments {
+        Value::Object(map) => map,
+        Value::Null => Map::new(),
+        _ => {
+            return Err(AgentError::InvalidArguments(format!(
+                "los argumentos de '{}' deben ser un objeto JSON",
+                call.fn_name
+            )))
+        }
+    };
+    payload.insert("cmd".to_string(), Value::String(call.fn_name.clone()));
+
+    let reply = request(
+        nats,
+        NATS_TOOL_SUBJECT,
+        headers.clone(),
+        serde_json::to_vec(&payload)?.into(),
+        policy,
+    )
+    .await?;
+    Ok(String::from_utf8_lossy(&reply).into_owned())
+}
+
+fn genai_tool(definition: &ToolDefinition) -> Result<Tool, AgentError> {
+    let mut tool = Tool::new(&definition.name).with_description(&definition.description);
+    if !definition.parameters_json.trim().is_empty() {
+        let schema = serde_json::from_str(&definition.parameters_json).map_err(|e| {
+            AgentError::InvalidArguments(format!(
+                "los parámetros de '{}' no son JSON válido: {e}",
+                definition.name
+            ))
+        })?;
+        tool = tool.with_schema(schema);
+    }
+    Ok(tool)
+}
+
+/// Política de reintentos de las herramientas, con el timeout de `LLM_TOOL_TIMEOUT_SECS`.
+fn tool_policy() -> RetryPolicy {
+    let timeout = env::var("LLM_TOOL_TIMEOUT_SECS")
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(DEFAULT_TOOL_TIMEOUT_SECS);
+    RetryPolicy::from_env().with_timeout(Duration::from_secs(timeout))
+}
+
+fn invocation(call: &ToolCall) -> ToolInvocation {
+    ToolInvocation {
+        call_id: call.call_id.clone(),
+        name: call.fn_name.clone(),
+        arguments_json: call.fn_arguments.to_string(),
+    }
+}
+
+fn message(role: &str, content: &str) -> TranscriptMessage {
+    TranscriptMessage {
+        role: role.to_string(),
+        content: content.to_string(),
+        ..Default::default()
+    }
+}
+
+/// Recorta resultados enormes (p. ej. el texto de una página) para no llenar el contexto.
+fn truncate(result: String) -> String {
+    if result.chars().count() <= MAX_RESULT_CHARS {
+        return result;
+    }
+    let cut: String = result.chars().take(MAX_RESULT_CHARS).collect();
+    format!("{cut}\n[resultado recortado a {MAX_RESULT_CHARS} caracteres]")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto_reply;
+
+    fn definition(name: &str, description: &str, parameters_json: &str) -> ToolDefinition {
+        ToolDefinition {
+            name: name.to_string(),
+            description: description.to_string(),
+            parameters_json: parameters_json.to_string(),
+        }
+    }
+
+    #[test]
+    fn bare_tools_are_completed_from_the_catalog() {
+        let tools = vec![
+            definition("web_search", "", ""),
+            definition("web_page", "Mi descripción", ""),
+        ];
+        let catalog = vec![
+            definition("web_search", "Busca en la web", r#"{"type":"object"}"#),
+            definition("web_page", "Descarga una página", r#"{"type":"object"}"#),
+        ];
+        let completed = complete(&tools, &catalog);
+        assert_eq!(completed[0], catalog[0]);
+        assert_eq!(completed[1], tools[1]);
+    }
+
+    #[test]
+    fn step_limit_keeps_the_transcript_without_error_header() {
+        let response = unfinished(
+            LlmResponse {
+                request_id: "r1".to_string(),
+                tool_steps: 2,
+                transcript: vec![message("user", "hola"), message("tool", "resultado")],
+                ..Default::default()
+            },
+            2,
+        );
+        let (reply, kind) = proto_reply(Ok(response), "r1", vec!["system@v1".to_string()]);
+        assert_eq!(kind, None);
+        assert_eq!(reply.transcript.len(), 2);
+        assert_eq!(reply.prompt_versions, vec!["system@v1"]);
+        let error = reply.error.unwrap();
+        assert_eq!(error.kind, ErrorKind::TaskFailed.as_str());
+        assert!(error.message.contains("2 pasos"));
+    }
+
+    #[test]
+    fn failures_carry_the_error_header() {
+        let (reply, kind) = proto_reply(
+            Err(AgentError::LlmApiError("sin conexión".to_string())),
+            "r1",
+            Vec::new(),
+        );
+        assert_eq!(kind, Some(ErrorKind::LlmApi));
+        assert!(reply.transcript.is_empty());
+        assert!(reply.error.is_some());
+    }
+}
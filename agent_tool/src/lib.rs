@@ -1,125 +1,218 @@
 use anyhow::Result;
 use async_nats::jetstream;
-use bytes::Bytes;
+use common::cancel::{listen_cancellations, task_id_of, InFlight};
+use common::error::AgentError;
+use common::messaging::request::respond;
+use common::messaging::NATS_TOOL_CANCEL_SUBJECT;
 use futures::StreamExt;
+#[cfg(feature = "toolkit")]
 use std::collections::HashMap;
 use std::env;
 use std::path::Path;
+use std::sync::Arc;
 
 mod tools;
 use crate::tools::pdf_analyzer::extract_pdf_text;
 
 const NATS_TOOL_SUBJECT: &str = "agents.tool";
 
+/// Herramientas registradas por nombre (solo con `toolkit`).
+#[cfg(feature = "toolkit")]
+type Registry = HashMap<&'static str, Box<dyn tools::Tool>>;
+#[cfg(not(feature = "toolkit"))]
+struct Registry;
+
 pub async fn run() -> Result<()> {
     let nats_url = env::var("NATS_URL").unwrap_or_else(|_| "nats://127.0.0.1:4222".to_string());
     let client = async_nats::connect(&nats_url).await?;
     let _js = jetstream::new(client.clone());
 
-    // ---------------------------
-    // Registry para `toolkit`
-    // ---------------------------
-    #[cfg(feature = "toolkit")]
-    let registry = {
-        use tools::{
-            excel_writer::ExcelWriterTool, file_system::FileSystemTool, pdf_parser::PdfParserTool,
-            web_scraper::WebSearchTool, Tool,
-        };
-        let mut map: HashMap<&'static str, Box<dyn Tool>> = HashMap::new();
-        map.insert("file_writer", Box::new(FileSystemTool));
-        map.insert("excel_writer", Box::new(ExcelWriterTool));
-        map.insert("pdf_extractor", Box::new(PdfParserTool));
-        map.insert("web_search", Box::new(WebSearchTool));
-        map
-    };
+    let registry = Arc::new(build_registry());
+
+    // Peticiones en curso por `Task-Id`, abortables desde `agents.tool.cancel`
+    let inflight = InFlight::new();
+    tokio::spawn(listen_cancellations(
+        client.clone(),
+        NATS_TOOL_CANCEL_SUBJECT,
+        inflight.clone(),
+    ));
 
     let mut subscription = client.subscribe(NATS_TOOL_SUBJECT).await?;
     while let Some(msg) = subscription.next().await {
-        let reply = msg.reply.clone();
-
-        // 1) Intenta parsear como JSON {"cmd": "...", ...}
-        let parsed_json: Result<serde_json::Value, _> = serde_json::from_slice(&msg.payload);
-        let response = if let Ok(mut val) = parsed_json {
-            // Copiamos 'cmd' a un String para no mantener un préstamo de 'val'
-            let cmd_opt = val
-                .get("cmd")
-                .and_then(|v| v.as_str())
-                .map(|s| s.to_string());
-
-            match cmd_opt.as_deref() {
-                // ---- Camino básico: analizar PDF por ruta ----
-                Some("analyze_pdf") | Some("pdf_to_text") => {
-                    let path = val.get("path").and_then(|p| p.as_str()).map(|s| s.to_string());
-                    match path {
-                        Some(p) => match extract_pdf_text(Path::new(&p)).await {
-                            Ok(t) => t,
-                            Err(e) => format!("ERROR extracting PDF: {e}"),
-                        },
-                        None => "ERROR: falta 'path' en la petición".to_string(),
-                    }
+        let (client, registry, inflight) = (client.clone(), registry.clone(), inflight.clone());
+
+        // Cada petición en su propia tarea para poder cancelarla sin bloquear el resto
+        tokio::spawn(async move {
+            let response = match task_id_of(&msg) {
+                Some(task_id) => inflight
+                    .run(&task_id, handle_request(&msg.payload, &registry))
+                    .await
+                    .unwrap_or_else(|_| {
+                        Err(AgentError::Cancelled(format!(
+                            "tarea '{task_id}' cancelada"
+                        )))
+                    }),
+                None => handle_request(&msg.payload, &registry).await,
+            };
+            respond(&client, msg.reply, response).await;
+        });
+    }
+    Ok(())
+}
+
+// ---------------------------
+// Registry para `toolkit`
+// ---------------------------
+#[cfg(feature = "toolkit")]
+fn build_registry() -> Registry {
+    use tools::{
+        docx_writer::DocxWriterTool,
+        excel_writer::ExcelWriterTool,
+        file_system::FileSystemTool,
+        pdf_parser::PdfParserTool,
+        web_scraper::{WebPageTool, WebSearchTool},
+        Tool,
+    };
+    let mut map: HashMap<&'static str, Box<dyn Tool>> = HashMap::new();
+    map.insert("file_writer", Box::new(FileSystemTool));
+    map.insert("excel_writer", Box::new(ExcelWriterTool));
+    map.insert("pdf_extractor", Box::new(PdfParserTool));
+    map.insert("web_search", Box::new(WebSearchTool));
+    map.insert("web_page", Box::new(WebPageTool));
+    map.insert("docx_writer", Box::new(DocxWriterTool));
+    map
+}
+
+#[cfg(not(feature = "toolkit"))]
+fn build_registry() -> Registry {
+    Registry
+}
+
+#[cfg_attr(not(feature = "toolkit"), allow(unused_variables, unused_mut))]
+async fn handle_request(payload: &[u8], registry: &Registry) -> Result<String, AgentError> {
+    // 1) Intenta parsear como JSON {"cmd": "...", ...}
+    let parsed_json: Result<serde_json::Value, _> = serde_json::from_slice(payload);
+    if let Ok(mut val) = parsed_json {
+        // Copiamos 'cmd' a un String para no mantener un préstamo de 'val'
+        let cmd_opt = val
+            .get("cmd")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
+        match cmd_opt.as_deref() {
+            // ---- Camino básico: analizar PDF por ruta ----
+            Some("analyze_pdf") | Some("pdf_to_text") => {
+                let path = val
+                    .get("path")
+                    .and_then(|p| p.as_str())
+                    .map(|s| s.to_string());
+                match path {
+                    Some(p) => extract_pdf_text(Path::new(&p))
+                        .await
+                        .map_err(|e| tool_error("analyze_pdf", e)),
+                    None => Err(AgentError::InvalidArguments(
+                        "falta 'path' en la petición".to_string(),
+                    )),
                 }
+            }
 
-                // ---- Rutas toolkit: pasan args JSON a la herramienta correspondiente ----
+            // ---- Catálogo de herramientas (nombre, descripción y esquema de argumentos) ----
+            Some("list_tools") => {
                 #[cfg(feature = "toolkit")]
-                Some(cmd) if ["file_writer", "excel_writer", "pdf_extractor", "web_search"]
-                    .contains(&cmd) =>
-                {
-                    use serde_json::json;
-
-                    if let Some(obj) = val.as_object_mut() {
-                        obj.remove("cmd"); // ya tenemos 'cmd' copiado
-                        let args_json = json!(obj).to_string();
-
-                        if let Some(tool) = registry.get(cmd) {
-                            match tool.execute(&args_json).await {
-                                Ok(value) => value.to_string(),
-                                Err(e) => format!("ERROR executing tool '{cmd}': {e}"),
-                            }
-                        } else {
-                            format!("ERROR: herramienta '{cmd}' no registrada")
-                        }
-                    } else {
-                        format!(
-                            "ERROR: el payload debe ser un objeto JSON con campos para '{cmd}'"
-                        )
-                    }
-                }
+                let tools: Vec<serde_json::Value> = {
+                    let mut names: Vec<_> = registry.keys().copied().collect();
+                    names.sort_unstable();
+                    names
+                        .into_iter()
+                        .map(|name| {
+                            let tool = &registry[name];
+                            serde_json::json!({
+                                "name": tool.name(),
+                                "description": tool.description(),
+                                "parameters": tool.parameters(),
+                            })
+                        })
+                        .collect()
+                };
+                #[cfg(not(feature = "toolkit"))]
+                let tools: Vec<serde_json::Value> = Vec::new();
+                Ok(serde_json::json!({ "tools": tools }).to_string())
+            }
 
-                // ---- Comando desconocido ----
-                Some(other) => format!("ERROR: comando no reconocido '{other}'"),
-
-                // Sin campo "cmd": fallback
-                None => {
-                    // 2) Fallback: payload solo con ruta .pdf en texto plano
-                    match std::str::from_utf8(&msg.payload) {
-                        Ok(p) if p.trim_end().ends_with(".pdf") => {
-                            match extract_pdf_text(Path::new(p.trim())).await {
-                                Ok(t) => t,
-                                Err(e) => format!("ERROR extracting PDF: {e}"),
-                            }
-                        }
-                        _ => "ERROR: payload sin 'cmd' no es JSON válido ni ruta .pdf".to_string(),
+            // ---- Rutas toolkit: pasan args JSON a la herramienta correspondiente ----
+            #[cfg(feature = "toolkit")]
+            Some(cmd)
+                if [
+                    "file_writer",
+                    "excel_writer",
+                    "docx_writer",
+                    "pdf_extractor",
+                    "web_search",
+                    "web_page",
+                ]
+                .contains(&cmd) =>
+            {
+                use serde_json::json;
+
+                if let Some(obj) = val.as_object_mut() {
+                    obj.remove("cmd"); // ya tenemos 'cmd' copiado
+                    let args_json = json!(obj).to_string();
+
+                    match registry.get(cmd) {
+                        Some(tool) => tool
+                            .execute(&args_json)
+                            .await
+                            .map(|value| value.to_string())
+                            .map_err(|e| tool_error(cmd, e)),
+                        None => Err(AgentError::ToolNotFound(cmd.to_string())),
                     }
+                } else {
+                    Err(AgentError::InvalidArguments(format!(
+                        "el payload debe ser un objeto JSON con campos para '{cmd}'"
+                    )))
                 }
             }
-        } else {
-            // 3) Fallback total: payload no es JSON -> prueba como ruta .pdf
-            match std::str::from_utf8(&msg.payload) {
-                Ok(p) if p.trim_end().ends_with(".pdf") => {
-                    match extract_pdf_text(Path::new(p.trim())).await {
-                        Ok(t) => t,
-                        Err(e) => format!("ERROR extracting PDF: {e}"),
+
+            // ---- Comando desconocido ----
+            Some(other) => Err(AgentError::ToolNotFound(other.to_string())),
+
+            // Sin campo "cmd": fallback
+            None => {
+                // 2) Fallback: payload solo con ruta .pdf en texto plano
+                match std::str::from_utf8(payload) {
+                    Ok(p) if p.trim_end().ends_with(".pdf") => {
+                        extract_pdf_text(Path::new(p.trim()))
+                            .await
+                            .map_err(|e| tool_error("analyze_pdf", e))
                     }
+                    _ => Err(AgentError::InvalidArguments(
+                        "payload sin 'cmd' no es JSON válido ni ruta .pdf".to_string(),
+                    )),
                 }
-                _ => "ERROR: payload no es JSON válido ni ruta .pdf".to_string(),
             }
-        };
-
-        if let Some(r) = reply {
-            let _ = client.publish(r, Bytes::from(response.into_bytes())).await;
+        }
+    } else {
+        // 3) Fallback total: payload no es JSON -> prueba como ruta .pdf
+        match std::str::from_utf8(payload) {
+            Ok(p) if p.trim_end().ends_with(".pdf") => extract_pdf_text(Path::new(p.trim()))
+                .await
+                .map_err(|e| tool_error("analyze_pdf", e)),
+            _ => Err(AgentError::InvalidArguments(
+                "payload no es JSON válido ni ruta .pdf".to_string(),
+            )),
         }
     }
-    Ok(())
 }
 
-
+/// Clasifica el error de una herramienta: argumentos que no encajan con su esquema,
+/// fallos de red (reintentables) o fallo de la propia herramienta.
+fn tool_error(cmd: &str, e: anyhow::Error) -> AgentError {
+    let message = format!("'{cmd}': {e:#}");
+    if e.downcast_ref::<serde_json::Error>().is_some() {
+        AgentError::InvalidArguments(message)
+    } else if e.downcast_ref::<reqwest::Error>().is_some() {
+        AgentError::Unavailable(message)
+    } else {
+        AgentError::TaskFailed(message)
+    }
+}
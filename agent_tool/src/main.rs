@@ -6,7 +6,10 @@ async fn main() -> Result<()> {
     tracing_subscriber::fmt::init();
 
     if let Err(e) = agent_tool::run().await {
-        tracing::error!("El Agente de Herramientas ha terminado con un error: {:?}", e);
+        tracing::error!(
+            "El Agente de Herramientas ha terminado con un error: {:?}",
+            e
+        );
     }
     Ok(())
 }
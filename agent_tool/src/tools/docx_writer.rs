@@ -0,0 +1,151 @@
+use super::Tool;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::Write;
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+pub struct DocxWriterTool;
+
+#[derive(Deserialize, Debug)]
+struct DocxWriterArgs {
+    path: String,
+    paragraphs: Vec<String>,
+}
+
+const CONTENT_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Override PartName="/word/document.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml"/></Types>"#;
+
+const RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/></Relationships>"#;
+
+#[async_trait]
+impl Tool for DocxWriterTool {
+    fn name(&self) -> &'static str {
+        "docx_writer"
+    }
+
+    fn description(&self) -> &'static str {
+        "Escribe un documento .docx con los párrafos indicados."
+    }
+
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "Ruta del archivo .docx" },
+                "paragraphs": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Párrafos del documento, en orden"
+                }
+            },
+            "required": ["path", "paragraphs"]
+        })
+    }
+
+    async fn execute(&self, args_json: &str) -> Result<Value> {
+        let args: DocxWriterArgs = serde_json::from_str(args_json)?;
+        let document = document_xml(&args.paragraphs);
+
+        // Escritura síncrona en hilo aparte, como el extractor de PDF
+        let path = args.path.clone();
+        tokio::task::spawn_blocking(move || write_docx(Path::new(&path), &document)).await??;
+
+        Ok(json!({ "status": "success", "path": args.path }))
+    }
+}
+
+/// Un DOCX mínimo: tipos de contenido, relación al documento y `word/document.xml`.
+fn write_docx(path: &Path, document: &str) -> Result<()> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut zip = ZipWriter::new(std::fs::File::create(path)?);
+    let options = SimpleFileOptions::default();
+    for (name, content) in [
+        ("[Content_Types].xml", CONTENT_TYPES),
+        ("_rels/.rels", RELS),
+        ("word/document.xml", document),
+    ] {
+        zip.start_file(name, options)?;
+        zip.write_all(content.as_bytes())?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+fn document_xml(paragraphs: &[String]) -> String {
+    let mut body = String::new();
+    for paragraph in paragraphs {
+        body.push_str("<w:p>");
+        // Los saltos de línea dentro de un párrafo se mantienen con <w:br/>
+        for (i, line) in paragraph.lines().enumerate() {
+            if i > 0 {
+                body.push_str("<w:r><w:br/></w:r>");
+            }
+            body.push_str(&format!(
+                r#"<w:r><w:t xml:space="preserve">{}</w:t></w:r>"#,
+                escape_xml(line)
+            ));
+        }
+        body.push_str("</w:p>");
+    }
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main"><w:body>{body}</w:body></w:document>"#
+    )
+}
+
+/// Escapa el texto para XML y quita los caracteres que XML 1.0 no admite (controles
+/// salvo tabulador y saltos de línea, U+FFFE y U+FFFF), que dejarían el .docx ilegible.
+fn escape_xml(text: &str) -> String {
+    text.chars()
+        .filter(|&c| is_xml_char(c))
+        .collect::<String>()
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn is_xml_char(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\r') || (c >= ' ' && !matches!(c, '\u{FFFE}' | '\u{FFFF}'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn control_characters_are_stripped_from_the_document() {
+        let dir = std::env::temp_dir().join(format!("docx_writer_{}", std::process::id()));
+        let path = dir.join("carta.docx");
+        let paragraphs = vec![
+            "Hola\u{0}\u{1}mundo\u{b}\u{c} & <equipo>".to_string(),
+            "Col1\tCol2\u{1f}\u{FFFF}\r\nsegunda línea".to_string(),
+        ];
+        write_docx(&path, &document_xml(&paragraphs)).unwrap();
+
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let mut xml = String::new();
+        archive
+            .by_name("word/document.xml")
+            .unwrap()
+            .read_to_string(&mut xml)
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(xml.chars().all(is_xml_char), "{xml:?}");
+        assert!(xml.contains("Holamundo &amp; &lt;equipo&gt;"), "{xml}");
+        assert!(
+            xml.contains("Col1\tCol2</w:t></w:r><w:r><w:br/></w:r>"),
+            "{xml}"
+        );
+        assert!(xml.contains("segunda línea"), "{xml}");
+    }
+}
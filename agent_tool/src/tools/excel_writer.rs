@@ -1,9 +1,14 @@
 use super::Tool;
 use anyhow::Result;
 use async_trait::async_trait;
-use rust_xlsxwriter::{Format, FormatAlign, Workbook, Worksheet, XlsxError};
+use common::gaps::GapReport;
+use common::offers::{sort_by_score, JobOffer, OfferStatus};
+use rust_xlsxwriter::{
+    ConditionalFormatFormula, Format, FormatAlign, Url, Workbook, Worksheet, XlsxError,
+};
 use serde::Deserialize;
 use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
 
 pub struct ExcelWriterTool;
 
@@ -11,15 +16,8 @@ pub struct ExcelWriterTool;
 struct ExcelWriterArgs {
     path: String,
     jobs: Vec<JobOffer>,
-}
-
-#[derive(Deserialize, Debug)]
-struct JobOffer {
-    title: String,
-    company: String,
-    location: String,
-    contact: String,
-    source_url: String,
+    #[serde(default)]
+    gaps: Vec<GapReport>,
 }
 
 #[async_trait]
@@ -32,13 +30,108 @@ impl Tool for ExcelWriterTool {
         "Escribe una lista de ofertas de trabajo en un archivo .xlsx."
     }
 
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "Ruta del archivo .xlsx" },
+                "jobs": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "title": { "type": "string" },
+                            "company": { "type": "string" },
+                            "location": { "type": "string" },
+                            "contact": { "type": "string" },
+                            "source_url": { "type": "string" },
+                            "sources": {
+                                "type": "array",
+                                "items": { "type": "string" },
+                                "description": "URLs de las publicaciones fusionadas en la oferta"
+                            },
+                            "score": {
+                                "type": "object",
+                                "description": "Encaje 0-100 con el perfil del candidato",
+                                "properties": {
+                                    "score": { "type": "integer", "minimum": 0, "maximum": 100 },
+                                    "summary": { "type": "string", "description": "Por qué de la puntuación" }
+                                },
+                                "required": ["score", "summary"]
+                            },
+                            "cover_letter": {
+                                "type": "object",
+                                "description": "Carta de presentación de la oferta",
+                                "properties": {
+                                    "markdown": { "type": "string" },
+                                    "docx": { "type": "string", "description": "Ruta enlazada desde el Excel" }
+                                },
+                                "required": ["markdown", "docx"]
+                            },
+                            "status": {
+                                "type": "string",
+                                "enum": ["new", "open", "changed", "gone"],
+                                "description": "Estado respecto a la ejecución anterior de la búsqueda"
+                            },
+                            "changes": {
+                                "type": "array",
+                                "items": { "type": "string" },
+                                "description": "Cambios desde la ejecución anterior"
+                            }
+                        },
+                        "required": ["title", "company", "location", "contact", "source_url"]
+                    }
+                },
+                "gaps": {
+                    "type": "array",
+                    "description": "Análisis de carencias por oferta; si hay, se escriben en la hoja 'Carencias'",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "title": { "type": "string" },
+                            "company": { "type": "string" },
+                            "source_url": { "type": "string" },
+                            "score": { "type": "integer" },
+                            "missing": {
+                                "type": "array",
+                                "items": {
+                                    "type": "object",
+                                    "properties": {
+                                        "requirement": { "type": "string" },
+                                        "detail": { "type": "string" }
+                                    },
+                                    "required": ["requirement"]
+                                }
+                            },
+                            "suggestions": {
+                                "type": "array",
+                                "items": {
+                                    "type": "object",
+                                    "properties": {
+                                        "original": { "type": "string" },
+                                        "rewrite": { "type": "string" },
+                                        "reason": { "type": "string" }
+                                    },
+                                    "required": ["original", "rewrite"]
+                                }
+                            }
+                        },
+                        "required": ["title", "source_url"]
+                    }
+                }
+            },
+            "required": ["path", "jobs"]
+        })
+    }
+
     async fn execute(&self, args_json: &str) -> Result<Value> {
-        let args: ExcelWriterArgs = serde_json::from_str(args_json)?;
+        let mut args: ExcelWriterArgs = serde_json::from_str(args_json)?;
+        sort_by_score(&mut args.jobs);
 
         let mut workbook = Workbook::new();
-        let mut worksheet = workbook.add_worksheet();
+        let worksheet = workbook.add_worksheet();
 
-        self.write_headers(&mut worksheet)?;
+        self.write_headers(worksheet)?;
 
         for (row_num, job) in args.jobs.iter().enumerate() {
             let r = row_num as u32 + 1;
@@ -48,6 +141,22 @@ impl Tool for ExcelWriterTool {
             worksheet.write_string(r, 3, &job.contact)?;
             // Importante: pasar &str, no &String
             worksheet.write_url(r, 4, job.source_url.as_str())?;
+            worksheet.write_string(r, 5, job.sources.join("\n"))?;
+            if let Some(score) = &job.score {
+                worksheet.write_number(r, 6, score.score)?;
+                worksheet.write_string(r, 7, &score.summary)?;
+            }
+            if let Some(letter) = &job.cover_letter {
+                let link = Url::new(file_link(&args.path, &letter.docx)).set_text("Carta");
+                worksheet.write_url(r, 8, link)?;
+            }
+            if let Some(status) = job.status {
+                worksheet.write_string(r, 9, status.label())?;
+                worksheet.write_string(r, 10, job.changes.join("\n"))?;
+            }
+        }
+        if args.jobs.iter().any(|j| j.status.is_some()) {
+            self.highlight_status(worksheet, args.jobs.len() as u32)?;
         }
 
         // Ancho de columnas razonable
@@ -56,6 +165,17 @@ impl Tool for ExcelWriterTool {
         worksheet.set_column_width(2, 20.0)?;
         worksheet.set_column_width(3, 18.0)?;
         worksheet.set_column_width(4, 46.0)?;
+        worksheet.set_column_width(5, 46.0)?;
+        worksheet.set_column_width(6, 12.0)?;
+        worksheet.set_column_width(7, 60.0)?;
+        worksheet.set_column_width(8, 12.0)?;
+        worksheet.set_column_width(9, 14.0)?;
+        worksheet.set_column_width(10, 40.0)?;
+
+        if !args.gaps.is_empty() {
+            let gaps_sheet = workbook.add_worksheet().set_name("Carencias")?;
+            self.write_gaps(gaps_sheet, &args.gaps)?;
+        }
 
         // Guardado síncrono (si prefieres no bloquear, lo envolvemos con spawn_blocking)
         workbook.save(&args.path)?;
@@ -65,6 +185,87 @@ impl Tool for ExcelWriterTool {
 }
 
 impl ExcelWriterTool {
+    /// Colorea cada fila según la columna "Estado": verde las nuevas, amarillo las
+    /// modificadas y gris tachado las desaparecidas.
+    fn highlight_status(&self, worksheet: &mut Worksheet, rows: u32) -> Result<(), XlsxError> {
+        let styles = [
+            (
+                OfferStatus::New,
+                Format::new()
+                    .set_font_color("006100")
+                    .set_background_color("C6EFCE"),
+            ),
+            (
+                OfferStatus::Changed,
+                Format::new()
+                    .set_font_color("9C5700")
+                    .set_background_color("FFEB9C"),
+            ),
+            (
+                OfferStatus::Gone,
+                Format::new()
+                    .set_font_color("808080")
+                    .set_background_color("EDEDED")
+                    .set_font_strikethrough(),
+            ),
+        ];
+        for (status, format) in styles {
+            let rule = ConditionalFormatFormula::new()
+                .set_rule(format!("=$J2=\"{}\"", status.label()).as_str())
+                .set_format(format);
+            worksheet.add_conditional_format(1, 0, rows, 10, &rule)?;
+        }
+        Ok(())
+    }
+
+    /// Hoja con una fila por carencia y por sugerencia de cada oferta analizada.
+    fn write_gaps(&self, worksheet: &mut Worksheet, gaps: &[GapReport]) -> Result<(), XlsxError> {
+        let header_fmt = Format::new().set_bold().set_align(FormatAlign::Center);
+        let wrap = Format::new().set_text_wrap();
+        let headers = [
+            ("Puesto", 28.0),
+            ("Empresa", 20.0),
+            ("Puntuación", 12.0),
+            ("Tipo", 12.0),
+            ("Requisito / Texto del CV", 50.0),
+            ("Nota / Propuesta", 60.0),
+            ("Por qué", 40.0),
+        ];
+        for (col, (header, width)) in headers.into_iter().enumerate() {
+            worksheet.write_with_format(0, col as u16, header, &header_fmt)?;
+            worksheet.set_column_width(col as u16, width)?;
+        }
+
+        let mut r = 1;
+        for report in gaps {
+            let mut row = |kind: &str, first: &str, second: &str, reason: &str| {
+                worksheet.write_string(r, 0, &report.title)?;
+                worksheet.write_string(r, 1, &report.company)?;
+                if let Some(score) = report.score {
+                    worksheet.write_number(r, 2, score)?;
+                }
+                worksheet.write_string(r, 3, kind)?;
+                worksheet.write_string_with_format(r, 4, first, &wrap)?;
+                worksheet.write_string_with_format(r, 5, second, &wrap)?;
+                worksheet.write_string_with_format(r, 6, reason, &wrap)?;
+                r += 1;
+                Ok::<(), XlsxError>(())
+            };
+            for gap in &report.missing {
+                row("Carencia", &gap.requirement, &gap.detail, "")?;
+            }
+            for suggestion in &report.suggestions {
+                row(
+                    "Sugerencia",
+                    &suggestion.original,
+                    &suggestion.rewrite,
+                    &suggestion.reason,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
     fn write_headers(&self, worksheet: &mut Worksheet) -> Result<(), XlsxError> {
         let header_fmt = Format::new().set_bold().set_align(FormatAlign::Center);
 
@@ -73,8 +274,107 @@ impl ExcelWriterTool {
         worksheet.write_with_format(0, 2, "Ubicación", &header_fmt)?;
         worksheet.write_with_format(0, 3, "Contacto", &header_fmt)?;
         worksheet.write_with_format(0, 4, "Fuente", &header_fmt)?;
+        worksheet.write_with_format(0, 5, "Fuentes", &header_fmt)?;
+        worksheet.write_with_format(0, 6, "Puntuación", &header_fmt)?;
+        worksheet.write_with_format(0, 7, "Por qué", &header_fmt)?;
+        worksheet.write_with_format(0, 8, "Carta", &header_fmt)?;
+        worksheet.write_with_format(0, 9, "Estado", &header_fmt)?;
+        worksheet.write_with_format(0, 10, "Cambios", &header_fmt)?;
         Ok(())
     }
 }
 
+/// Enlace a un archivo local. Si está dentro de la carpeta del Excel el enlace es
+/// relativo (y sigue funcionando si se mueve la carpeta entera); si no, apunta a su ruta
+/// absoluta. `rust_xlsxwriter` solo reconoce los enlaces a archivos con `file:///`, y
+/// guarda sin ese prefijo las rutas que no son de unidad de Windows.
+fn file_link(xlsx_path: &str, target: &str) -> String {
+    let dir = Path::new(xlsx_path).parent().unwrap_or(Path::new(""));
+    let dir = absolute(if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    });
+    let target = absolute(Path::new(target));
+    let path = match target.strip_prefix(&dir) {
+        Ok(relative) => relative.to_path_buf(),
+        Err(_) => target,
+    };
+    let path = path.to_string_lossy();
+    // `canonicalize` da en Windows rutas `\\?\C:\...`, que Excel no abre
+    format!("file:///{}", path.trim_start_matches(r"\\?\"))
+}
+
+/// Ruta canónica si existe; si no, la ruta absoluta sin resolver.
+fn absolute(path: &Path) -> PathBuf {
+    path.canonicalize()
+        .or_else(|_| std::path::absolute(path))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("excel_link_{}_{name}", std::process::id()));
+        fs::create_dir_all(dir.join("cartas")).unwrap();
+        dir.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn letters_inside_the_excel_folder_get_relative_links() {
+        let dir = temp_dir("inside");
+        let letter = dir.join("cartas").join("acme.docx");
+        fs::write(&letter, b"docx").unwrap();
+        let xlsx = dir.join("ofertas.xlsx");
+
+        let link = file_link(&xlsx.to_string_lossy(), &letter.to_string_lossy());
+        let expected = Path::new("cartas").join("acme.docx");
+        assert_eq!(link, format!("file:///{}", expected.display()));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn letters_outside_the_excel_folder_get_absolute_links() {
+        let dir = temp_dir("outside");
+        let letter = dir.join("cartas").join("acme.docx");
+        fs::write(&letter, b"docx").unwrap();
+        let xlsx = dir.join("informes").join("ofertas.xlsx");
+
+        let link = file_link(&xlsx.to_string_lossy(), &letter.to_string_lossy());
+        assert_eq!(link, format!("file:///{}", letter.display()));
+        assert!(Path::new(link.trim_start_matches("file:///")).is_absolute());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn written_links_point_at_the_letter() {
+        let dir = temp_dir("written");
+        let letter = dir.join("cartas").join("acme.docx");
+        fs::write(&letter, b"docx").unwrap();
+        let xlsx = dir.join("ofertas.xlsx");
 
+        let mut workbook = Workbook::new();
+        let link = Url::new(file_link(
+            &xlsx.to_string_lossy(),
+            &letter.to_string_lossy(),
+        ));
+        workbook.add_worksheet().write_url(0, 0, link).unwrap();
+        workbook.save(&xlsx).unwrap();
+
+        let file = fs::File::open(&xlsx).unwrap();
+        let mut archive = zip::ZipArchive::new(file).unwrap();
+        let mut rels = String::new();
+        std::io::Read::read_to_string(
+            &mut archive
+                .by_name("xl/worksheets/_rels/sheet1.xml.rels")
+                .unwrap(),
+            &mut rels,
+        )
+        .unwrap();
+        assert!(rels.contains(r#"Target="cartas/acme.docx""#), "{rels}");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -3,6 +3,7 @@ use anyhow::Result;
 use async_trait::async_trait;
 use serde::Deserialize;
 use serde_json::{json, Value};
+use std::path::Path;
 use tokio::fs;
 
 pub struct FileSystemTool;
@@ -15,12 +16,32 @@ struct FileWriterArgs {
 
 #[async_trait]
 impl Tool for FileSystemTool {
-    fn name(&self) -> &'static str { "file_writer" }
-    fn description(&self) -> &'static str { "Escribe contenido en un archivo en el sistema de ficheros." }
+    fn name(&self) -> &'static str {
+        "file_writer"
+    }
+    fn description(&self) -> &'static str {
+        "Escribe contenido en un archivo en el sistema de ficheros."
+    }
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "path": { "type": "string", "description": "Ruta del archivo a escribir" },
+                "content": { "type": "string", "description": "Contenido del archivo" }
+            },
+            "required": ["path", "content"]
+        })
+    }
 
     async fn execute(&self, args_json: &str) -> Result<Value> {
         let args: FileWriterArgs = serde_json::from_str(args_json)?;
+        if let Some(dir) = Path::new(&args.path)
+            .parent()
+            .filter(|d| !d.as_os_str().is_empty())
+        {
+            fs::create_dir_all(dir).await?;
+        }
         fs::write(&args.path, &args.content).await?;
         Ok(json!({ "status": "success", "path": args.path }))
     }
-}
\ No newline at end of file
+}
@@ -5,6 +5,9 @@ pub mod pdf_analyzer;
 
 // --- Herramientas opcionales (solo se compilan si activas --features toolkit) ---
 
+#[cfg(feature = "toolkit")]
+pub mod docx_writer;
+
 #[cfg(feature = "toolkit")]
 pub mod excel_writer;
 
@@ -26,17 +29,16 @@ use async_trait::async_trait;
 #[cfg(feature = "toolkit")]
 use serde_json::Value;
 
-/// Trait de herramientas solo cuando está activo `toolkit`
-/// (puede no usarse directamente si no consultas `name/description`).
+/// Trait de herramientas solo cuando está activo `toolkit`.
 #[cfg(feature = "toolkit")]
-#[allow(dead_code)]
 #[async_trait]
 pub trait Tool: Send + Sync {
     /// Nombre de la herramienta
     fn name(&self) -> &'static str;
     /// Descripción de lo que hace la herramienta
     fn description(&self) -> &'static str;
+    /// JSON Schema de los argumentos que acepta `execute`
+    fn parameters(&self) -> Value;
     /// Ejecuta la herramienta con argumentos en formato JSON
     async fn execute(&self, args_json: &str) -> Result<Value>;
 }
-
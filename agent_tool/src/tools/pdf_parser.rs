@@ -14,15 +14,28 @@ struct PdfParserArgs {
 
 #[async_trait]
 impl Tool for PdfParserTool {
-    fn name(&self) -> &'static str { "pdf_extractor" }
-    fn description(&self) -> &'static str { "Extrae texto de un archivo PDF proporcionado como base64." }
+    fn name(&self) -> &'static str {
+        "pdf_extractor"
+    }
+    fn description(&self) -> &'static str {
+        "Extrae texto de un archivo PDF proporcionado como base64."
+    }
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "pdf_data_base64": { "type": "string", "description": "Contenido del PDF en Base64" }
+            },
+            "required": ["pdf_data_base64"]
+        })
+    }
 
     async fn execute(&self, args_json: &str) -> Result<Value> {
         let args: PdfParserArgs = serde_json::from_str(args_json)?;
         let pdf_bytes = general_purpose::STANDARD.decode(&args.pdf_data_base64)?;
-        
+
         let text = pdf_extract::extract_text_from_mem(&pdf_bytes)?;
-        
+
         Ok(json!({ "text": text }))
     }
-}
\ No newline at end of file
+}
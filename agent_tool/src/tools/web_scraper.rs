@@ -5,6 +5,9 @@ use scraper::{Html, Selector};
 use serde::Deserialize;
 use serde_json::{json, Value};
 
+/// Versión HTML de DuckDuckGo, que no necesita JavaScript.
+const SEARCH_URL: &str = "https://html.duckduckgo.com/html/";
+
 pub struct WebSearchTool;
 
 #[derive(Deserialize)]
@@ -14,26 +17,143 @@ struct WebSearchArgs {
 
 #[async_trait]
 impl Tool for WebSearchTool {
-    fn name(&self) -> &'static str { "web_search" }
-    fn description(&self) -> &'static str { "Realiza una búsqueda web y extrae contenido de texto." }
+    fn name(&self) -> &'static str {
+        "web_search"
+    }
+    fn description(&self) -> &'static str {
+        "Realiza una búsqueda web y extrae contenido de texto."
+    }
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": { "type": "string", "description": "Texto de la búsqueda" }
+            },
+            "required": ["query"]
+        })
+    }
 
     async fn execute(&self, args_json: &str) -> Result<Value> {
         let args: WebSearchArgs = serde_json::from_str(args_json)?;
-        
-        let url = format!("https://html.duckduckgo.com/html/?q={}", args.query);
-        let response_html = reqwest::get(&url).await?.text().await?;
-        
+
+        let response_html = search_request(&reqwest::Client::new(), &args.query)
+            .send()
+            .await?
+            .text()
+            .await?;
+
         let document = Html::parse_document(&response_html);
         let selector = Selector::parse("a.result__a").unwrap();
-        
+
         let mut results = vec![];
         for element in document.select(&selector).take(5) {
             let title = element.text().collect::<String>().trim().to_string();
             if let Some(link) = element.value().attr("href") {
-                 results.push(json!({"title": title, "link": link}));
+                results.push(json!({"title": title, "link": link}));
             }
         }
-        
+
         Ok(json!({ "results": results }))
     }
-}
\ No newline at end of file
+}
+
+/// Petición de búsqueda con la consulta codificada en la URL (`&`, `#`, espacios...).
+fn search_request(client: &reqwest::Client, query: &str) -> reqwest::RequestBuilder {
+    client.get(SEARCH_URL).query(&[("q", query)])
+}
+
+/// Caracteres de texto que devuelve `web_page` por defecto.
+const DEFAULT_PAGE_CHARS: usize = 8000;
+
+pub struct WebPageTool;
+
+#[derive(Deserialize)]
+struct WebPageArgs {
+    url: String,
+    #[serde(default)]
+    max_chars: Option<usize>,
+}
+
+#[async_trait]
+impl Tool for WebPageTool {
+    fn name(&self) -> &'static str {
+        "web_page"
+    }
+    fn description(&self) -> &'static str {
+        "Descarga una página web y devuelve su título y su texto visible."
+    }
+    fn parameters(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "url": { "type": "string", "description": "URL de la página" },
+                "max_chars": {
+                    "type": "integer",
+                    "description": "Caracteres de texto como máximo (8000 por defecto)"
+                }
+            },
+            "required": ["url"]
+        })
+    }
+
+    async fn execute(&self, args_json: &str) -> Result<Value> {
+        let args: WebPageArgs = serde_json::from_str(args_json)?;
+        let max_chars = args.max_chars.unwrap_or(DEFAULT_PAGE_CHARS);
+
+        let response_html = reqwest::get(&args.url)
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let document = Html::parse_document(&response_html);
+
+        let title_selector = Selector::parse("title").unwrap();
+        let title = document
+            .select(&title_selector)
+            .next()
+            .map(|t| t.text().collect::<String>().trim().to_string())
+            .unwrap_or_default();
+
+        // Texto visible: todo el `body` salvo scripts, estilos y navegación
+        let body_selector = Selector::parse("body").unwrap();
+        let hidden = [
+            "script", "style", "noscript", "nav", "header", "footer", "svg",
+        ];
+        let mut words = Vec::new();
+        if let Some(body) = document.select(&body_selector).next() {
+            for node in body.descendants() {
+                let Some(text) = node.value().as_text() else {
+                    continue;
+                };
+                let inside_hidden = node.ancestors().any(|a| {
+                    a.value()
+                        .as_element()
+                        .is_some_and(|e| hidden.contains(&e.name()))
+                });
+                if !inside_hidden {
+                    words.extend(text.split_whitespace());
+                }
+            }
+        }
+        let text: String = words.join(" ").chars().take(max_chars).collect();
+
+        Ok(json!({ "url": args.url, "title": title, "text": text }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_query_is_url_encoded() {
+        let request = search_request(&reqwest::Client::new(), "C# & Rust #remoto?")
+            .build()
+            .unwrap();
+        assert_eq!(
+            request.url().as_str(),
+            "https://html.duckduckgo.com/html/?q=C%23+%26+Rust+%23remoto%3F"
+        );
+        assert_eq!(request.url().query_pairs().count(), 1);
+    }
+}
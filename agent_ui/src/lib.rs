@@ -1,6 +1,7 @@
 // agent_ui/src/lib.rs
 use anyhow::Result;
 use async_nats::Client as NatsClient;
+use common::task::{TaskStatus, TaskUpdate};
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use eframe::egui;
 use futures::StreamExt;
@@ -27,7 +28,11 @@ pub fn run() -> eframe::Result<()> {
     // Listener NATS en hilo separado
     thread::spawn(move || {
         let rt = Builder::new_current_thread().enable_all().build().unwrap();
-        let _ = rt.block_on(listen_status(&nats_url_for_thread, &status_subject_for_thread, tx));
+        let _ = rt.block_on(listen_status(
+            &nats_url_for_thread,
+            &status_subject_for_thread,
+            tx,
+        ));
     });
 
     let native_options = eframe::NativeOptions {
@@ -69,6 +74,12 @@ impl MyApp {
 impl eframe::App for MyApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         for msg in self.rx.try_iter() {
+            // Las actualizaciones del Coordinador llegan como `TaskUpdate` en JSON
+            if let Ok(update) = serde_json::from_str::<TaskUpdate>(&msg) {
+                self.busy = matches!(update.status, TaskStatus::InProgress(_));
+                self.last_status = describe_update(&update);
+                continue;
+            }
             self.last_status = msg;
             self.busy = self.last_status.contains("Iniciando")
                 || self.last_status.contains("Procesando")
@@ -99,6 +110,17 @@ impl eframe::App for MyApp {
     }
 }
 
+fn describe_update(update: &TaskUpdate) -> String {
+    let status = match &update.status {
+        TaskStatus::Idle => "En espera".to_string(),
+        TaskStatus::InProgress(msg) => format!("Procesando: {msg}"),
+        TaskStatus::Completed(path) => format!("Completada: {path}"),
+        TaskStatus::Failed(err) => format!("Fallida: {err}"),
+        TaskStatus::Cancelled(reason) => format!("Cancelada: {reason}"),
+    };
+    format!("[{}] {status}", update.task_id)
+}
+
 async fn listen_status(nats_url: &str, subject: &str, tx: Sender<String>) -> Result<()> {
     let client = async_nats::connect(nats_url).await?;
     subscribe_and_forward(&client, subject.to_string(), tx).await
@@ -119,4 +141,3 @@ async fn subscribe_and_forward(
     }
     Ok(())
 }
-
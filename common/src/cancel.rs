@@ -0,0 +1,88 @@
+use crate::error::AgentError;
+use futures::future::{AbortHandle, Abortable, Aborted};
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Registro de trabajos en curso por `task_id`, para poder abortarlos al recibir
+/// una cancelación. Se puede clonar y compartir entre tareas de tokio.
+#[derive(Clone, Default)]
+pub struct InFlight {
+    handles: Arc<Mutex<HashMap<String, HashMap<u64, AbortHandle>>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl InFlight {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ejecuta `fut` registrado bajo `task_id`. Devuelve `Err(Aborted)` si se cancela
+    /// la tarea antes de que termine.
+    pub async fn run<F: Future>(&self, task_id: &str, fut: F) -> Result<F::Output, Aborted> {
+        let (handle, registration) = AbortHandle::new_pair();
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.lock()
+            .entry(task_id.to_string())
+            .or_default()
+            .insert(id, handle);
+
+        let out = Abortable::new(fut, registration).await;
+
+        let mut handles = self.lock();
+        if let Some(entries) = handles.get_mut(task_id) {
+            entries.remove(&id);
+            if entries.is_empty() {
+                handles.remove(task_id);
+            }
+        }
+        out
+    }
+
+    /// Aborta todo lo que esté en curso para `task_id`; devuelve cuántos trabajos había.
+    pub fn cancel(&self, task_id: &str) -> usize {
+        let entries = self.lock().remove(task_id).unwrap_or_default();
+        for handle in entries.values() {
+            handle.abort();
+        }
+        entries.len()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, HashMap<u64, AbortHandle>>> {
+        // Un pánico con el lock tomado no deja el mapa en un estado inválido
+        self.handles.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Extrae el `task_id` de la cabecera `Task-Id` de una petición, si viene.
+pub fn task_id_of(msg: &async_nats::Message) -> Option<String> {
+    msg.headers
+        .as_ref()?
+        .get(crate::messaging::TASK_ID_HEADER)
+        .map(|v| v.as_str().to_string())
+}
+
+/// Escucha un subject de cancelación (payload: `task_id`) y aborta lo que haya en
+/// curso para esa tarea en `inflight`.
+pub async fn listen_cancellations(
+    client: async_nats::Client,
+    subject: &'static str,
+    inflight: InFlight,
+) -> Result<(), AgentError> {
+    let mut sub = client
+        .subscribe(subject)
+        .await
+        .map_err(|e| AgentError::NatsError(e.into()))?;
+    while let Some(msg) = sub.next().await {
+        if let Ok(task_id) = std::str::from_utf8(&msg.payload) {
+            let task_id = task_id.trim();
+            let aborted = inflight.cancel(task_id);
+            if aborted > 0 {
+                tracing::info!("Tarea {task_id}: {aborted} peticiones canceladas");
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,450 @@
+//! Perfil estructurado del candidato, extraído del texto del CV.
+//!
+//! Se serializa en JSON (checkpoints, API de tareas, respuestas del LLM) y tiene su
+//! equivalente protobuf en `agent_messages.proto` (`messages::CvProfile`).
+
+use crate::messaging::messages;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct CvProfile {
+    #[serde(default)]
+    pub skills: Vec<Skill>,
+    /// Puestos desempeñados o buscados (p. ej. "Desarrollador backend").
+    #[serde(default)]
+    pub roles: Vec<String>,
+    #[serde(default)]
+    pub education: Vec<Education>,
+    #[serde(default)]
+    pub languages: Vec<SpokenLanguage>,
+    /// Ciudad o región donde reside o quiere trabajar.
+    #[serde(default)]
+    pub location: Option<String>,
+    #[serde(default)]
+    pub remote: RemotePreference,
+    #[serde(default)]
+    pub seniority: Seniority,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Skill {
+    pub name: String,
+    /// Años de experiencia con la habilidad, si el CV permite deducirlos.
+    #[serde(default)]
+    pub years: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Education {
+    pub title: String,
+    #[serde(default)]
+    pub institution: Option<String>,
+    /// Año de finalización.
+    #[serde(default)]
+    pub year: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SpokenLanguage {
+    pub language: String,
+    /// Nivel tal y como aparece en el CV (`nativo`, `C1`, `B2`, ...).
+    #[serde(default)]
+    pub level: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum RemotePreference {
+    #[default]
+    Any,
+    Remote,
+    Hybrid,
+    Onsite,
+}
+
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Seniority {
+    #[default]
+    Unknown,
+    Junior,
+    Mid,
+    Senior,
+    Lead,
+}
+
+impl Seniority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Seniority::Unknown => "unknown",
+            Seniority::Junior => "junior",
+            Seniority::Mid => "mid",
+            Seniority::Senior => "senior",
+            Seniority::Lead => "lead",
+        }
+    }
+}
+
+/// Años máximos de experiencia que se aceptan en una habilidad.
+const MAX_SKILL_YEARS: f32 = 50.0;
+
+impl CvProfile {
+    /// JSON Schema del perfil, para pedírselo al LLM.
+    pub fn json_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "skills": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "name": { "type": "string" },
+                            "years": { "type": ["number", "null"], "minimum": 0, "maximum": MAX_SKILL_YEARS }
+                        },
+                        "required": ["name"]
+                    }
+                },
+                "roles": { "type": "array", "items": { "type": "string" } },
+                "education": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "title": { "type": "string" },
+                            "institution": { "type": ["string", "null"] },
+                            "year": { "type": ["integer", "null"] }
+                        },
+                        "required": ["title"]
+                    }
+                },
+                "languages": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "language": { "type": "string" },
+                            "level": { "type": ["string", "null"] }
+                        },
+                        "required": ["language"]
+                    }
+                },
+                "location": { "type": ["string", "null"] },
+                "remote": { "enum": ["any", "remote", "hybrid", "onsite"] },
+                "seniority": { "enum": ["unknown", "junior", "mid", "senior", "lead"] }
+            },
+            "required": ["skills", "roles", "education", "languages", "remote", "seniority"]
+        })
+    }
+
+    /// Problemas del perfil más allá de la forma del JSON (vacío si es válido).
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.skills.is_empty() && self.roles.is_empty() {
+            errors.push("el perfil no tiene ni habilidades ni puestos".to_string());
+        }
+
+        let mut seen = Vec::new();
+        for skill in &self.skills {
+            let name = skill.name.trim().to_lowercase();
+            if name.is_empty() {
+                errors.push("hay una habilidad sin nombre".to_string());
+                continue;
+            }
+            if seen.contains(&name) {
+                errors.push(format!("habilidad repetida '{}'", skill.name));
+            }
+            if let Some(years) = skill.years {
+                if !(0.0..=MAX_SKILL_YEARS).contains(&years) {
+                    errors.push(format!("años de '{}' fuera de rango: {years}", skill.name));
+                }
+            }
+            seen.push(name);
+        }
+        if self.roles.iter().any(|r| r.trim().is_empty()) {
+            errors.push("hay un puesto vacío".to_string());
+        }
+        for education in &self.education {
+            if education.title.trim().is_empty() {
+                errors.push("hay una titulación sin nombre".to_string());
+            }
+            if let Some(year) = education.year {
+                if !(1950..=2100).contains(&year) {
+                    errors.push(format!(
+                        "año de '{}' fuera de rango: {year}",
+                        education.title
+                    ));
+                }
+            }
+        }
+        if self.languages.iter().any(|l| l.language.trim().is_empty()) {
+            errors.push("hay un idioma sin nombre".to_string());
+        }
+        if self
+            .location
+            .as_deref()
+            .is_some_and(|l| l.trim().is_empty())
+        {
+            errors.push("'location' vacío: usa null si no se conoce".to_string());
+        }
+        errors
+    }
+}
+
+impl From<&CvProfile> for messages::CvProfile {
+    fn from(p: &CvProfile) -> Self {
+        messages::CvProfile {
+            skills: p
+                .skills
+                .iter()
+                .map(|s| messages::Skill {
+                    name: s.name.clone(),
+                    years: s.years,
+                })
+                .collect(),
+            roles: p.roles.clone(),
+            education: p
+                .education
+                .iter()
+                .map(|e| messages::Education {
+                    title: e.title.clone(),
+                    institution: e.institution.clone(),
+                    year: e.year,
+                })
+                .collect(),
+            languages: p
+                .languages
+                .iter()
+                .map(|l| messages::SpokenLanguage {
+                    language: l.language.clone(),
+                    level: l.level.clone(),
+                })
+                .collect(),
+            location: p.location.clone(),
+            remote: messages::RemotePreference::from(p.remote) as i32,
+            seniority: messages::Seniority::from(p.seniority) as i32,
+        }
+    }
+}
+
+impl From<messages::CvProfile> for CvProfile {
+    fn from(p: messages::CvProfile) -> Self {
+        CvProfile {
+            remote: p.remote().into(),
+            seniority: p.seniority().into(),
+            skills: p
+                .skills
+                .into_iter()
+                .map(|s| Skill {
+                    name: s.name,
+                    years: s.years,
+                })
+                .collect(),
+            roles: p.roles,
+            education: p
+                .education
+                .into_iter()
+                .map(|e| Education {
+                    title: e.title,
+                    institution: e.institution,
+                    year: e.year,
+                })
+                .collect(),
+            languages: p
+                .languages
+                .into_iter()
+                .map(|l| SpokenLanguage {
+                    language: l.language,
+                    level: l.level,
+                })
+                .collect(),
+            location: p.location,
+        }
+    }
+}
+
+impl From<RemotePreference> for messages::RemotePreference {
+    fn from(r: RemotePreference) -> Self {
+        match r {
+            RemotePreference::Any => messages::RemotePreference::Any,
+            RemotePreference::Remote => messages::RemotePreference::Remote,
+            RemotePreference::Hybrid => messages::RemotePreference::Hybrid,
+            RemotePreference::Onsite => messages::RemotePreference::Onsite,
+        }
+    }
+}
+
+impl From<messages::RemotePreference> for RemotePreference {
+    fn from(r: messages::RemotePreference) -> Self {
+        match r {
+            messages::RemotePreference::Any => RemotePreference::Any,
+            messages::RemotePreference::Remote => RemotePreference::Remote,
+            messages::RemotePreference::Hybrid => RemotePreference::Hybrid,
+            messages::RemotePreference::Onsite => RemotePreference::Onsite,
+        }
+    }
+}
+
+impl From<Seniority> for messages::Seniority {
+    fn from(s: Seniority) -> Self {
+        match s {
+            Seniority::Unknown => messages::Seniority::Unknown,
+            Seniority::Junior => messages::Seniority::Junior,
+            Seniority::Mid => messages::Seniority::Mid,
+            Seniority::Senior => messages::Seniority::Senior,
+            Seniority::Lead => messages::Seniority::Lead,
+        }
+    }
+}
+
+impl From<messages::Seniority> for Seniority {
+    fn from(s: messages::Seniority) -> Self {
+        match s {
+            messages::Seniority::Unknown => Seniority::Unknown,
+            messages::Seniority::Junior => Seniority::Junior,
+            messages::Seniority::Mid => Seniority::Mid,
+            messages::Seniority::Senior => Seniority::Senior,
+            messages::Seniority::Lead => Seniority::Lead,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema;
+    use prost::Message as _;
+
+    fn profile() -> CvProfile {
+        CvProfile {
+            skills: vec![
+                Skill {
+                    name: "Rust".into(),
+                    years: Some(4.0),
+                },
+                Skill {
+                    name: "SQL".into(),
+                    years: None,
+                },
+            ],
+            roles: vec!["Desarrollador backend".into()],
+            education: vec![Education {
+                title: "Grado en Informática".into(),
+                institution: Some("Universidad de Sevilla".into()),
+                year: Some(2018),
+            }],
+            languages: vec![SpokenLanguage {
+                language: "Inglés".into(),
+                level: Some("C1".into()),
+            }],
+            location: Some("Sevilla".into()),
+            remote: RemotePreference::Hybrid,
+            seniority: Seniority::Senior,
+        }
+    }
+
+    #[test]
+    fn a_complete_profile_is_valid() {
+        assert!(profile().validate().is_empty());
+    }
+
+    #[test]
+    fn validate_reports_missing_and_out_of_range_fields() {
+        let mut cv = profile();
+        cv.skills.push(Skill {
+            name: " ".into(),
+            years: None,
+        });
+        cv.skills.push(Skill {
+            name: "rust".into(),
+            years: Some(80.0),
+        });
+        cv.roles.push(String::new());
+        cv.education[0].year = Some(1900);
+        cv.languages[0].language = String::new();
+        cv.location = Some(" ".into());
+
+        let errors = cv.validate();
+        for expected in [
+            "hay una habilidad sin nombre",
+            "habilidad repetida 'rust'",
+            "años de 'rust' fuera de rango: 80",
+            "hay un puesto vacío",
+            "año de 'Grado en Informática' fuera de rango: 1900",
+            "hay un idioma sin nombre",
+            "'location' vacío: usa null si no se conoce",
+        ] {
+            assert!(
+                errors.iter().any(|e| e == expected),
+                "falta '{expected}' en {errors:?}"
+            );
+        }
+        assert_eq!(errors.len(), 7, "{errors:?}");
+
+        let empty = CvProfile::default().validate();
+        assert_eq!(empty, ["el perfil no tiene ni habilidades ni puestos"]);
+    }
+
+    #[test]
+    fn json_schema_accepts_serialized_profiles_and_rejects_bad_shapes() {
+        let schema = CvProfile::json_schema();
+        let value = serde_json::to_value(profile()).unwrap();
+        assert!(schema::validate(&schema, &value).is_empty());
+        let value = serde_json::to_value(CvProfile::default()).unwrap();
+        assert!(schema::validate(&schema, &value).is_empty());
+
+        let mut missing = serde_json::to_value(profile()).unwrap();
+        missing.as_object_mut().unwrap().remove("roles");
+        assert!(!schema::validate(&schema, &missing).is_empty());
+
+        let mut bad_enum = serde_json::to_value(profile()).unwrap();
+        bad_enum["remote"] = json!("teletrabajo");
+        assert!(!schema::validate(&schema, &bad_enum).is_empty());
+
+        let mut bad_years = serde_json::to_value(profile()).unwrap();
+        bad_years["skills"][0]["years"] = json!(MAX_SKILL_YEARS + 1.0);
+        assert!(!schema::validate(&schema, &bad_years).is_empty());
+
+        let mut no_name = serde_json::to_value(profile()).unwrap();
+        no_name["skills"][0] = json!({ "years": 2 });
+        assert!(!schema::validate(&schema, &no_name).is_empty());
+    }
+
+    #[test]
+    fn proto_round_trip_keeps_every_field() {
+        for cv in [profile(), CvProfile::default()] {
+            let bytes = messages::CvProfile::from(&cv).encode_to_vec();
+            let decoded = messages::CvProfile::decode(bytes.as_slice()).unwrap();
+            assert_eq!(CvProfile::from(decoded), cv);
+        }
+
+        for remote in [
+            RemotePreference::Any,
+            RemotePreference::Remote,
+            RemotePreference::Hybrid,
+            RemotePreference::Onsite,
+        ] {
+            let cv = CvProfile {
+                remote,
+                ..profile()
+            };
+            assert_eq!(CvProfile::from(messages::CvProfile::from(&cv)), cv);
+        }
+        for seniority in [
+            Seniority::Unknown,
+            Seniority::Junior,
+            Seniority::Mid,
+            Seniority::Senior,
+            Seniority::Lead,
+        ] {
+            let cv = CvProfile {
+                seniority,
+                ..profile()
+            };
+            assert_eq!(CvProfile::from(messages::CvProfile::from(&cv)), cv);
+        }
+    }
+}
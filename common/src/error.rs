@@ -18,6 +18,118 @@ pub enum AgentError {
     LlmApiError(String),
     #[error("Tarea fallida: {0}")]
     TaskFailed(String),
+    #[error("Argumentos no válidos: {0}")]
+    InvalidArguments(String),
+    #[error("Tiempo de espera agotado: {0}")]
+    Timeout(String),
+    #[error("Servicio no disponible: {0}")]
+    Unavailable(String),
+    #[error("Cancelada: {0}")]
+    Cancelled(String),
+    /// El LLM no devolvió una salida válida (p. ej. JSON que no cumple el esquema) tras
+    /// agotar los intentos de reparación.
+    #[error("Salida no válida: {0}")]
+    InvalidOutput(String),
+    /// Error devuelto por otro agente; `kind` viene en la cabecera `Agent-Error`.
+    #[error("{message}")]
+    Remote { kind: ErrorKind, message: String },
+}
+
+/// Clase de un `AgentError`, tal y como viaja en la cabecera `Agent-Error` de las
+/// respuestas entre agentes. Decide si merece la pena reintentar una petición.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    Nats,
+    Serialization,
+    Proto,
+    Io,
+    ToolNotFound,
+    Configuration,
+    LlmApi,
+    TaskFailed,
+    InvalidArguments,
+    Timeout,
+    Unavailable,
+    Cancelled,
+    InvalidOutput,
+}
+
+impl ErrorKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorKind::Nats => "nats",
+            ErrorKind::Serialization => "serialization",
+            ErrorKind::Proto => "proto",
+            ErrorKind::Io => "io",
+            ErrorKind::ToolNotFound => "tool_not_found",
+            ErrorKind::Configuration => "configuration",
+            ErrorKind::LlmApi => "llm_api",
+            ErrorKind::TaskFailed => "task_failed",
+            ErrorKind::InvalidArguments => "invalid_arguments",
+            ErrorKind::Timeout => "timeout",
+            ErrorKind::Unavailable => "unavailable",
+            ErrorKind::Cancelled => "cancelled",
+            ErrorKind::InvalidOutput => "invalid_output",
+        }
+    }
+
+    /// Clase a partir del valor de la cabecera; las desconocidas cuentan como `TaskFailed`.
+    pub fn parse(value: &str) -> Self {
+        match value.trim() {
+            "nats" => ErrorKind::Nats,
+            "serialization" => ErrorKind::Serialization,
+            "proto" => ErrorKind::Proto,
+            "io" => ErrorKind::Io,
+            "tool_not_found" => ErrorKind::ToolNotFound,
+            "configuration" => ErrorKind::Configuration,
+            "llm_api" => ErrorKind::LlmApi,
+            "invalid_arguments" => ErrorKind::InvalidArguments,
+            "timeout" => ErrorKind::Timeout,
+            "unavailable" => ErrorKind::Unavailable,
+            "cancelled" => ErrorKind::Cancelled,
+            "invalid_output" => ErrorKind::InvalidOutput,
+            _ => ErrorKind::TaskFailed,
+        }
+    }
+
+    /// Errores transitorios (red, tiempo de espera, API del LLM). Una herramienta
+    /// inexistente, unos argumentos mal formados, una cancelación o una salida que ya se
+    /// intentó reparar no mejoran reintentando.
+    pub fn is_retryable(self) -> bool {
+        matches!(
+            self,
+            ErrorKind::Nats
+                | ErrorKind::Io
+                | ErrorKind::LlmApi
+                | ErrorKind::Timeout
+                | ErrorKind::Unavailable
+        )
+    }
+}
+
+impl AgentError {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            AgentError::NatsError(_) => ErrorKind::Nats,
+            AgentError::SerializationError(_) => ErrorKind::Serialization,
+            AgentError::ProtoError(_) => ErrorKind::Proto,
+            AgentError::IoError(_) => ErrorKind::Io,
+            AgentError::ToolNotFound(_) => ErrorKind::ToolNotFound,
+            AgentError::Configuration(_) => ErrorKind::Configuration,
+            AgentError::LlmApiError(_) => ErrorKind::LlmApi,
+            AgentError::TaskFailed(_) => ErrorKind::TaskFailed,
+            AgentError::InvalidArguments(_) => ErrorKind::InvalidArguments,
+            AgentError::Timeout(_) => ErrorKind::Timeout,
+            AgentError::Unavailable(_) => ErrorKind::Unavailable,
+            AgentError::Cancelled(_) => ErrorKind::Cancelled,
+            AgentError::InvalidOutput(_) => ErrorKind::InvalidOutput,
+            AgentError::Remote { kind, .. } => *kind,
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        self.kind().is_retryable()
+    }
 }
 
 impl From<prost::EncodeError> for AgentError {
@@ -30,4 +142,4 @@ impl From<prost::DecodeError> for AgentError {
     fn from(e: prost::DecodeError) -> Self {
         AgentError::ProtoError(e.to_string())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,38 @@
+//! Análisis de carencias del CV frente a una oferta y sugerencias para adaptarlo.
+
+use serde::{Deserialize, Serialize};
+
+/// Carencias del CV para una oferta y reescrituras propuestas de sus puntos.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct GapReport {
+    pub title: String,
+    #[serde(default)]
+    pub company: String,
+    pub source_url: String,
+    #[serde(default)]
+    pub score: Option<u8>,
+    /// Habilidades y requisitos de la oferta que el CV no muestra.
+    #[serde(default)]
+    pub missing: Vec<Gap>,
+    #[serde(default)]
+    pub suggestions: Vec<BulletRewrite>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Gap {
+    /// Habilidad o requisito tal y como lo pide la oferta.
+    pub requirement: String,
+    /// Por qué falta o qué hay en el CV que se acerque.
+    #[serde(default)]
+    pub detail: String,
+}
+
+/// Reescritura de un punto del CV que destaca la experiencia que pide la oferta.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BulletRewrite {
+    /// Texto original, tal cual aparece en el CV.
+    pub original: String,
+    pub rewrite: String,
+    #[serde(default)]
+    pub reason: String,
+}
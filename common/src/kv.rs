@@ -0,0 +1,93 @@
+//! Utilidades comunes de los buckets KV de JetStream: apertura, claves y marcas de tiempo.
+//!
+//! Los agentes guardan en KV tareas, perfiles de búsqueda y sesiones. Las claves llegan
+//! de los clientes (en texto plano o en JSON) y deben cumplir el juego de caracteres de
+//! JetStream.
+
+use crate::error::AgentError;
+use async_nats::jetstream::{self, kv};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Abre el bucket `bucket`, creándolo (con historial 1 y en disco) si no existe.
+pub async fn open_bucket(
+    js: &jetstream::Context,
+    bucket: &str,
+    description: &str,
+) -> Result<kv::Store, AgentError> {
+    if let Ok(store) = js.get_key_value(bucket).await {
+        return Ok(store);
+    }
+    let store = js
+        .create_key_value(kv::Config {
+            bucket: bucket.to_string(),
+            description: description.to_string(),
+            history: 1,
+            storage: jetstream::stream::StorageType::File,
+            ..Default::default()
+        })
+        .await
+        .map_err(async_nats::Error::from)?;
+    Ok(store)
+}
+
+/// Comprueba que `key` sea una clave KV válida (`[A-Za-z0-9_=-]`, no vacía). `field` es
+/// el nombre del campo para el mensaje de error.
+pub fn validate_key(field: &str, key: &str) -> Result<(), AgentError> {
+    if key.is_empty()
+        || !key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '='))
+    {
+        return Err(AgentError::InvalidArguments(format!(
+            "{field} '{key}' no válido: usa solo letras, dígitos, '-', '_' o '='"
+        )));
+    }
+    Ok(())
+}
+
+/// Identificador de un payload en texto plano o `{"<field>": "..."}`.
+pub fn parse_id(payload: &[u8], field: &str) -> Option<String> {
+    let text = std::str::from_utf8(payload).ok()?.trim();
+    let id = match serde_json::from_str::<serde_json::Value>(text) {
+        Ok(value) => value.get(field)?.as_str()?.trim().to_string(),
+        Err(_) => text.to_string(),
+    };
+    (!id.is_empty()).then_some(id)
+}
+
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_only_accept_kv_characters() {
+        assert!(validate_key("task_id", "task-1_a=").is_ok());
+        let err = validate_key("session_id", "a.b").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Argumentos no válidos: session_id 'a.b' no válido: usa solo letras, dígitos, '-', '_' o '='"
+        );
+        assert!(validate_key("task_id", "").is_err());
+        assert!(validate_key("task_id", "tarea ñ").is_err());
+    }
+
+    #[test]
+    fn ids_come_as_text_or_json() {
+        assert_eq!(parse_id(b"  t-1 \n", "task_id"), Some("t-1".to_string()));
+        assert_eq!(
+            parse_id(br#"{"task_id": " t-2 "}"#, "task_id"),
+            Some("t-2".to_string())
+        );
+        assert_eq!(parse_id(br#"{"profile_id": "p"}"#, "task_id"), None);
+        assert_eq!(parse_id(br#"{"task_id": 3}"#, "task_id"), None);
+        assert_eq!(parse_id(b"   ", "task_id"), None);
+        assert_eq!(parse_id(&[0xff, 0xfe], "task_id"), None);
+    }
+}
@@ -1,3 +1,10 @@
+pub mod cancel;
+pub mod cv;
 pub mod error;
+pub mod gaps;
+pub mod kv;
 pub mod messaging;
-pub mod task;
\ No newline at end of file
+pub mod offers;
+pub mod prompts;
+pub mod schema;
+pub mod task;
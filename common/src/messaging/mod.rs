@@ -1,3 +1,7 @@
+pub mod request;
+pub mod stream;
+pub mod tools;
+
 pub mod messages {
     // Incluye el código generado por prost
     include!(concat!(env!("OUT_DIR"), "/agent_messages.rs"));
@@ -9,3 +13,42 @@ pub const NATS_TOOL_SUBJECT: &str = "agents.tool";
 pub const NATS_COORDINATOR_SUBJECT: &str = "agents.coordinator";
 pub const NATS_UI_STATUS_SUBJECT: &str = "agents.status";
 
+// Cancelación de tareas (payload: task_id)
+pub const NATS_COORDINATOR_CANCEL_SUBJECT: &str = "agents.coordinator.cancel";
+pub const NATS_LLM_CANCEL_SUBJECT: &str = "agents.llm.cancel";
+pub const NATS_TOOL_CANCEL_SUBJECT: &str = "agents.tool.cancel";
+
+// Consultas de tareas al Coordinador (request/reply, JSON)
+pub const NATS_TASKS_LIST_SUBJECT: &str = "tasks.list";
+pub const NATS_TASKS_GET_SUBJECT: &str = "tasks.get";
+pub const NATS_TASKS_RESULT_SUBJECT: &str = "tasks.result";
+
+// Perfiles de búsqueda programada del Coordinador (request/reply, JSON)
+pub const NATS_PROFILES_SAVE_SUBJECT: &str = "profiles.save";
+pub const NATS_PROFILES_LIST_SUBJECT: &str = "profiles.list";
+pub const NATS_PROFILES_DELETE_SUBJECT: &str = "profiles.delete";
+pub const NATS_PROFILES_RUN_SUBJECT: &str = "profiles.run";
+
+// Sesiones de conversación del Agente LLM (request/reply, JSON)
+pub const NATS_SESSIONS_LIST_SUBJECT: &str = "sessions.list";
+pub const NATS_SESSIONS_CLEAR_SUBJECT: &str = "sessions.clear";
+pub const NATS_SESSIONS_EXPORT_SUBJECT: &str = "sessions.export";
+
+// Contadores de la caché de respuestas del Agente LLM (request/reply, JSON)
+pub const NATS_LLM_CACHE_STATS_SUBJECT: &str = "agents.llm.cache.stats";
+
+/// Cabecera NATS con el `task_id` al que pertenece una petición entre agentes.
+pub const TASK_ID_HEADER: &str = "Task-Id";
+
+/// Cabecera de las respuestas de error con la clase del error (`ErrorKind`).
+pub const ERROR_KIND_HEADER: &str = "Agent-Error";
+
+/// Cabecera con el formato del payload; sin ella el payload es texto UTF-8 (o JSON).
+pub const CONTENT_TYPE_HEADER: &str = "Content-Type";
+
+/// Valor de `Content-Type` para los mensajes protobuf de `messages`.
+pub const PROTOBUF_CONTENT_TYPE: &str = "application/x-protobuf";
+
+// Subjects JetStream (cola durable de tareas del Coordinador)
+pub const NATS_COORDINATOR_TASKS_SUBJECT: &str = "agents.coordinator.tasks";
+pub const NATS_COORDINATOR_DLQ_SUBJECT: &str = "agents.coordinator.dlq";
@@ -0,0 +1,380 @@
+//! Request/reply entre agentes con timeout por llamada y reintentos.
+//!
+//! Quien pide usa [`request`]: cada intento tiene su propio timeout y los errores
+//! transitorios (sin respuesta, nadie escuchando, fallos de red o del LLM) se reintentan
+//! con backoff exponencial y jitter. Quien responde usa [`respond`], que en caso de error
+//! envía `ERROR: ...` con la clase del error en la cabecera `Agent-Error`, para que el
+//! otro lado sepa si tiene sentido reintentar. Las respuestas protobuf se envían con
+//! [`respond_proto`] y llevan el detalle del error en el propio mensaje.
+
+use crate::error::{AgentError, ErrorKind};
+use crate::messaging::messages::ErrorReply;
+use crate::messaging::{CONTENT_TYPE_HEADER, ERROR_KIND_HEADER, PROTOBUF_CONTENT_TYPE};
+use async_nats::client::{Request, RequestErrorKind};
+use async_nats::{Client, HeaderMap, Message, Subject};
+use bytes::Bytes;
+use prost::Message as _;
+use std::env;
+use std::time::Duration;
+use tracing::warn;
+
+/// Timeout, número de intentos y backoff de una petición.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Tiempo máximo de espera de cada intento.
+    pub timeout: Duration,
+    /// Intentos en total (1 = sin reintentos).
+    pub max_attempts: u32,
+    /// Espera antes del primer reintento; se duplica en cada uno.
+    pub initial_backoff: Duration,
+    /// Tope de la espera entre reintentos.
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Valores por defecto sobrescribibles con `AGENT_REQUEST_TIMEOUT_SECS`,
+    /// `AGENT_REQUEST_MAX_ATTEMPTS`, `AGENT_REQUEST_BACKOFF_MS` y
+    /// `AGENT_REQUEST_MAX_BACKOFF_MS`.
+    pub fn from_env() -> Self {
+        fn var(name: &str) -> Option<u64> {
+            env::var(name).ok().and_then(|s| s.parse().ok())
+        }
+        let default = Self::default();
+        Self {
+            timeout: var("AGENT_REQUEST_TIMEOUT_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(default.timeout),
+            max_attempts: var("AGENT_REQUEST_MAX_ATTEMPTS")
+                .map(|n| n.clamp(1, u32::MAX as u64) as u32)
+                .unwrap_or(default.max_attempts),
+            initial_backoff: var("AGENT_REQUEST_BACKOFF_MS")
+                .map(Duration::from_millis)
+                .unwrap_or(default.initial_backoff),
+            max_backoff: var("AGENT_REQUEST_MAX_BACKOFF_MS")
+                .map(Duration::from_millis)
+                .unwrap_or(default.max_backoff),
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Espera antes del reintento `attempt` (1 = primer reintento): exponencial con tope
+    /// y jitter en `[mitad, total]` para que los clientes no reintenten a la vez.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exp = attempt.saturating_sub(1).min(16);
+        let base = self
+            .initial_backoff
+            .saturating_mul(1 << exp)
+            .min(self.max_backoff);
+        base.mul_f64(0.5 + fastrand::f64() * 0.5)
+    }
+
+    /// Espera antes de repetir el intento `attempt` que falló con `error`, o `None` si
+    /// el error no es transitorio o ya no quedan intentos.
+    fn retry_delay(&self, error: &AgentError, attempt: u32) -> Option<Duration> {
+        (error.is_retryable() && attempt < self.max_attempts).then(|| self.backoff(attempt))
+    }
+}
+
+/// Envía una petición y devuelve el payload de la respuesta, reintentando los errores
+/// transitorios según `policy`. Las respuestas de error del otro agente se devuelven
+/// como `AgentError::Remote` con su clase.
+pub async fn request(
+    client: &Client,
+    subject: &str,
+    headers: HeaderMap,
+    payload: Bytes,
+    policy: &RetryPolicy,
+) -> Result<Bytes, AgentError> {
+    let mut attempt = 1;
+    loop {
+        let sent = client
+            .send_request(
+                subject.to_string(),
+                Request::new()
+                    .headers(headers.clone())
+                    .payload(payload.clone())
+                    .timeout(Some(policy.timeout)),
+            )
+            .await;
+        let result = match sent {
+            Ok(msg) => into_result(msg),
+            Err(e) => Err(match e.kind() {
+                RequestErrorKind::TimedOut => AgentError::Timeout(format!(
+                    "'{subject}' no respondió en {}s",
+                    policy.timeout.as_secs_f32()
+                )),
+                RequestErrorKind::NoResponders => {
+                    AgentError::Unavailable(format!("ningún agente escucha en '{subject}'"))
+                }
+                _ => AgentError::NatsError(e.into()),
+            }),
+        };
+
+        match result {
+            Err(e) => match policy.retry_delay(&e, attempt) {
+                Some(delay) => {
+                    warn!(
+                        "'{subject}': intento {attempt}/{} fallido ({e}); reintento en {:.1}s",
+                        policy.max_attempts,
+                        delay.as_secs_f32()
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                None => return Err(e),
+            },
+            ok => return ok,
+        }
+    }
+}
+
+/// Responde a una petición: el texto tal cual si fue bien, o `ERROR: ...` con la
+/// cabecera `Agent-Error` si falló.
+pub async fn respond(client: &Client, reply: Option<Subject>, result: Result<String, AgentError>) {
+    let Some(reply) = reply else {
+        return;
+    };
+    let published = match result {
+        Ok(text) => client.publish(reply, Bytes::from(text.into_bytes())).await,
+        Err(e) => {
+            let mut headers = HeaderMap::new();
+            headers.insert(ERROR_KIND_HEADER, e.kind().as_str());
+            client
+                .publish_with_headers(reply, headers, Bytes::from(format!("ERROR: {e}")))
+                .await
+        }
+    };
+    if let Err(e) = published {
+        warn!("No se pudo publicar la respuesta: {e}");
+    }
+}
+
+/// Responde con un mensaje protobuf y la cabecera `Content-Type`. Si `error` no es
+/// `None` se añade también `Agent-Error`, para que [`request`] lo trate como error.
+pub async fn respond_proto<M: prost::Message>(
+    client: &Client,
+    reply: Option<Subject>,
+    message: &M,
+    error: Option<ErrorKind>,
+) {
+    let Some(reply) = reply else {
+        return;
+    };
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE_HEADER, PROTOBUF_CONTENT_TYPE);
+    if let Some(kind) = error {
+        headers.insert(ERROR_KIND_HEADER, kind.as_str());
+    }
+    let payload = Bytes::from(message.encode_to_vec());
+    if let Err(e) = client.publish_with_headers(reply, headers, payload).await {
+        warn!("No se pudo publicar la respuesta: {e}");
+    }
+}
+
+/// `true` si el mensaje lleva `Content-Type: application/x-protobuf`.
+pub fn is_protobuf(headers: Option<&HeaderMap>) -> bool {
+    headers
+        .and_then(|h| h.get(CONTENT_TYPE_HEADER))
+        .is_some_and(|v| v.as_str().trim() == PROTOBUF_CONTENT_TYPE)
+}
+
+/// Convierte una respuesta en error si trae la cabecera `Agent-Error`.
+fn into_result(msg: Message) -> Result<Bytes, AgentError> {
+    match reply_error(&msg) {
+        Some(e) => Err(e),
+        None => Ok(msg.payload),
+    }
+}
+
+/// Error de una respuesta, si lo es: lleva la cabecera `Agent-Error` o, sin cabecera,
+/// es un texto que empieza por `ERROR` (agentes antiguos, cuenta como `TaskFailed`). En
+/// las respuestas protobuf el mensaje del error sale del campo `error` (`ErrorReply`).
+pub fn reply_error(msg: &Message) -> Option<AgentError> {
+    let kind = msg
+        .headers
+        .as_ref()
+        .and_then(|h| h.get(ERROR_KIND_HEADER))
+        .map(|v| ErrorKind::parse(v.as_str()));
+    let protobuf = is_protobuf(msg.headers.as_ref());
+    if kind.is_none() && (protobuf || !msg.payload.starts_with(b"ERROR")) {
+        return None;
+    }
+
+    if protobuf {
+        let error = ErrorReply::decode(msg.payload.as_ref())
+            .ok()
+            .and_then(|r| r.error)
+            .unwrap_or_default();
+        return Some(AgentError::Remote {
+            kind: kind.unwrap_or_else(|| ErrorKind::parse(&error.kind)),
+            message: error.message,
+        });
+    }
+
+    let text = String::from_utf8_lossy(&msg.payload);
+    let message = text
+        .strip_prefix("ERROR")
+        .unwrap_or(&text)
+        .trim_start_matches(':')
+        .trim()
+        .to_string();
+    Some(AgentError::Remote {
+        kind: kind.unwrap_or(ErrorKind::TaskFailed),
+        message,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            timeout: Duration::from_secs(1),
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+        }
+    }
+
+    fn reply(payload: &[u8], headers: &[(&str, &str)]) -> Message {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.insert(*name, *value);
+        }
+        Message {
+            subject: "respuesta".into(),
+            reply: None,
+            payload: Bytes::copy_from_slice(payload),
+            headers: (!headers.is_empty()).then_some(map),
+            status: None,
+            description: None,
+            length: payload.len(),
+        }
+    }
+
+    fn remote(error: Option<AgentError>) -> (ErrorKind, String) {
+        match error {
+            Some(AgentError::Remote { kind, message }) => (kind, message),
+            other => panic!("se esperaba un error remoto: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_with_jitter_and_stops_at_the_cap() {
+        let policy = policy();
+        for _ in 0..200 {
+            for (attempt, base) in [
+                (1, 100),
+                (2, 200),
+                (3, 400),
+                (4, 800),
+                (5, 1000),
+                (40, 1000),
+            ] {
+                let delay = policy.backoff(attempt);
+                let base = Duration::from_millis(base);
+                assert!(
+                    delay >= base / 2 && delay <= base,
+                    "intento {attempt}: {delay:?} fuera de [{:?}, {base:?}]",
+                    base / 2
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn only_transient_errors_are_retried() {
+        let policy = policy();
+        for error in [
+            AgentError::Timeout("x".into()),
+            AgentError::Unavailable("x".into()),
+            AgentError::Remote {
+                kind: ErrorKind::Timeout,
+                message: "x".into(),
+            },
+        ] {
+            assert!(policy.retry_delay(&error, 1).is_some(), "{error:?}");
+            assert!(policy.retry_delay(&error, 3).is_none(), "{error:?}");
+        }
+        for error in [
+            AgentError::ToolNotFound("x".into()),
+            AgentError::InvalidArguments("x".into()),
+            AgentError::Remote {
+                kind: ErrorKind::ToolNotFound,
+                message: "x".into(),
+            },
+            AgentError::Remote {
+                kind: ErrorKind::InvalidArguments,
+                message: "x".into(),
+            },
+        ] {
+            assert!(policy.retry_delay(&error, 1).is_none(), "{error:?}");
+        }
+    }
+
+    #[test]
+    fn text_replies_take_the_kind_from_the_header() {
+        let msg = reply(
+            b"ERROR: La herramienta 'foo' no fue encontrada",
+            &[(ERROR_KIND_HEADER, "tool_not_found")],
+        );
+        let (kind, message) = remote(reply_error(&msg));
+        assert_eq!(kind, ErrorKind::ToolNotFound);
+        assert_eq!(message, "La herramienta 'foo' no fue encontrada");
+
+        let (kind, _) = remote(reply_error(&reply(b"x", &[(ERROR_KIND_HEADER, "timeout")])));
+        assert_eq!(kind, ErrorKind::Timeout);
+        let (kind, _) = remote(reply_error(&reply(b"x", &[(ERROR_KIND_HEADER, "raro")])));
+        assert_eq!(kind, ErrorKind::TaskFailed);
+    }
+
+    #[test]
+    fn text_replies_without_header_fail_only_with_the_error_prefix() {
+        let (kind, message) = remote(reply_error(&reply(b"ERROR: sin disco", &[])));
+        assert_eq!(kind, ErrorKind::TaskFailed);
+        assert_eq!(message, "sin disco");
+        assert!(reply_error(&reply(b"todo bien", &[])).is_none());
+        assert!(reply_error(&reply(b"", &[])).is_none());
+    }
+
+    #[test]
+    fn protobuf_replies_read_the_error_field() {
+        let payload = ErrorReply {
+            error: Some(crate::messaging::messages::ErrorDetail {
+                kind: "unavailable".into(),
+                message: "sin proveedores".into(),
+                retryable: true,
+            }),
+        }
+        .encode_to_vec();
+        let content_type = (CONTENT_TYPE_HEADER, PROTOBUF_CONTENT_TYPE);
+
+        let (kind, message) = remote(reply_error(&reply(
+            &payload,
+            &[content_type, (ERROR_KIND_HEADER, "llm_api")],
+        )));
+        assert_eq!(kind, ErrorKind::LlmApi);
+        assert_eq!(message, "sin proveedores");
+
+        // Sin cabecera `Agent-Error` una respuesta protobuf nunca es un error, aunque su
+        // payload empiece por `ERROR`.
+        assert!(reply_error(&reply(&payload, &[content_type])).is_none());
+        assert!(reply_error(&reply(b"ERROR", &[content_type])).is_none());
+    }
+}
@@ -0,0 +1,321 @@
+//! Respuestas del LLM en streaming.
+//!
+//! Con `LlmRequest.stream`, el Agente LLM publica en el reply subject un `LlmChunk` por
+//! cada trozo que le llega del proveedor, numerados desde 0, y termina con un `LlmChunk`
+//! `done` con el consumo de tokens (o con el error). [`stream_llm`] se suscribe a un
+//! inbox propio, envía la petición y devuelve los trozos en orden como un
+//! `Stream<Item = String>`.
+
+use crate::error::{AgentError, ErrorKind};
+use crate::messaging::messages::{LlmChunk, LlmRequest, Usage};
+use crate::messaging::request::reply_error;
+use crate::messaging::{CONTENT_TYPE_HEADER, NATS_LLM_SUBJECT, PROTOBUF_CONTENT_TYPE};
+use async_nats::{Client, HeaderMap, Message, StatusCode};
+use futures::{Stream, StreamExt};
+use prost::Message as _;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::{sleep, Instant, Sleep};
+
+/// Trozos de texto de una generación en streaming, en orden. El stream termina con el
+/// último trozo o con un error; después, [`LlmStream::usage`] y [`LlmStream::error`]
+/// dicen cómo acabó.
+pub struct LlmStream {
+    /// Mensajes del inbox (la suscripción, o un stream cualquiera en los tests).
+    messages: Pin<Box<dyn Stream<Item = Message> + Send>>,
+    next_seq: u64,
+    /// Trozos llegados antes que alguno anterior, por `seq`.
+    pending: BTreeMap<u64, LlmChunk>,
+    idle_timeout: Duration,
+    deadline: Pin<Box<Sleep>>,
+    end: Option<LlmChunk>,
+    error: Option<AgentError>,
+    finished: bool,
+}
+
+/// Envía `req` en streaming a `agents.llm` con `headers` (p. ej. `Task-Id`). Si pasa
+/// `idle_timeout` sin recibir ningún trozo, el stream termina con `AgentError::Timeout`.
+pub async fn stream_llm(
+    client: &Client,
+    mut headers: HeaderMap,
+    mut req: LlmRequest,
+    idle_timeout: Duration,
+) -> Result<LlmStream, AgentError> {
+    req.stream = true;
+    let inbox = client.new_inbox();
+    let sub = client
+        .subscribe(inbox.clone())
+        .await
+        .map_err(|e| AgentError::NatsError(e.into()))?;
+    headers.insert(CONTENT_TYPE_HEADER, PROTOBUF_CONTENT_TYPE);
+    client
+        .publish_with_reply_and_headers(
+            NATS_LLM_SUBJECT,
+            inbox,
+            headers,
+            req.encode_to_vec().into(),
+        )
+        .await
+        .map_err(|e| AgentError::NatsError(e.into()))?;
+
+    Ok(LlmStream::new(sub, idle_timeout))
+}
+
+impl LlmStream {
+    fn new(messages: impl Stream<Item = Message> + Send + 'static, idle_timeout: Duration) -> Self {
+        LlmStream {
+            messages: Box::pin(messages),
+            next_seq: 0,
+            pending: BTreeMap::new(),
+            idle_timeout,
+            deadline: Box::pin(sleep(idle_timeout)),
+            end: None,
+            error: None,
+            finished: false,
+        }
+    }
+
+    /// Tokens consumidos, cuando el stream ha terminado bien.
+    pub fn usage(&self) -> Option<&Usage> {
+        self.end.as_ref().and_then(|end| end.usage.as_ref())
+    }
+
+    /// Modelo que ha generado la respuesta, cuando el stream ha terminado bien.
+    pub fn model(&self) -> Option<&str> {
+        self.end.as_ref().map(|end| end.model.as_str())
+    }
+
+    /// Motivo por el que el stream terminó antes del último trozo.
+    pub fn error(&self) -> Option<&AgentError> {
+        self.error.as_ref()
+    }
+
+    fn fail(&mut self, error: AgentError) -> Poll<Option<String>> {
+        self.error = Some(error);
+        self.finished = true;
+        Poll::Ready(None)
+    }
+}
+
+impl Stream for LlmStream {
+    type Item = String;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<String>> {
+        loop {
+            if self.finished {
+                return Poll::Ready(None);
+            }
+
+            let next_seq = self.next_seq;
+            if let Some(chunk) = self.pending.remove(&next_seq) {
+                self.next_seq += 1;
+                if let Some(error) = &chunk.error {
+                    let error = AgentError::Remote {
+                        kind: ErrorKind::parse(&error.kind),
+                        message: error.message.clone(),
+                    };
+                    return self.fail(error);
+                }
+                if chunk.done {
+                    self.end = Some(chunk);
+                    self.finished = true;
+                    return Poll::Ready(None);
+                }
+                if chunk.delta.is_empty() {
+                    continue;
+                }
+                return Poll::Ready(Some(chunk.delta));
+            }
+
+            match self.messages.poll_next_unpin(cx) {
+                Poll::Ready(Some(msg)) => {
+                    let deadline = Instant::now() + self.idle_timeout;
+                    self.deadline.as_mut().reset(deadline);
+                    if msg.status == Some(StatusCode::NO_RESPONDERS) {
+                        return self.fail(AgentError::Unavailable(format!(
+                            "ningún agente escucha en '{NATS_LLM_SUBJECT}'"
+                        )));
+                    }
+                    if let Some(error) = reply_error(&msg) {
+                        return self.fail(error);
+                    }
+                    match LlmChunk::decode(msg.payload.as_ref()) {
+                        Ok(chunk) => {
+                            self.pending.insert(chunk.seq, chunk);
+                        }
+                        Err(e) => return self.fail(e.into()),
+                    }
+                }
+                Poll::Ready(None) => {
+                    return self.fail(AgentError::Unavailable(
+                        "la suscripción al stream se ha cerrado".to_string(),
+                    ))
+                }
+                Poll::Pending => {
+                    if self.deadline.as_mut().poll(cx).is_ready() {
+                        let secs = self.idle_timeout.as_secs_f32();
+                        return self.fail(AgentError::Timeout(format!(
+                            "el stream del LLM no envió nada en {secs}s"
+                        )));
+                    }
+                    return Poll::Pending;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messaging::messages::ErrorDetail;
+    use crate::messaging::ERROR_KIND_HEADER;
+    use futures::channel::mpsc;
+    use futures::stream;
+
+    fn message(chunk: LlmChunk, headers: Option<HeaderMap>) -> Message {
+        let payload = chunk.encode_to_vec();
+        Message {
+            subject: "_INBOX.prueba".into(),
+            reply: None,
+            length: payload.len(),
+            payload: payload.into(),
+            headers,
+            status: None,
+            description: None,
+        }
+    }
+
+    fn delta(seq: u64, text: &str) -> Message {
+        message(
+            LlmChunk {
+                seq,
+                delta: text.to_string(),
+                ..Default::default()
+            },
+            None,
+        )
+    }
+
+    fn done(seq: u64) -> Message {
+        message(
+            LlmChunk {
+                seq,
+                done: true,
+                model: "llama3.1:8b".to_string(),
+                usage: Some(Usage {
+                    prompt_tokens: 10,
+                    completion_tokens: 3,
+                    total_tokens: 13,
+                }),
+                ..Default::default()
+            },
+            None,
+        )
+    }
+
+    fn llm_stream(messages: Vec<Message>) -> LlmStream {
+        // Sin más mensajes el inbox sigue abierto, como una suscripción real
+        LlmStream::new(
+            stream::iter(messages).chain(stream::pending()),
+            Duration::from_secs(5),
+        )
+    }
+
+    #[tokio::test]
+    async fn chunks_are_reassembled_by_seq() {
+        let mut llm = llm_stream(vec![
+            delta(2, "mundo"),
+            delta(0, "Hola"),
+            done(4),
+            delta(1, ", "),
+            delta(3, ""),
+        ]);
+        let mut text = Vec::new();
+        while let Some(piece) = llm.next().await {
+            text.push(piece);
+        }
+        assert_eq!(text, ["Hola", ", ", "mundo"]);
+        assert!(llm.error().is_none(), "{:?}", llm.error());
+        assert_eq!(llm.model(), Some("llama3.1:8b"));
+        assert_eq!(llm.usage().map(|u| u.total_tokens), Some(13));
+        assert_eq!(llm.next().await, None);
+    }
+
+    #[tokio::test]
+    async fn a_remote_error_in_the_last_chunk_ends_the_stream() {
+        let failed = message(
+            LlmChunk {
+                seq: 1,
+                done: true,
+                error: Some(ErrorDetail {
+                    kind: "llm_api".to_string(),
+                    message: "el proveedor devolvió 500".to_string(),
+                    retryable: true,
+                }),
+                ..Default::default()
+            },
+            None,
+        );
+        let mut llm = llm_stream(vec![failed, delta(0, "Hola")]);
+        assert_eq!(llm.next().await.as_deref(), Some("Hola"));
+        assert_eq!(llm.next().await, None);
+        match llm.error() {
+            Some(AgentError::Remote { kind, message }) => {
+                assert_eq!(*kind, ErrorKind::LlmApi);
+                assert_eq!(message, "el proveedor devolvió 500");
+            }
+            other => panic!("se esperaba un error remoto: {other:?}"),
+        }
+        assert!(llm.usage().is_none());
+    }
+
+    #[tokio::test]
+    async fn an_error_reply_ends_the_stream_with_its_kind() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE_HEADER, PROTOBUF_CONTENT_TYPE);
+        headers.insert(ERROR_KIND_HEADER, "invalid_arguments");
+        let mut llm = llm_stream(vec![message(LlmChunk::default(), Some(headers))]);
+        assert_eq!(llm.next().await, None);
+        assert!(matches!(
+            llm.error(),
+            Some(AgentError::Remote {
+                kind: ErrorKind::InvalidArguments,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn the_idle_timeout_restarts_with_every_message() {
+        let idle = Duration::from_millis(200);
+        let (tx, rx) = mpsc::unbounded();
+        let mut llm = LlmStream::new(rx, idle);
+
+        // Cada trozo llega antes del timeout, aunque entre todos lo superen
+        let sender = tokio::spawn(async move {
+            for seq in 0..3 {
+                sleep(idle / 2).await;
+                tx.unbounded_send(delta(seq, "x")).unwrap();
+            }
+            sleep(idle * 10).await;
+            drop(tx);
+        });
+        let started = Instant::now();
+        let mut pieces = 0;
+        while llm.next().await.is_some() {
+            pieces += 1;
+        }
+        assert_eq!(pieces, 3);
+        assert!(
+            matches!(llm.error(), Some(AgentError::Timeout(_))),
+            "{:?}",
+            llm.error()
+        );
+        assert!(started.elapsed() < idle * 5, "{:?}", started.elapsed());
+        sender.abort();
+    }
+}
@@ -0,0 +1,62 @@
+//! Herramientas del Agente de Herramientas ofrecidas al LLM.
+//!
+//! Con `LlmRequest.tools`, el Agente LLM deja que el modelo llame a comandos de
+//! `agents.tool`. [`tool_definitions`] construye esas definiciones a partir del catálogo
+//! de `list_tools` (nombre, descripción y JSON Schema de los argumentos de cada `Tool`).
+
+use crate::error::AgentError;
+use crate::messaging::messages::ToolDefinition;
+use crate::messaging::request::{request, RetryPolicy};
+use crate::messaging::NATS_TOOL_SUBJECT;
+use async_nats::{Client, HeaderMap};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Catalog {
+    tools: Vec<CatalogTool>,
+}
+
+#[derive(Deserialize)]
+struct CatalogTool {
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    parameters: serde_json::Value,
+}
+
+/// Definiciones de las herramientas `names` (todas si está vacío) según `list_tools`.
+/// Falla con `ToolNotFound` si alguna no está en el catálogo (p. ej. el agente se
+/// arrancó sin `toolkit`).
+pub async fn tool_definitions(
+    client: &Client,
+    headers: HeaderMap,
+    names: &[&str],
+) -> Result<Vec<ToolDefinition>, AgentError> {
+    let reply = request(
+        client,
+        NATS_TOOL_SUBJECT,
+        headers,
+        r#"{"cmd":"list_tools"}"#.into(),
+        &RetryPolicy::from_env(),
+    )
+    .await?;
+    let catalog: Catalog = serde_json::from_slice(&reply)?;
+
+    if let Some(missing) = names
+        .iter()
+        .find(|name| !catalog.tools.iter().any(|t| t.name == **name))
+    {
+        return Err(AgentError::ToolNotFound(missing.to_string()));
+    }
+    Ok(catalog
+        .tools
+        .into_iter()
+        .filter(|t| names.is_empty() || names.contains(&t.name.as_str()))
+        .map(|t| ToolDefinition {
+            name: t.name,
+            description: t.description,
+            parameters_json: t.parameters.to_string(),
+        })
+        .collect())
+}
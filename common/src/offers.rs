@@ -0,0 +1,397 @@
+use crate::cv::{RemotePreference, Seniority};
+use serde::{Deserialize, Serialize};
+use text::ContractType;
+
+pub mod text;
+
+/// Oferta de trabajo tal y como la escribe `excel_writer`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct JobOffer {
+    pub title: String,
+    pub company: String,
+    pub location: String,
+    pub contact: String,
+    pub source_url: String,
+    /// URLs de todas las publicaciones fusionadas en esta oferta (deduplicación).
+    #[serde(default)]
+    pub sources: Vec<String>,
+    /// Encaje con el perfil del candidato, si se ha podido calcular.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<MatchScore>,
+    /// Carta de presentación generada para la oferta, si está en la preselección.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cover_letter: Option<CoverLetter>,
+    /// Estado respecto a la ejecución anterior de la misma búsqueda programada.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<OfferStatus>,
+    /// Qué ha cambiado desde la ejecución anterior (`status` = `changed`).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<String>,
+}
+
+/// Estado de una oferta al repetir una búsqueda.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OfferStatus {
+    /// No estaba en la ejecución anterior.
+    New,
+    /// Sigue publicada sin cambios.
+    Open,
+    /// Sigue publicada con otro título, salario o ubicación.
+    Changed,
+    /// Estaba en la ejecución anterior y ya no aparece.
+    Gone,
+}
+
+impl OfferStatus {
+    /// Texto de la columna "Estado" del Excel.
+    pub fn label(&self) -> &'static str {
+        match self {
+            OfferStatus::New => "Nueva",
+            OfferStatus::Open => "Abierta",
+            OfferStatus::Changed => "Modificada",
+            OfferStatus::Gone => "Desaparecida",
+        }
+    }
+}
+
+/// Rutas de la carta de presentación de una oferta.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct CoverLetter {
+    /// Versión Markdown, con notas que remiten cada párrafo a los fragmentos del CV.
+    pub markdown: String,
+    /// Versión DOCX lista para enviar.
+    pub docx: String,
+}
+
+/// Puntuación 0–100 de una oferta frente al `CvProfile` del candidato, desglosada por
+/// partes para poder explicarla.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct MatchScore {
+    pub score: u8,
+    /// Explicación breve ("por qué") de la puntuación.
+    pub summary: String,
+    /// Habilidades del candidato que menciona la oferta.
+    #[serde(default)]
+    pub skills_matched: Vec<String>,
+    /// Habilidades que pide la oferta y no aparecen en el perfil.
+    #[serde(default)]
+    pub skills_missing: Vec<String>,
+    #[serde(default)]
+    pub skills: MatchPart,
+    #[serde(default)]
+    pub seniority: MatchPart,
+    #[serde(default)]
+    pub location: MatchPart,
+    #[serde(default)]
+    pub language: MatchPart,
+}
+
+/// Una parte de la puntuación: puntos obtenidos sobre el máximo y el motivo.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct MatchPart {
+    pub points: u8,
+    pub max: u8,
+    pub reason: String,
+}
+
+/// Ordena las ofertas de mayor a menor puntuación; las que no tienen puntuación van al
+/// final y, a igualdad, se mantiene el orden original.
+pub fn sort_by_score(offers: &mut [JobOffer]) {
+    offers.sort_by_key(|o| std::cmp::Reverse(o.score.as_ref().map(|s| s.score)));
+}
+
+/// Filtros duros sobre las ofertas encontradas: la oferta que no los cumple se descarta
+/// antes de puntuarla. Las palabras clave se buscan como palabras completas, sin
+/// distinguir mayúsculas ni tildes, en el título, la empresa y la ubicación; el resto de
+/// criterios se deducen de ese mismo texto con `text`. Si la oferta no indica un dato
+/// (p. ej. el salario), ese criterio no la descarta salvo con `exclude_unknown`.
+///
+/// No hay filtro por radio: las ofertas no traen coordenadas y no se geocodifican. Los
+/// campos desconocidos (p. ej. `radius_km`) se rechazan en vez de ignorarse.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct OfferFilters {
+    /// Si no está vacía, la oferta debe contener al menos una de estas palabras.
+    #[serde(default)]
+    pub include_keywords: Vec<String>,
+    /// Se descarta la oferta si contiene alguna de estas palabras.
+    #[serde(default)]
+    pub exclude_keywords: Vec<String>,
+    /// Si no está vacía, la oferta debe estar en alguna de estas ciudades o ser remota.
+    #[serde(default)]
+    pub cities: Vec<String>,
+    /// Si no está vacía, modalidades aceptadas (`remote`, `hybrid`, `onsite`).
+    #[serde(default)]
+    pub work_modes: Vec<RemotePreference>,
+    /// Salario anual mínimo, en la moneda de la oferta (ver `text::annual_salary`).
+    #[serde(default)]
+    pub min_salary: Option<u32>,
+    /// Si no está vacía, tipos de contrato aceptados.
+    #[serde(default)]
+    pub contract_types: Vec<ContractType>,
+    /// Niveles que se descartan (p. ej. `junior` y `lead` para un perfil mid/senior).
+    #[serde(default)]
+    pub exclude_seniorities: Vec<Seniority>,
+    /// Si no está vacía, solo se aceptan ofertas de estas empresas.
+    #[serde(default)]
+    pub allowed_companies: Vec<String>,
+    /// Se descartan las ofertas de estas empresas.
+    #[serde(default)]
+    pub denied_companies: Vec<String>,
+    /// Descarta también las ofertas que no indican un dato por el que se filtra.
+    #[serde(default)]
+    pub exclude_unknown: bool,
+}
+
+impl OfferFilters {
+    pub fn is_empty(&self) -> bool {
+        *self == OfferFilters::default()
+    }
+
+    /// `Err` con el motivo si la oferta queda excluida.
+    pub fn check(&self, offer: &JobOffer) -> Result<(), String> {
+        let normalized = text::offer_text(offer);
+        let names = |list: &[String]| -> Vec<String> {
+            list.iter()
+                .map(|n| text::normalize(n))
+                .filter(|n| !n.is_empty())
+                .collect()
+        };
+
+        if let Some(word) = names(&self.exclude_keywords)
+            .into_iter()
+            .find(|k| text::mentions(&normalized, k))
+        {
+            return Err(format!("contiene la palabra excluida '{word}'"));
+        }
+        let include = names(&self.include_keywords);
+        if !include.is_empty() && !include.iter().any(|k| text::mentions(&normalized, k)) {
+            return Err("no contiene ninguna de las palabras requeridas".to_string());
+        }
+        let unknown = |what: &str| -> Result<(), String> {
+            if self.exclude_unknown {
+                Err(format!("no indica {what}"))
+            } else {
+                Ok(())
+            }
+        };
+
+        if let Some(company) = names(&self.denied_companies)
+            .into_iter()
+            .find(|c| text::mentions(&normalized, c))
+        {
+            return Err(format!("empresa excluida '{company}'"));
+        }
+        let allowed = names(&self.allowed_companies);
+        if !allowed.is_empty() && !allowed.iter().any(|c| text::mentions(&normalized, c)) {
+            if offer.company.trim().is_empty() {
+                unknown("la empresa")?;
+            } else {
+                return Err(format!("empresa '{}' no permitida", offer.company.trim()));
+            }
+        }
+
+        let mode = text::work_mode(&normalized);
+        if !self.work_modes.is_empty() && !self.work_modes.contains(&RemotePreference::Any) {
+            match mode {
+                Some(mode) if !self.work_modes.contains(&mode) => {
+                    return Err(format!("modalidad {} no aceptada", mode_name(mode)));
+                }
+                Some(_) => {}
+                None => unknown("la modalidad")?,
+            }
+        }
+
+        let cities = names(&self.cities);
+        let remote = mode == Some(RemotePreference::Remote);
+        if !cities.is_empty() && !remote && !cities.iter().any(|c| text::mentions(&normalized, c)) {
+            if offer.location.trim().is_empty() {
+                unknown("la ubicación")?;
+            } else {
+                return Err(format!(
+                    "ubicación '{}' fuera de las ciudades aceptadas",
+                    offer.location.trim()
+                ));
+            }
+        }
+
+        if let Some(min) = self.min_salary {
+            match text::annual_salary(offer) {
+                Some(salary) if salary < min => {
+                    return Err(format!("salario {salary} por debajo de {min}"));
+                }
+                Some(_) => {}
+                None => unknown("el salario")?,
+            }
+        }
+
+        if !self.contract_types.is_empty() {
+            match text::contract_type(&normalized) {
+                Some(contract) if !self.contract_types.contains(&contract) => {
+                    return Err(format!("contrato '{}' no aceptado", contract.as_str()));
+                }
+                Some(_) => {}
+                None => unknown("el tipo de contrato")?,
+            }
+        }
+
+        if !self.exclude_seniorities.is_empty() {
+            match text::seniority(&normalized) {
+                Some(level) if self.exclude_seniorities.contains(&level) => {
+                    return Err(format!("nivel '{}' excluido", level.as_str()));
+                }
+                Some(_) => {}
+                None => unknown("el nivel")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn mode_name(mode: RemotePreference) -> &'static str {
+    match mode {
+        RemotePreference::Any => "cualquiera",
+        RemotePreference::Remote => "remota",
+        RemotePreference::Hybrid => "híbrida",
+        RemotePreference::Onsite => "presencial",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offer(title: &str, company: &str, location: &str) -> JobOffer {
+        JobOffer {
+            title: title.to_string(),
+            company: company.to_string(),
+            location: location.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn words(list: &[&str]) -> Vec<String> {
+        list.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn empty_filters_accept_everything() {
+        assert!(OfferFilters::default().is_empty());
+        assert!(OfferFilters::default()
+            .check(&offer("Cualquier cosa", "", ""))
+            .is_ok());
+    }
+
+    #[test]
+    fn keywords_match_whole_words() {
+        let filters = OfferFilters {
+            include_keywords: words(&["Java"]),
+            exclude_keywords: words(&["prácticas"]),
+            ..Default::default()
+        };
+        assert!(filters
+            .check(&offer("Java Developer", "Acme", "Madrid"))
+            .is_ok());
+        assert_eq!(
+            filters.check(&offer("JavaScript Developer", "Acme", "Madrid")),
+            Err("no contiene ninguna de las palabras requeridas".to_string())
+        );
+        assert_eq!(
+            filters.check(&offer("Java Developer (Practicas)", "Acme", "Madrid")),
+            Err("contiene la palabra excluida 'practicas'".to_string())
+        );
+    }
+
+    #[test]
+    fn companies_are_allowed_or_denied() {
+        let denied = OfferFilters {
+            denied_companies: words(&["Acme"]),
+            ..Default::default()
+        };
+        assert!(denied.check(&offer("Rust", "ACME", "Madrid")).is_err());
+        assert!(denied.check(&offer("Rust", "Globex", "Madrid")).is_ok());
+
+        let allowed = OfferFilters {
+            allowed_companies: words(&["Globex"]),
+            ..Default::default()
+        };
+        assert_eq!(
+            allowed.check(&offer("Rust", "Acme", "Madrid")),
+            Err("empresa 'Acme' no permitida".to_string())
+        );
+        assert!(allowed.check(&offer("Rust", "", "Madrid")).is_ok());
+    }
+
+    #[test]
+    fn cities_accept_remote_offers() {
+        let filters = OfferFilters {
+            cities: words(&["Madrid", "Valencia"]),
+            ..Default::default()
+        };
+        assert!(filters.check(&offer("Rust", "Acme", "Valencia")).is_ok());
+        assert!(filters
+            .check(&offer("Rust remoto", "Acme", "Sevilla"))
+            .is_ok());
+        assert!(filters.check(&offer("Rust", "Acme", "Sevilla")).is_err());
+        assert!(filters.check(&offer("Rust", "Acme", "")).is_ok());
+    }
+
+    #[test]
+    fn work_modes_contracts_and_seniority() {
+        let filters = OfferFilters {
+            work_modes: vec![RemotePreference::Remote, RemotePreference::Hybrid],
+            contract_types: vec![ContractType::Permanent],
+            exclude_seniorities: vec![Seniority::Junior],
+            ..Default::default()
+        };
+        assert!(filters
+            .check(&offer("Rust híbrido indefinido", "Acme", ""))
+            .is_ok());
+        assert_eq!(
+            filters.check(&offer("Rust presencial", "Acme", "")),
+            Err("modalidad presencial no aceptada".to_string())
+        );
+        assert_eq!(
+            filters.check(&offer("Rust remoto freelance", "Acme", "")),
+            Err("contrato 'freelance' no aceptado".to_string())
+        );
+        assert_eq!(
+            filters.check(&offer("Junior Rust remoto", "Acme", "")),
+            Err("nivel 'junior' excluido".to_string())
+        );
+    }
+
+    #[test]
+    fn min_salary_and_unknown_data() {
+        let filters = OfferFilters {
+            min_salary: Some(35_000),
+            ..Default::default()
+        };
+        assert!(filters.check(&offer("Rust 40k", "Acme", "")).is_ok());
+        assert_eq!(
+            filters.check(&offer("Rust 2.000 €/mes", "Acme", "")),
+            Err("salario 24000 por debajo de 35000".to_string())
+        );
+        assert!(filters
+            .check(&offer("Java 8 Kubernetes", "Acme", ""))
+            .is_ok());
+
+        let strict = OfferFilters {
+            exclude_unknown: true,
+            ..filters
+        };
+        assert_eq!(
+            strict.check(&offer("Java 8 Kubernetes", "Acme", "")),
+            Err("no indica el salario".to_string())
+        );
+    }
+
+    #[test]
+    fn unknown_fields_such_as_radius_are_rejected() {
+        let err =
+            serde_json::from_str::<OfferFilters>(r#"{"cities": ["Madrid"], "radius_km": 30}"#)
+                .unwrap_err();
+        assert!(err.to_string().contains("radius_km"));
+    }
+}
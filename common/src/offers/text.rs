@@ -0,0 +1,294 @@
+//! Lectura del texto de una oferta (título, empresa y ubicación): nivel, modalidad, tipo
+//! de contrato y salario. Las ofertas de las búsquedas no traen estos datos por separado,
+//! así que se deducen de palabras clave; `None` significa que la oferta no los indica.
+
+use super::JobOffer;
+use crate::cv::{RemotePreference, Seniority};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ContractType {
+    Permanent,
+    Temporary,
+    Freelance,
+    Internship,
+}
+
+impl ContractType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContractType::Permanent => "permanent",
+            ContractType::Temporary => "temporary",
+            ContractType::Freelance => "freelance",
+            ContractType::Internship => "internship",
+        }
+    }
+}
+
+/// Palabras que indican el nivel de una oferta, en orden de prioridad ("semi senior"
+/// antes que "senior").
+const SENIORITY_WORDS: &[(Seniority, &[&str])] = &[
+    (
+        Seniority::Mid,
+        &["semi senior", "semisenior", "ssr", "mid", "middle"],
+    ),
+    (
+        Seniority::Lead,
+        &["lead", "lider", "principal", "staff", "head", "jefe"],
+    ),
+    (Seniority::Senior, &["senior", "sr"]),
+    (
+        Seniority::Junior,
+        &[
+            "junior",
+            "jr",
+            "trainee",
+            "becario",
+            "practicas",
+            "intern",
+            "internship",
+            "graduate",
+        ],
+    ),
+];
+
+/// Palabras de cada modalidad, en orden de prioridad (una oferta "remoto o híbrido" es
+/// remota).
+const WORK_MODE_WORDS: &[(RemotePreference, &[&str])] = &[
+    (
+        RemotePreference::Remote,
+        &["remoto", "remote", "teletrabajo", "work from home"],
+    ),
+    (RemotePreference::Hybrid, &["hibrido", "hybrid"]),
+    (
+        RemotePreference::Onsite,
+        &["presencial", "onsite", "on site", "in office"],
+    ),
+];
+
+const CONTRACT_WORDS: &[(ContractType, &[&str])] = &[
+    (
+        ContractType::Internship,
+        &[
+            "practicas",
+            "becario",
+            "beca",
+            "internship",
+            "intern",
+            "trainee",
+        ],
+    ),
+    (
+        ContractType::Freelance,
+        &["freelance", "autonomo", "contractor", "por proyecto"],
+    ),
+    (
+        ContractType::Temporary,
+        &[
+            "temporal",
+            "temporary",
+            "sustitucion",
+            "obra y servicio",
+            "fixed term",
+        ],
+    ),
+    (
+        ContractType::Permanent,
+        &["indefinido", "permanent", "fijo", "estable"],
+    ),
+];
+
+/// Texto normalizado de la oferta: título, empresa y ubicación.
+pub fn offer_text(offer: &JobOffer) -> String {
+    normalize(&format!(
+        "{} {} {}",
+        offer.title, offer.company, offer.location
+    ))
+}
+
+/// Nivel que pide la oferta, a partir de su texto normalizado.
+pub fn seniority(text: &str) -> Option<Seniority> {
+    find_first(SENIORITY_WORDS, text)
+}
+
+/// Modalidad de la oferta, a partir de su texto normalizado.
+pub fn work_mode(text: &str) -> Option<RemotePreference> {
+    find_first(WORK_MODE_WORDS, text)
+}
+
+/// Tipo de contrato de la oferta, a partir de su texto normalizado.
+pub fn contract_type(text: &str) -> Option<ContractType> {
+    find_first(CONTRACT_WORDS, text)
+}
+
+/// Salario anual más alto que indica la oferta, en su moneda. Solo se tienen en cuenta
+/// cantidades con moneda o `k` suelta ("35.000 €", "40k", "$90,000"); las mensuales se
+/// multiplican por 12 y las por hora por 1.760.
+pub fn annual_salary(offer: &JobOffer) -> Option<u32> {
+    let raw = format!("{} {} {}", offer.title, offer.company, offer.location).to_lowercase();
+    let chars: Vec<char> = raw.chars().collect();
+    let mut best: Option<u32> = None;
+    let mut i = 0;
+    while i < chars.len() {
+        if !chars[i].is_ascii_digit() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < chars.len() && (chars[i].is_ascii_digit() || matches!(chars[i], '.' | ',')) {
+            i += 1;
+        }
+        let number: String = chars[start..i].iter().collect();
+        let number = number.trim_end_matches(['.', ',']);
+        let before: String = chars[start.saturating_sub(2)..start].iter().collect();
+        let after: String = chars[i..(i + 12).min(chars.len())].iter().collect();
+        let after = after.trim_start();
+
+        // `k` solo cuenta como palabra propia ("40k", "40 k€", "40k/año"), no como
+        // inicial de la siguiente ("Java 8 Kubernetes")
+        let thousands = after
+            .strip_prefix('k')
+            .is_some_and(|rest| !rest.starts_with(char::is_alphabetic));
+        let unit = if thousands { &after[1..] } else { after };
+        let currency = ["€", "$", "£"].iter().any(|c| before.contains(c))
+            || ["€", "eur", "$", "usd", "£", "gbp"]
+                .iter()
+                .any(|c| unit.trim_start().starts_with(c));
+        if !(thousands || currency) {
+            continue;
+        }
+        let Some(mut value) = parse_amount(number) else {
+            continue;
+        };
+        if thousands {
+            value *= 1000.0;
+        }
+        // El periodo se busca como palabra completa en las palabras que siguen a la
+        // cantidad, para que "horario" o "mesa" no cuenten como "hora" o "mes"
+        let rest: String = chars[i..].iter().collect();
+        let unit_words = normalize(&rest)
+            .split(' ')
+            .take(3)
+            .collect::<Vec<_>>()
+            .join(" ");
+        if ["mes", "mensual", "month"]
+            .iter()
+            .any(|p| mentions(&unit_words, p))
+        {
+            value *= 12.0;
+        } else if ["h", "hora", "hour"]
+            .iter()
+            .any(|p| mentions(&unit_words, p))
+        {
+            value *= 1760.0;
+        }
+        if (1_000.0..=10_000_000.0).contains(&value) {
+            let value = value as u32;
+            best = Some(best.map_or(value, |b| b.max(value)));
+        }
+    }
+    best
+}
+
+/// Cantidad con separadores de miles o decimales: "30.000" y "30,000" son 30000; "2,5"
+/// y "2.5" son 2,5.
+fn parse_amount(number: &str) -> Option<f64> {
+    let groups: Vec<&str> = number.split(['.', ',']).collect();
+    let thousands = groups.len() > 1 && groups[1..].iter().all(|g| g.len() == 3);
+    if thousands {
+        groups.concat().parse().ok()
+    } else {
+        number.replace(',', ".").parse().ok()
+    }
+}
+
+fn find_first<T: Copy>(table: &[(T, &[&str])], text: &str) -> Option<T> {
+    table
+        .iter()
+        .find(|(_, words)| words.iter().any(|w| mentions(text, w)))
+        .map(|(value, _)| *value)
+}
+
+/// `term` aparece en `text` como palabra o secuencia de palabras completas (ambos
+/// normalizados).
+pub fn mentions(text: &str, term: &str) -> bool {
+    !term.is_empty() && format!(" {text} ").contains(&format!(" {term} "))
+}
+
+/// Minúsculas, sin tildes y sin signos salvo `+` y `#` (para `c++`, `c#`), con espacios
+/// simples.
+pub fn normalize(text: &str) -> String {
+    let folded: String = text
+        .to_lowercase()
+        .chars()
+        .map(|c| match c {
+            'á' | 'à' | 'ä' | 'â' => 'a',
+            'é' | 'è' | 'ë' | 'ê' => 'e',
+            'í' | 'ì' | 'ï' | 'î' => 'i',
+            'ó' | 'ò' | 'ö' | 'ô' => 'o',
+            'ú' | 'ù' | 'ü' | 'û' => 'u',
+            'ñ' => 'n',
+            'ç' => 'c',
+            '+' | '#' => c,
+            c if c.is_alphanumeric() => c,
+            _ => ' ',
+        })
+        .collect();
+    folded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn salary(title: &str) -> Option<u32> {
+        annual_salary(&JobOffer {
+            title: title.to_string(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn annual_salary_reads_currency_amounts() {
+        assert_eq!(salary("Backend 35.000 €"), Some(35_000));
+        assert_eq!(salary("Backend $90,000"), Some(90_000));
+        assert_eq!(salary("Backend 30.000-38.000 EUR"), Some(38_000));
+        assert_eq!(salary("Backend 2.000 €/mes"), Some(24_000));
+        assert_eq!(salary("Freelance 50 € / hora"), Some(88_000));
+        assert_eq!(salary("Freelance 40 €/h"), Some(70_400));
+        assert_eq!(salary("Backend 1.800 € al mes"), Some(21_600));
+    }
+
+    #[test]
+    fn annual_salary_needs_the_whole_period_word() {
+        assert_eq!(salary("Backend 30.000 € horario flexible"), Some(30_000));
+        assert_eq!(salary("Backend 30.000 € mesa de trabajo"), Some(30_000));
+        assert_eq!(salary("Backend 30.000 € hourly bonus"), Some(30_000));
+    }
+
+    #[test]
+    fn annual_salary_reads_k_as_its_own_token() {
+        assert_eq!(salary("Backend 40k"), Some(40_000));
+        assert_eq!(salary("Backend 40 k"), Some(40_000));
+        assert_eq!(salary("Backend 45k€"), Some(45_000));
+        assert_eq!(salary("Backend 40k/año"), Some(40_000));
+        assert_eq!(salary("Backend 2,5k €/mes"), Some(30_000));
+    }
+
+    #[test]
+    fn annual_salary_ignores_numbers_without_unit() {
+        assert_eq!(salary("Java 8 Kubernetes"), None);
+        assert_eq!(salary("Java 17 kotlin 2024"), None);
+        assert_eq!(salary("Equipo de 5 personas"), None);
+        assert_eq!(salary("Bono de 5 €"), None);
+    }
+
+    #[test]
+    fn mentions_matches_whole_words() {
+        assert!(mentions("senior java developer", "java"));
+        assert!(mentions("senior java developer", "java developer"));
+        assert!(!mentions("senior javascript developer", "java"));
+        assert!(!mentions("senior java developer", ""));
+    }
+}
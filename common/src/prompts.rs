@@ -0,0 +1,671 @@
+//! Plantillas de prompts versionadas.
+//!
+//! Cada plantilla es un fichero `PROMPTS_DIR/<nombre>/<versión>.prompt` (por defecto
+//! `prompts/`) con una cabecera que declara sus variables y, tras una línea `---`, el
+//! texto:
+//!
+//! ```text
+//! @description Extracción del perfil del CV
+//! @var cv_text text
+//! @var errors list?
+//! ---
+//! Extrae el perfil de este CV:
+//! {{cv_text}}
+//! {{#errors}}
+//! Errores:
+//! {{errors}}
+//! {{/errors}}
+//! ```
+//!
+//! Tipos: `text`, `number`, `list` (una línea `- ...` por elemento) y `json` (JSON con
+//! sangría); con `?` la variable es opcional. `{{#var}}...{{/var}}` solo se incluye si
+//! `var` tiene valor y `{{^var}}...{{/var}}` solo si no lo tiene.
+//!
+//! `PROMPTS_DIR/prompts.json` elige la versión de cada plantilla: `active`, o un reparto
+//! A/B en `variants` (`{"v1": 90, "v2": 10}`) que asigna la versión según la clave de
+//! cada llamada (el `task_id`), de modo que una tarea usa siempre la misma.
+//! `prompts.json` se vuelve a leer cuando cambia su fecha de modificación y las
+//! plantillas en cada llamada: cambiar de versión o volver atrás no requiere recompilar
+//! ni reiniciar. Sin el fichero de una versión se usa la copia compilada de `prompts/`,
+//! si es la misma versión.
+
+use crate::error::AgentError;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+/// Plantillas incluidas en el binario: nombre, versión y texto. La primera de cada
+/// nombre es la versión por defecto.
+const BUILTIN: &[(&str, &str, &str)] = &[
+    (
+        "system",
+        "v1",
+        include_str!("../../prompts/system/v1.prompt"),
+    ),
+    (
+        "session_summary",
+        "v1",
+        include_str!("../../prompts/session_summary/v1.prompt"),
+    ),
+    (
+        "cv_profile",
+        "v1",
+        include_str!("../../prompts/cv_profile/v1.prompt"),
+    ),
+    (
+        "cv_analysis",
+        "v1",
+        include_str!("../../prompts/cv_analysis/v1.prompt"),
+    ),
+    (
+        "query_gen",
+        "v1",
+        include_str!("../../prompts/query_gen/v1.prompt"),
+    ),
+    (
+        "cover_letter",
+        "v2",
+        include_str!("../../prompts/cover_letter/v2.prompt"),
+    ),
+    (
+        "cover_letter",
+        "v1",
+        include_str!("../../prompts/cover_letter/v1.prompt"),
+    ),
+    (
+        "gap_analysis",
+        "v1",
+        include_str!("../../prompts/gap_analysis/v1.prompt"),
+    ),
+    ("plan", "v1", include_str!("../../prompts/plan/v1.prompt")),
+];
+
+/// Texto de una plantilla ya rellenada y su versión (`nombre@versión`), que se envía al
+/// Agente LLM en `LlmRequest.prompt_version`.
+#[derive(Debug, Clone, Default)]
+pub struct RenderedPrompt {
+    pub text: String,
+    /// Vacío si el prompt no sale del registro.
+    pub version: String,
+}
+
+impl From<String> for RenderedPrompt {
+    fn from(text: String) -> Self {
+        RenderedPrompt {
+            text,
+            version: String::new(),
+        }
+    }
+}
+
+/// Rellena la plantilla `name` con `vars` (un objeto JSON) usando el registro de
+/// `PROMPTS_DIR`. `key` decide la variante en los repartos A/B.
+pub fn render(name: &str, key: &str, vars: &Value) -> Result<RenderedPrompt, AgentError> {
+    static REGISTRY: OnceLock<PromptRegistry> = OnceLock::new();
+    REGISTRY
+        .get_or_init(PromptRegistry::from_env)
+        .render(name, key, vars)
+}
+
+/// Plantillas de un directorio.
+pub struct PromptRegistry {
+    dir: PathBuf,
+    /// `prompts.json` ya leído y su fecha de modificación (`None` si no existe).
+    manifest: Mutex<Option<(Option<SystemTime>, Arc<Manifest>)>>,
+}
+
+type Manifest = HashMap<String, Selection>;
+
+#[derive(Deserialize, Default, Clone)]
+struct Selection {
+    #[serde(default)]
+    active: Option<String>,
+    #[serde(default)]
+    variants: HashMap<String, u32>,
+}
+
+impl PromptRegistry {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        PromptRegistry {
+            dir: dir.into(),
+            manifest: Mutex::new(None),
+        }
+    }
+
+    /// Registro de `PROMPTS_DIR` (por defecto `prompts`).
+    pub fn from_env() -> Self {
+        Self::new(env::var("PROMPTS_DIR").unwrap_or_else(|_| "prompts".to_string()))
+    }
+
+    pub fn render(
+        &self,
+        name: &str,
+        key: &str,
+        vars: &Value,
+    ) -> Result<RenderedPrompt, AgentError> {
+        let version = self.version(name, key)?;
+        let template = self.template(name, &version)?;
+        Ok(RenderedPrompt {
+            text: template.render(vars)?,
+            version: format!("{name}@{version}"),
+        })
+    }
+
+    /// Contenido de `prompts.json`, que solo se vuelve a leer si ha cambiado.
+    fn manifest(&self) -> Result<Arc<Manifest>, AgentError> {
+        let path = self.dir.join("prompts.json");
+        let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+        let mut cached = self.manifest.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((at, manifest)) = cached.as_ref() {
+            if *at == modified {
+                return Ok(manifest.clone());
+            }
+        }
+        let manifest: Manifest = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| {
+                AgentError::Configuration(format!("{} no es válido: {e}", path.display()))
+            })?,
+            Err(_) => HashMap::new(),
+        };
+        let manifest = Arc::new(manifest);
+        *cached = Some((modified, manifest.clone()));
+        Ok(manifest)
+    }
+
+    /// Versión de `name` según `prompts.json`; sin entrada, la versión compilada.
+    fn version(&self, name: &str, key: &str) -> Result<String, AgentError> {
+        let selection = self.manifest()?.get(name).cloned().unwrap_or_default();
+
+        let mut variants: Vec<(String, u32)> = selection
+            .variants
+            .into_iter()
+            .filter(|(_, weight)| *weight > 0)
+            .collect();
+        if !variants.is_empty() {
+            variants.sort();
+            let total: u64 = variants.iter().map(|(_, w)| *w as u64).sum();
+            let mut point = fnv1a(&format!("{name}:{key}")) % total;
+            for (version, weight) in variants {
+                if point < weight as u64 {
+                    return Ok(version);
+                }
+                point -= weight as u64;
+            }
+        }
+        if let Some(active) = selection.active {
+            return Ok(active);
+        }
+        BUILTIN
+            .iter()
+            .find(|(n, _, _)| *n == name)
+            .map(|(_, version, _)| version.to_string())
+            .ok_or_else(|| AgentError::Configuration(format!("plantilla '{name}' desconocida")))
+    }
+
+    fn template(&self, name: &str, version: &str) -> Result<Template, AgentError> {
+        let path = self.dir.join(name).join(format!("{version}.prompt"));
+        let source = match std::fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(_) => BUILTIN
+                .iter()
+                .find(|(n, v, _)| *n == name && *v == version)
+                .map(|(_, _, source)| source.to_string())
+                .ok_or_else(|| {
+                    AgentError::Configuration(format!(
+                        "no existe la plantilla {name}@{version} ({})",
+                        path.display()
+                    ))
+                })?,
+        };
+        Template::parse(&source)
+            .map_err(|e| AgentError::Configuration(format!("plantilla {name}@{version}: {e}")))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VarKind {
+    Text,
+    Number,
+    List,
+    Json,
+}
+
+impl VarKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            VarKind::Text => "text",
+            VarKind::Number => "number",
+            VarKind::List => "list",
+            VarKind::Json => "json",
+        }
+    }
+}
+
+struct VarSpec {
+    name: String,
+    kind: VarKind,
+    optional: bool,
+}
+
+enum Node {
+    Text(String),
+    Var(String),
+    Section {
+        name: String,
+        inverted: bool,
+        children: Vec<Node>,
+    },
+}
+
+struct Template {
+    vars: Vec<VarSpec>,
+    body: Vec<Node>,
+}
+
+impl Template {
+    fn parse(source: &str) -> Result<Self, String> {
+        let (header, body) = match source.strip_prefix("---\n") {
+            Some(body) => ("", body),
+            None => source
+                .split_once("\n---\n")
+                .ok_or("falta la línea '---' entre la cabecera y el texto")?,
+        };
+        // El salto de línea final del fichero no forma parte del texto
+        let body = body.strip_suffix('\n').unwrap_or(body);
+
+        let mut vars = Vec::new();
+        for line in header.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') || line.starts_with("@description") {
+                continue;
+            }
+            let spec = line
+                .strip_prefix("@var ")
+                .ok_or_else(|| format!("línea de cabecera no válida: '{line}'"))?;
+            let (name, kind) = spec
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| format!("falta el tipo en '@var {spec}'"))?;
+            let kind = kind.trim();
+            let (kind, optional) = match kind.strip_suffix('?') {
+                Some(kind) => (kind, true),
+                None => (kind, false),
+            };
+            let kind = match kind {
+                "text" => VarKind::Text,
+                "number" => VarKind::Number,
+                "list" => VarKind::List,
+                "json" => VarKind::Json,
+                other => return Err(format!("tipo '{other}' no válido para '{name}'")),
+            };
+            vars.push(VarSpec {
+                name: name.to_string(),
+                kind,
+                optional,
+            });
+        }
+
+        let body = strip_standalone_tags(body);
+        let mut rest = body.as_str();
+        let nodes = parse_nodes(&mut rest, None)?;
+        let template = Template { vars, body: nodes };
+        template.check_names(&template.body)?;
+        Ok(template)
+    }
+
+    /// Toda variable usada en el texto tiene que estar declarada.
+    fn check_names(&self, nodes: &[Node]) -> Result<(), String> {
+        for node in nodes {
+            let name = match node {
+                Node::Text(_) => continue,
+                Node::Var(name) => name,
+                Node::Section { name, children, .. } => {
+                    self.check_names(children)?;
+                    name
+                }
+            };
+            if !self.vars.iter().any(|v| &v.name == name) {
+                return Err(format!("'{{{{{name}}}}}' no está declarada con @var"));
+            }
+        }
+        Ok(())
+    }
+
+    fn render(&self, vars: &Value) -> Result<String, AgentError> {
+        let empty = serde_json::Map::new();
+        let values = match vars {
+            Value::Object(map) => map,
+            Value::Null => &empty,
+            _ => {
+                return Err(AgentError::InvalidArguments(
+                    "las variables de la plantilla deben ser un objeto JSON".to_string(),
+                ))
+            }
+        };
+        if let Some(unknown) = values
+            .keys()
+            .find(|k| !self.vars.iter().any(|v| &v.name == *k))
+        {
+            return Err(AgentError::InvalidArguments(format!(
+                "la plantilla no declara la variable '{unknown}'"
+            )));
+        }
+
+        let mut rendered = HashMap::new();
+        for spec in &self.vars {
+            let value = values.get(&spec.name).filter(|v| !v.is_null());
+            let text = match (value, spec.kind) {
+                (None, _) if spec.optional => String::new(),
+                (None, _) => {
+                    return Err(AgentError::InvalidArguments(format!(
+                        "falta la variable '{}'",
+                        spec.name
+                    )))
+                }
+                (Some(Value::String(s)), VarKind::Text) => s.clone(),
+                (Some(Value::Number(n)), VarKind::Number) => n.to_string(),
+                (Some(Value::Array(items)), VarKind::List) => items
+                    .iter()
+                    .map(|item| match item {
+                        Value::String(s) => format!("- {s}"),
+                        other => format!("- {other}"),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                (Some(value), VarKind::Json) => {
+                    serde_json::to_string_pretty(value).unwrap_or_default()
+                }
+                (Some(value), kind) => {
+                    return Err(AgentError::InvalidArguments(format!(
+                        "la variable '{}' debe ser {} y es {value}",
+                        spec.name,
+                        kind.as_str()
+                    )))
+                }
+            };
+            rendered.insert(spec.name.as_str(), text);
+        }
+
+        let mut out = String::new();
+        render_nodes(&self.body, &rendered, &mut out);
+        Ok(out)
+    }
+}
+
+/// Quita el salto de línea de las etiquetas de sección que ocupan una línea entera, para
+/// que las secciones vacías no dejen líneas en blanco.
+fn strip_standalone_tags(body: &str) -> String {
+    body.split_inclusive('\n')
+        .map(|line| {
+            let tag = line.trim();
+            let standalone = tag.starts_with("{{")
+                && tag.ends_with("}}")
+                && tag[2..].starts_with(['#', '^', '/'])
+                && tag.matches("{{").count() == 1;
+            if standalone {
+                tag
+            } else {
+                line
+            }
+        })
+        .collect()
+}
+
+/// Lee nodos hasta el cierre de `section` (o el final del texto).
+fn parse_nodes(rest: &mut &str, section: Option<&str>) -> Result<Vec<Node>, String> {
+    let mut nodes = Vec::new();
+    loop {
+        let Some(start) = rest.find("{{") else {
+            if let Some(open) = section {
+                return Err(format!("falta '{{{{/{open}}}}}'"));
+            }
+            push_text(&mut nodes, rest);
+            *rest = "";
+            return Ok(nodes);
+        };
+        let end = rest[start..]
+            .find("}}")
+            .map(|e| start + e)
+            .ok_or("'{{' sin cerrar")?;
+        let tag = rest[start + 2..end].trim();
+        push_text(&mut nodes, &rest[..start]);
+        *rest = &rest[end + 2..];
+
+        if let Some(name) = tag.strip_prefix('/') {
+            return match section {
+                Some(open) if open == name.trim() => Ok(nodes),
+                _ => Err(format!("'{{{{/{}}}}}' sin abrir", name.trim())),
+            };
+        }
+        let (name, inverted) = match (tag.strip_prefix('#'), tag.strip_prefix('^')) {
+            (Some(name), _) => (name.trim(), Some(false)),
+            (_, Some(name)) => (name.trim(), Some(true)),
+            _ => (tag, None),
+        };
+        if name.is_empty() {
+            return Err("etiqueta '{{}}' vacía".to_string());
+        }
+        match inverted {
+            Some(inverted) => {
+                let children = parse_nodes(rest, Some(name))?;
+                nodes.push(Node::Section {
+                    name: name.to_string(),
+                    inverted,
+                    children,
+                });
+            }
+            None => nodes.push(Node::Var(name.to_string())),
+        }
+    }
+}
+
+fn push_text(nodes: &mut Vec<Node>, text: &str) {
+    if !text.is_empty() {
+        nodes.push(Node::Text(text.to_string()));
+    }
+}
+
+fn render_nodes(nodes: &[Node], vars: &HashMap<&str, String>, out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Var(name) => out.push_str(vars.get(name.as_str()).map_or("", String::as_str)),
+            Node::Section {
+                name,
+                inverted,
+                children,
+            } => {
+                let present = vars
+                    .get(name.as_str())
+                    .is_some_and(|v| !v.trim().is_empty());
+                if present != *inverted {
+                    render_nodes(children, vars, out);
+                }
+            }
+        }
+    }
+}
+
+/// Hash FNV-1a: estable entre ejecuciones, para repartir las variantes A/B.
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs;
+    use std::time::Duration;
+
+    const SOURCE: &str = "@description Prueba
+@var name text
+@var years number?
+@var skills list?
+@var extra json?
+---
+Hola {{name}}.
+{{#years}}
+Años: {{years}}
+{{/years}}
+{{^skills}}
+Sin habilidades.
+{{/skills}}
+{{#skills}}
+Habilidades:
+{{skills}}
+{{/skills}}
+{{extra}}
+";
+
+    fn render(vars: Value) -> Result<String, AgentError> {
+        Template::parse(SOURCE).unwrap().render(&vars)
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("prompts_{}_{name}", std::process::id()));
+        fs::create_dir_all(dir.join("demo")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn sections_render_without_blank_lines() {
+        assert_eq!(
+            render(json!({ "name": "Ana" })).unwrap(),
+            "Hola Ana.\nSin habilidades.\n"
+        );
+        assert_eq!(
+            render(json!({
+                "name": "Ana",
+                "years": 5,
+                "skills": ["Rust", "SQL"],
+                "extra": { "a": 1 }
+            }))
+            .unwrap(),
+            "Hola Ana.\nAños: 5\nHabilidades:\n- Rust\n- SQL\n{\n  \"a\": 1\n}"
+        );
+    }
+
+    #[test]
+    fn variables_must_be_declared_and_typed() {
+        let error = |vars| render(vars).unwrap_err().to_string();
+        assert_eq!(
+            error(json!({})),
+            "Argumentos no válidos: falta la variable 'name'"
+        );
+        assert_eq!(
+            error(json!({ "name": "Ana", "cv": "..." })),
+            "Argumentos no válidos: la plantilla no declara la variable 'cv'"
+        );
+        assert_eq!(
+            error(json!({ "name": "Ana", "years": "cinco" })),
+            "Argumentos no válidos: la variable 'years' debe ser number y es \"cinco\""
+        );
+        assert!(render(json!({ "name": "Ana", "years": null })).is_ok());
+    }
+
+    #[test]
+    fn invalid_templates_are_rejected() {
+        let error = |source| Template::parse(source).err().unwrap();
+        assert_eq!(
+            error("@var a text\nHola"),
+            "falta la línea '---' entre la cabecera y el texto"
+        );
+        assert_eq!(
+            error("@var a texto\n---\n{{a}}"),
+            "tipo 'texto' no válido para 'a'"
+        );
+        assert_eq!(
+            error("@var a text\n---\n{{b}}"),
+            "'{{b}}' no está declarada con @var"
+        );
+        assert_eq!(error("@var a text\n---\n{{#a}}x"), "falta '{{/a}}'");
+        assert_eq!(error("@var a text\n---\nx{{/a}}"), "'{{/a}}' sin abrir");
+        assert_eq!(error("---\n{{a"), "'{{' sin cerrar");
+    }
+
+    #[test]
+    fn a_key_always_gets_the_same_variant() {
+        let dir = temp_dir("variants");
+        fs::write(dir.join("demo/v1.prompt"), "---\nuno").unwrap();
+        fs::write(dir.join("demo/v2.prompt"), "---\ndos").unwrap();
+        fs::write(
+            dir.join("prompts.json"),
+            r#"{"demo": {"active": "v1", "variants": {"v1": 50, "v2": 50}}}"#,
+        )
+        .unwrap();
+        let registry = PromptRegistry::new(&dir);
+
+        let versions: Vec<String> = (0..50)
+            .map(|i| {
+                let key = format!("task-{i}");
+                let first = registry.render("demo", &key, &Value::Null).unwrap();
+                let again = registry.render("demo", &key, &Value::Null).unwrap();
+                assert_eq!(first.version, again.version);
+                assert_eq!(
+                    first.text,
+                    if first.version == "demo@v1" {
+                        "uno"
+                    } else {
+                        "dos"
+                    }
+                );
+                first.version
+            })
+            .collect();
+        assert!(versions.iter().any(|v| v == "demo@v1"));
+        assert!(versions.iter().any(|v| v == "demo@v2"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn manifest_is_reloaded_when_it_changes() {
+        let dir = temp_dir("reload");
+        fs::write(dir.join("demo/v1.prompt"), "---\nuno").unwrap();
+        fs::write(dir.join("demo/v2.prompt"), "---\ndos").unwrap();
+        let manifest = dir.join("prompts.json");
+        fs::write(&manifest, r#"{"demo": {"active": "v1"}}"#).unwrap();
+        let registry = PromptRegistry::new(&dir);
+        assert_eq!(
+            registry.render("demo", "k", &Value::Null).unwrap().text,
+            "uno"
+        );
+
+        fs::write(&manifest, r#"{"demo": {"active": "v2"}}"#).unwrap();
+        let later = SystemTime::now() + Duration::from_secs(5);
+        fs::File::options()
+            .write(true)
+            .open(&manifest)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        let rendered = registry.render("demo", "k", &Value::Null).unwrap();
+        assert_eq!(
+            (rendered.text.as_str(), rendered.version.as_str()),
+            ("dos", "demo@v2")
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn builtin_templates_are_the_fallback() {
+        let dir = temp_dir("builtin");
+        let registry = PromptRegistry::new(&dir);
+        let rendered = registry.render("cover_letter", "k", &Value::Null);
+        // La copia compilada existe aunque el directorio esté vacío: falla por las variables.
+        assert!(matches!(rendered, Err(AgentError::InvalidArguments(_))));
+        let summary = registry
+            .render("session_summary", "k", &Value::Null)
+            .unwrap();
+        assert_eq!(summary.version, "session_summary@v1");
+        assert!(matches!(
+            registry.render("desconocida", "k", &Value::Null),
+            Err(AgentError::Configuration(_))
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
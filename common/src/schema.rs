@@ -0,0 +1,368 @@
+//! Validación de JSON contra un JSON Schema (el subconjunto que se usa para pedir salidas
+//! estructuradas al LLM).
+//!
+//! Se admiten `type` (uno o varios), `properties`, `required`, `additionalProperties`
+//! (booleano o esquema), `items`, `enum`, `const`, `anyOf`, `oneOf`, `allOf`, `minimum`,
+//! `maximum`, `minLength`, `maxLength`, `minItems` y `maxItems`. El resto de palabras
+//! clave se ignoran. Los errores llevan la ruta del valor en formato JSON Pointer.
+
+use serde_json::{Map, Value};
+
+/// Problemas de `value` frente a `schema` (vacío si lo cumple), como
+/// `"/skills/0/years: se esperaba number"`.
+pub fn validate(schema: &Value, value: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    check(schema, value, "", &mut errors);
+    errors
+}
+
+fn check(schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    let schema = match schema {
+        Value::Bool(true) => return,
+        Value::Bool(false) => {
+            errors.push(format!("{}: no se admite ningún valor", at(path)));
+            return;
+        }
+        Value::Object(schema) => schema,
+        _ => return,
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|t| is_type(value, t)) {
+            errors.push(format!(
+                "{}: se esperaba {} y es {}",
+                at(path),
+                types.join(" o "),
+                type_name(value)
+            ));
+            return;
+        }
+    }
+
+    if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+        if !options.contains(value) {
+            let options: Vec<String> = options.iter().map(Value::to_string).collect();
+            errors.push(format!(
+                "{}: {value} no es uno de {}",
+                at(path),
+                options.join(", ")
+            ));
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            errors.push(format!("{}: debe ser {expected}", at(path)));
+        }
+    }
+
+    check_combinators(schema, value, path, errors);
+
+    match value {
+        Value::Object(object) => check_object(schema, object, path, errors),
+        Value::Array(items) => check_array(schema, items, path, errors),
+        Value::String(text) => {
+            let len = text.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+                if len < min {
+                    errors.push(format!("{}: longitud mínima {min}", at(path)));
+                }
+            }
+            if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+                if len > max {
+                    errors.push(format!("{}: longitud máxima {max}", at(path)));
+                }
+            }
+        }
+        Value::Number(number) => {
+            let number = number.as_f64().unwrap_or_default();
+            if let Some(min) = schema.get("minimum").and_then(Value::as_f64) {
+                if number < min {
+                    errors.push(format!("{}: {number} es menor que {min}", at(path)));
+                }
+            }
+            if let Some(max) = schema.get("maximum").and_then(Value::as_f64) {
+                if number > max {
+                    errors.push(format!("{}: {number} es mayor que {max}", at(path)));
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn check_combinators(
+    schema: &Map<String, Value>,
+    value: &Value,
+    path: &str,
+    errors: &mut Vec<String>,
+) {
+    if let Some(all) = schema.get("allOf").and_then(Value::as_array) {
+        for sub in all {
+            check(sub, value, path, errors);
+        }
+    }
+    if let Some(any) = schema.get("anyOf").and_then(Value::as_array) {
+        if !any
+            .iter()
+            .any(|sub| validate_at(sub, value, path).is_empty())
+        {
+            errors.push(format!(
+                "{}: no cumple ninguna de las opciones de anyOf",
+                at(path)
+            ));
+        }
+    }
+    if let Some(one) = schema.get("oneOf").and_then(Value::as_array) {
+        let matching = one
+            .iter()
+            .filter(|sub| validate_at(sub, value, path).is_empty())
+            .count();
+        if matching != 1 {
+            errors.push(format!(
+                "{}: debe cumplir exactamente una opción de oneOf y cumple {matching}",
+                at(path)
+            ));
+        }
+    }
+}
+
+fn check_object(
+    schema: &Map<String, Value>,
+    object: &Map<String, Value>,
+    path: &str,
+    errors: &mut Vec<String>,
+) {
+    if let Some(required) = schema.get("required").and_then(Value::as_array) {
+        for name in required.iter().filter_map(Value::as_str) {
+            if !object.contains_key(name) {
+                errors.push(format!("{}: falta la propiedad '{name}'", at(path)));
+            }
+        }
+    }
+    let properties = schema.get("properties").and_then(Value::as_object);
+    for (name, field) in object {
+        let field_path = format!("{path}/{}", pointer_escape(name));
+        match properties.and_then(|p| p.get(name)) {
+            Some(sub) => check(sub, field, &field_path, errors),
+            None => match schema.get("additionalProperties") {
+                Some(Value::Bool(false)) => {
+                    errors.push(format!("{}: propiedad '{name}' no permitida", at(path)))
+                }
+                Some(sub @ Value::Object(_)) => check(sub, field, &field_path, errors),
+                _ => {}
+            },
+        }
+    }
+}
+
+fn check_array(schema: &Map<String, Value>, items: &[Value], path: &str, errors: &mut Vec<String>) {
+    let len = items.len() as u64;
+    if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+        if len < min {
+            errors.push(format!(
+                "{}: al menos {min} elementos y tiene {len}",
+                at(path)
+            ));
+        }
+    }
+    if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+        if len > max {
+            errors.push(format!(
+                "{}: como mucho {max} elementos y tiene {len}",
+                at(path)
+            ));
+        }
+    }
+    if let Some(sub) = schema.get("items") {
+        for (i, item) in items.iter().enumerate() {
+            check(sub, item, &format!("{path}/{i}"), errors);
+        }
+    }
+}
+
+fn validate_at(schema: &Value, value: &Value, path: &str) -> Vec<String> {
+    let mut errors = Vec::new();
+    check(schema, value, path, &mut errors);
+    errors
+}
+
+fn is_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0)
+        }
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn at(path: &str) -> &str {
+    if path.is_empty() {
+        "/"
+    } else {
+        path
+    }
+}
+
+fn pointer_escape(name: &str) -> String {
+    name.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn type_accepts_one_or_several() {
+        assert!(validate(&json!({"type": "string"}), &json!("a")).is_empty());
+        assert_eq!(
+            validate(&json!({"type": "string"}), &json!(1)),
+            vec!["/: se esperaba string y es number"]
+        );
+        let nullable = json!({"type": ["number", "null"]});
+        assert!(validate(&nullable, &json!(null)).is_empty());
+        assert_eq!(
+            validate(&nullable, &json!("1")),
+            vec!["/: se esperaba number o null y es string"]
+        );
+        assert!(validate(&json!({"type": "integer"}), &json!(3.0)).is_empty());
+        assert!(!validate(&json!({"type": "integer"}), &json!(3.5)).is_empty());
+    }
+
+    #[test]
+    fn required_and_additional_properties() {
+        let schema = json!({
+            "type": "object",
+            "properties": { "name": { "type": "string" } },
+            "required": ["name", "years"],
+            "additionalProperties": false
+        });
+        assert_eq!(
+            validate(&schema, &json!({"name": "Rust", "level": 3})),
+            vec![
+                "/: falta la propiedad 'years'",
+                "/: propiedad 'level' no permitida"
+            ]
+        );
+
+        let typed = json!({ "additionalProperties": { "type": "number" } });
+        assert!(validate(&typed, &json!({"a": 1, "b": 2.5})).is_empty());
+        assert_eq!(
+            validate(&typed, &json!({"a/b": "x"})),
+            vec!["/a~1b: se esperaba number y es string"]
+        );
+        assert!(validate(&json!({}), &json!({"any": "thing"})).is_empty());
+    }
+
+    #[test]
+    fn enum_and_const() {
+        let schema = json!({ "enum": ["remote", "hybrid"] });
+        assert!(validate(&schema, &json!("remote")).is_empty());
+        assert_eq!(
+            validate(&schema, &json!("onsite")),
+            vec![r#"/: "onsite" no es uno de "remote", "hybrid""#]
+        );
+        assert_eq!(
+            validate(&json!({ "const": 1 }), &json!(2)),
+            vec!["/: debe ser 1"]
+        );
+    }
+
+    #[test]
+    fn any_of_and_one_of() {
+        let any = json!({ "anyOf": [{ "type": "string" }, { "minimum": 0 }] });
+        assert!(validate(&any, &json!("a")).is_empty());
+        assert!(validate(&any, &json!(5)).is_empty());
+        assert_eq!(
+            validate(&any, &json!(-1)),
+            vec!["/: no cumple ninguna de las opciones de anyOf"]
+        );
+
+        let one = json!({ "oneOf": [{ "type": "number" }, { "minimum": 0 }] });
+        assert!(validate(&one, &json!(-1)).is_empty());
+        assert_eq!(
+            validate(&one, &json!(1)),
+            vec!["/: debe cumplir exactamente una opción de oneOf y cumple 2"]
+        );
+        assert_eq!(
+            validate(&json!({ "oneOf": [{ "type": "string" }] }), &json!(1)),
+            vec!["/: debe cumplir exactamente una opción de oneOf y cumple 0"]
+        );
+    }
+
+    #[test]
+    fn items_report_their_path() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "skills": {
+                    "type": "array",
+                    "maxItems": 2,
+                    "items": {
+                        "type": "object",
+                        "properties": { "years": { "type": "number", "maximum": 50 } }
+                    }
+                }
+            }
+        });
+        let value = json!({ "skills": [{ "years": 3 }, { "years": "5" }, { "years": 80 }] });
+        assert_eq!(
+            validate(&schema, &value),
+            vec![
+                "/skills: como mucho 2 elementos y tiene 3",
+                "/skills/1/years: se esperaba number y es string",
+                "/skills/2/years: 80 es mayor que 50",
+            ]
+        );
+    }
+
+    #[test]
+    fn string_and_number_bounds() {
+        let text = json!({ "minLength": 2, "maxLength": 3 });
+        assert_eq!(validate(&text, &json!("a")), vec!["/: longitud mínima 2"]);
+        assert!(validate(&text, &json!("añé")).is_empty());
+        assert_eq!(
+            validate(&text, &json!("abcd")),
+            vec!["/: longitud máxima 3"]
+        );
+        assert_eq!(
+            validate(&json!({ "minimum": 0 }), &json!(-2)),
+            vec!["/: -2 es menor que 0"]
+        );
+        assert_eq!(
+            validate(&json!({ "minItems": 1 }), &json!([])),
+            vec!["/: al menos 1 elementos y tiene 0"]
+        );
+    }
+
+    #[test]
+    fn boolean_schemas() {
+        assert!(validate(&json!(true), &json!({"a": 1})).is_empty());
+        assert_eq!(
+            validate(&json!(false), &json!(1)),
+            vec!["/: no se admite ningún valor"]
+        );
+    }
+}
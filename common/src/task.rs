@@ -1,3 +1,4 @@
+use crate::offers::OfferFilters;
 use serde::{Deserialize, Serialize};
 
 /// Representa el estado actual de una tarea compleja.
@@ -7,6 +8,7 @@ pub enum TaskStatus {
     InProgress(String), // Mensaje de estado
     Completed(String),  // Ruta al archivo de resultados
     Failed(String),     // Mensaje de error
+    Cancelled(String),  // Motivo de la cancelación
 }
 
 /// Define una tarea inicial enviada por el UI al Coordinador.
@@ -15,4 +17,75 @@ pub struct InitialTask {
     pub task_id: String,
     pub task_name: String,
     pub pdf_content: Vec<u8>,
-}
\ No newline at end of file
+    /// Objetivo en lenguaje natural. Si está presente, el Coordinador planifica los
+    /// pasos con el LLM en lugar de ejecutar el pipeline fijo CV → ofertas.
+    #[serde(default)]
+    pub goal: Option<String>,
+    /// Perfil de búsqueda guardado que origina la tarea (búsquedas programadas). Si está
+    /// presente, el CV y las consultas salen del perfil y `pdf_content` puede ir vacío.
+    #[serde(default)]
+    pub profile_id: Option<String>,
+    /// Filtros duros que se aplican a las ofertas antes de puntuarlas.
+    #[serde(default)]
+    pub filters: OfferFilters,
+    /// Si está presente, se escribe una carta de presentación para las mejores ofertas.
+    #[serde(default)]
+    pub cover_letters: Option<CoverLetterOptions>,
+    /// Si está presente, se analizan las carencias del CV frente a las mejores ofertas.
+    #[serde(default)]
+    pub gap_analysis: Option<GapAnalysisOptions>,
+}
+
+/// Preselección de ofertas para el análisis de carencias.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GapAnalysisOptions {
+    /// Número de ofertas (las de mayor puntuación) que se analizan.
+    #[serde(default = "default_top")]
+    pub top: usize,
+    /// Puntuación mínima para analizar la oferta.
+    #[serde(default)]
+    pub min_score: Option<u8>,
+}
+
+/// Preselección de ofertas y estilo de las cartas de presentación.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CoverLetterOptions {
+    /// Número de ofertas (las de mayor puntuación) que reciben carta.
+    #[serde(default = "default_top")]
+    pub top: usize,
+    /// Puntuación mínima para recibir carta.
+    #[serde(default)]
+    pub min_score: Option<u8>,
+    /// Tono de la carta (p. ej. "formal", "cercano").
+    #[serde(default = "default_tone")]
+    pub tone: String,
+    /// Idioma en que se escribe la carta.
+    #[serde(default = "default_language")]
+    pub language: String,
+    /// Longitud máxima aproximada, en palabras.
+    #[serde(default = "default_max_words")]
+    pub max_words: usize,
+}
+
+fn default_top() -> usize {
+    3
+}
+
+fn default_tone() -> String {
+    "profesional y cercano".to_string()
+}
+
+fn default_language() -> String {
+    "español".to_string()
+}
+
+fn default_max_words() -> usize {
+    300
+}
+
+/// Actualización de estado publicada por el Coordinador en `agents.status`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TaskUpdate {
+    pub task_id: String,
+    pub status: TaskStatus,
+}
@@ -8,8 +8,7 @@ fn main() {
     let subscriber = FmtSubscriber::builder()
         .with_max_level(Level::INFO)
         .finish();
-    tracing::subscriber::set_global_default(subscriber)
-        .expect("setting default subscriber failed");
+    tracing::subscriber::set_global_default(subscriber).expect("setting default subscriber failed");
 
     info!("Iniciando el sistema de agentes...");
 
@@ -53,4 +52,4 @@ fn main() {
     for handle in agent_handles {
         let _ = handle.join();
     }
-}
\ No newline at end of file
+}